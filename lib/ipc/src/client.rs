@@ -0,0 +1,304 @@
+//! A blocking client.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    methods, AccountParams, AuthParams, FlagParams, LoadSavefileParams, Outcome, Position, Request,
+    Response, RpcError, SetFlagParams, Stats,
+};
+
+/// How long to wait for a response. The server waits up to 5 seconds for the
+/// tool itself.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum ClientError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Rpc(RpcError),
+    /// The server closed the connection.
+    Closed,
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(e) => write!(f, "IPC connection error: {e}"),
+            ClientError::Json(e) => write!(f, "Invalid IPC message: {e}"),
+            ClientError::Rpc(e) => write!(f, "{e}"),
+            ClientError::Closed => write!(f, "The tool closed the IPC connection"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ClientError {
+    fn from(e: serde_json::Error) -> Self {
+        ClientError::Json(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+pub struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    next_id: u64,
+}
+
+impl Client {
+    /// Connects and authenticates with the token of the tool's config.
+    pub fn connect(address: impl ToSocketAddrs, token: &str) -> Result<Client> {
+        let writer = TcpStream::connect(address)?;
+        writer.set_read_timeout(Some(READ_TIMEOUT))?;
+        writer.set_nodelay(true)?;
+        let mut client = Client { reader: BufReader::new(writer.try_clone()?), writer, next_id: 1 };
+        client.call::<bool>(methods::AUTH, AuthParams { token: token.to_string() })?;
+        Ok(client)
+    }
+
+    /// Calls `method` and waits for its result.
+    pub fn call<T: DeserializeOwned>(&mut self, method: &str, params: impl Serialize) -> Result<T> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let request = Request {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(id)),
+            method: method.to_string(),
+            params: serde_json::to_value(params)?,
+        };
+        let mut line = serde_json::to_string(&request)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(ClientError::Closed);
+            }
+            let response: Response = serde_json::from_str(&line)?;
+            // Skip answers to calls that timed out before.
+            if response.id != json!(id) {
+                continue;
+            }
+            return match response.outcome {
+                Outcome::Result(value) => Ok(serde_json::from_value(value)?),
+                Outcome::Error(e) => Err(ClientError::Rpc(e)),
+            };
+        }
+    }
+
+    /// Sends a notification, which the server carries out without answering.
+    pub fn notify(&mut self, method: &str, params: impl Serialize) -> Result<()> {
+        let request = Request {
+            jsonrpc: "2.0".to_string(),
+            id: None,
+            method: method.to_string(),
+            params: serde_json::to_value(params)?,
+        };
+        let mut line = serde_json::to_string(&request)?;
+        line.push('\n');
+        Ok(self.writer.write_all(line.as_bytes())?)
+    }
+
+    pub fn flag(&mut self, name: &str) -> Result<Option<bool>> {
+        self.call(methods::FLAG_GET, FlagParams { name: name.to_string() })
+    }
+
+    pub fn set_flag(&mut self, name: &str, state: bool) -> Result<bool> {
+        self.call(methods::FLAG_SET, SetFlagParams { name: name.to_string(), state })
+    }
+
+    pub fn igt(&mut self) -> Result<Option<u32>> {
+        self.call(methods::IGT_GET, Value::Null)
+    }
+
+    pub fn save_position(&mut self) -> Result<Option<Position>> {
+        self.call(methods::POSITION_SAVE, Value::Null)
+    }
+
+    pub fn load_position(&mut self, position: &Position) -> Result<bool> {
+        self.call(methods::POSITION_LOAD, position)
+    }
+
+    pub fn stats(&mut self) -> Result<Option<Stats>> {
+        self.call(methods::STATS_GET, Value::Null)
+    }
+
+    pub fn set_stats(&mut self, stats: &Stats) -> Result<bool> {
+        self.call(methods::STATS_SET, stats)
+    }
+
+    pub fn savefiles(&mut self, account: Option<&str>) -> Result<Vec<String>> {
+        self.call(methods::SAVEFILE_LIST, AccountParams { account: account.map(String::from) })
+    }
+
+    pub fn load_savefile(&mut self, save: &str, quitout: bool) -> Result<()> {
+        let params = LoadSavefileParams { save: save.to_string(), quitout, account: None };
+        self.call(methods::SAVEFILE_LOAD, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+    use crate::{params, result, Handler, Server};
+
+    const TOKEN: &str = "hunter2";
+
+    /// Game state behind a stub server.
+    struct StubGame {
+        no_death: bool,
+        igt: Option<u32>,
+        position: Position,
+        stats: Stats,
+        loaded: Vec<(String, bool)>,
+    }
+
+    impl Handler for StubGame {
+        fn handle(&mut self, method: &str, p: Value) -> std::result::Result<Value, RpcError> {
+            match method {
+                methods::FLAG_GET => {
+                    let FlagParams { name } = params(p)?;
+                    result((name == "no_death").then_some(self.no_death))
+                }
+                methods::FLAG_SET => {
+                    let SetFlagParams { name, state } = params(p)?;
+                    if name != "no_death" {
+                        return Err(RpcError::tool(format!("Unknown flag {name}")));
+                    }
+                    self.no_death = state;
+                    result(true)
+                }
+                methods::IGT_GET => result(self.igt),
+                methods::POSITION_SAVE => result(Some(self.position)),
+                methods::POSITION_LOAD => {
+                    let position: Position = params(p)?;
+                    if position.map_id != self.position.map_id {
+                        return Err(RpcError::tool("Position saved in another map"));
+                    }
+                    self.position = position;
+                    result(true)
+                }
+                methods::STATS_GET => result(Some(&self.stats)),
+                methods::STATS_SET => {
+                    let stats: Stats = params(p)?;
+                    self.stats.level = stats.level.or(self.stats.level);
+                    self.stats.vitality = stats.vitality.or(self.stats.vitality);
+                    result(true)
+                }
+                methods::SAVEFILE_LIST => result(["any%/asylum.sl2", "any%/taurus.sl2"]),
+                methods::SAVEFILE_LOAD => {
+                    let LoadSavefileParams { save, quitout, .. } = params(p)?;
+                    self.loaded.push((save, quitout));
+                    result(())
+                }
+                method => Err(RpcError::method_not_found(method)),
+            }
+        }
+    }
+
+    /// Serves a stub game from a thread polling like the render loop does.
+    fn stub_server() -> (String, Arc<AtomicBool>, thread::JoinHandle<StubGame>) {
+        let server = Server::spawn("127.0.0.1:0", TOKEN).unwrap();
+        let address = server.address().to_string();
+        let done = Arc::new(AtomicBool::new(false));
+        let stop = done.clone();
+        let handle = thread::spawn(move || {
+            let mut game = StubGame {
+                no_death: false,
+                igt: Some(61500),
+                position: Position { map_id: Some(0x0a010000), pos: [0.; 3], angle: 0. },
+                stats: Stats::default(),
+                loaded: Vec::new(),
+            };
+            while !stop.load(Ordering::Relaxed) {
+                server.poll(&mut game);
+                thread::sleep(Duration::from_millis(5));
+            }
+            game
+        });
+        (address, done, handle)
+    }
+
+    #[test]
+    fn test_client() {
+        let (address, done, handle) = stub_server();
+        let mut client = Client::connect(&address, TOKEN).unwrap();
+
+        assert_eq!(client.flag("no_death").unwrap(), Some(false));
+        assert!(client.set_flag("no_death", true).unwrap());
+        assert_eq!(client.flag("no_death").unwrap(), Some(true));
+        assert_eq!(client.flag("gravity").unwrap(), None);
+        assert_eq!(client.igt().unwrap(), Some(61500));
+
+        let mut position = client.save_position().unwrap().unwrap();
+        position.pos = [1., 2., 3.];
+        assert!(client.load_position(&position).unwrap());
+        assert_eq!(client.save_position().unwrap(), Some(position));
+
+        client.set_stats(&Stats { level: Some(12), ..Default::default() }).unwrap();
+        client.set_stats(&Stats { vitality: Some(20), ..Default::default() }).unwrap();
+        let stats = client.stats().unwrap().unwrap();
+        assert_eq!((stats.level, stats.vitality, stats.faith), (Some(12), Some(20), None));
+
+        assert_eq!(client.savefiles(None).unwrap(), ["any%/asylum.sl2", "any%/taurus.sl2"]);
+        client.load_savefile("any%/taurus.sl2", true).unwrap();
+        client
+            .notify(methods::FLAG_SET, SetFlagParams { name: "no_death".into(), state: false })
+            .unwrap();
+        assert_eq!(client.flag("no_death").unwrap(), Some(false));
+
+        done.store(true, Ordering::Relaxed);
+        let game = handle.join().unwrap();
+        assert_eq!(game.loaded, [("any%/taurus.sl2".to_string(), true)]);
+    }
+
+    #[test]
+    fn test_client_errors() {
+        let (address, done, _) = stub_server();
+        let mut client = Client::connect(&address, TOKEN).unwrap();
+
+        let e = client.set_flag("gravity", true).unwrap_err();
+        assert!(matches!(e, ClientError::Rpc(RpcError { code: RpcError::TOOL_ERROR, .. })));
+
+        let elsewhere = Position { map_id: Some(0x0e000000), pos: [0.; 3], angle: 0. };
+        assert_eq!(
+            client.load_position(&elsewhere).unwrap_err().to_string(),
+            "Position saved in another map (-32000)"
+        );
+
+        let e = client.call::<Value>("stats.delete", Value::Null).unwrap_err();
+        assert!(matches!(e, ClientError::Rpc(RpcError { code: RpcError::METHOD_NOT_FOUND, .. })));
+
+        let e = client.call::<bool>(methods::FLAG_SET, json!({ "name": 3 })).unwrap_err();
+        assert!(matches!(e, ClientError::Rpc(RpcError { code: RpcError::INVALID_PARAMS, .. })));
+
+        let e = Client::connect(&address, "hunter3").err().unwrap();
+        assert!(matches!(e, ClientError::Rpc(RpcError { code: RpcError::UNAUTHORIZED, .. })));
+
+        // Once the server is dropped, calls fail instead of hanging.
+        done.store(true, Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(50));
+        assert!(client.igt().is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! Control of the tool by external programs.
+//!
+//! The tool runs a JSON-RPC 2.0 server on a localhost TCP port, with one
+//! message per line. Every connection starts with an [`methods::AUTH`] call
+//! carrying the token of the tool's config. This crate has the protocol, the server loop and a
+//! client, and doesn't depend on the game, so that it builds and is tested on
+//! any platform.
+
+pub mod client;
+pub mod server;
+
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub use client::{Client, ClientError};
+pub use server::{Handler, Server};
+
+/// Default address of the server. Only loopback addresses are accepted.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:16835";
+
+/// Method names.
+pub mod methods {
+    /// `{ token }` → `true`. Must be the first call of a connection; a wrong
+    /// token closes it. The server answers it itself.
+    pub const AUTH: &str = "auth";
+    /// `{ name }` → the state of a flag of the config, e.g. `no_death`, or
+    /// `null` if it can't be read.
+    pub const FLAG_GET: &str = "flag.get";
+    /// `{ name, state }` → whether the flag was written.
+    pub const FLAG_SET: &str = "flag.set";
+    /// → IGT in milliseconds, or `null`.
+    pub const IGT_GET: &str = "igt.get";
+    /// → the current [`Position`](crate::Position), or `null`.
+    pub const POSITION_SAVE: &str = "position.save";
+    /// [`Position`](crate::Position) → whether it was written. Positions of
+    /// another map are refused.
+    pub const POSITION_LOAD: &str = "position.load";
+    /// → the character's [`Stats`](crate::Stats), or `null`.
+    pub const STATS_GET: &str = "stats.get";
+    /// [`Stats`](crate::Stats) → whether they were written. Missing stats are
+    /// left as they are.
+    pub const STATS_SET: &str = "stats.set";
+    /// `{ account? }` → the saves of the practice library.
+    pub const SAVEFILE_LIST: &str = "savefile.list";
+    /// `{ save, quitout?, account? }` → `null` once the save replaced the
+    /// game's, after a backup. `save` must be one of `savefile.list`.
+    pub const SAVEFILE_LOAD: &str = "savefile.load";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthParams {
+    pub token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlagParams {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetFlagParams {
+    pub name: String,
+    pub state: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountParams {
+    /// Steam account ID; the first account when missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoadSavefileParams {
+    /// Path of the save in the practice library, as listed by
+    /// [`methods::SAVEFILE_LIST`].
+    pub save: String,
+    /// Quit out after loading, so that the game picks the save up.
+    #[serde(default)]
+    pub quitout: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+}
+
+/// A position of the player, with the map it is in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub map_id: Option<u32>,
+    pub pos: [f32; 3],
+    pub angle: f32,
+}
+
+/// Character stats. Missing stats are left as they are when writing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub souls: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vitality: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attunement: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endurance: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strength: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dexterity: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resistance: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intelligence: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub faith: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub humanity: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    /// Missing for notifications, which get no response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub params: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Result(Value),
+    Error(RpcError),
+}
+
+impl Response {
+    pub fn new(id: Value, result: Result<Value, RpcError>) -> Self {
+        let outcome = match result {
+            Ok(value) => Outcome::Result(value),
+            Err(e) => Outcome::Error(e),
+        };
+        Response { jsonrpc: "2.0".to_string(), id, outcome }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
+    /// The call was valid, but the tool couldn't carry it out.
+    pub const TOOL_ERROR: i64 = -32000;
+    /// The connection didn't start with an `auth` call with the right token.
+    pub const UNAUTHORIZED: i64 = -32001;
+
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError { code, message: message.into() }
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        RpcError::new(RpcError::METHOD_NOT_FOUND, format!("Unknown method {method}"))
+    }
+
+    pub fn tool(message: impl Into<String>) -> Self {
+        RpcError::new(RpcError::TOOL_ERROR, message)
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// Parses the params of a call. Missing params parse like an empty object.
+pub fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { Value::Object(Default::default()) } else { params };
+    serde_json::from_value(params)
+        .map_err(|e| RpcError::new(RpcError::INVALID_PARAMS, e.to_string()))
+}
+
+/// Serializes the result of a call.
+pub fn result<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(RpcError::INTERNAL_ERROR, e.to_string()))
+}
@@ -0,0 +1,406 @@
+//! The server loop.
+//!
+//! Connections are served on threads of their own, but calls are handed over
+//! to the owner of the [`Server`], which handles them when it polls, e.g.
+//! once per frame on the render thread.
+//!
+//! A connection must start with an `auth` call carrying the token, and is
+//! closed on the first line that isn't a JSON-RPC 2.0 request. This keeps out
+//! e.g. a web page making the browser post to the port: its HTTP headers end
+//! the connection before any call reaches the game. Lines longer than
+//! [`MAX_LINE_LEN`] close the connection too, before they fill up memory.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use serde_json::Value;
+
+use crate::{methods, params, AuthParams, Request, Response, RpcError};
+
+/// How often the threads check whether the server was dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a call may wait for the owner to poll.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest line accepted, without its newline.
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// Carries out calls.
+pub trait Handler {
+    fn handle(&mut self, method: &str, params: Value) -> Result<Value, RpcError>;
+}
+
+struct Call {
+    method: String,
+    params: Value,
+    reply: Sender<Result<Value, RpcError>>,
+}
+
+/// Handle to the server threads, which stop once it is dropped.
+pub struct Server {
+    address: SocketAddr,
+    calls: Receiver<Call>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Server {
+    /// Listens on `address`, which must be a loopback address, for clients
+    /// that know `token`.
+    pub fn spawn(address: impl ToSocketAddrs, token: &str) -> Result<Server, String> {
+        if token.is_empty() {
+            return Err("Not serving IPC without a token".to_string());
+        }
+        let address = address
+            .to_socket_addrs()
+            .map_err(|e| format!("Invalid IPC address: {e}"))?
+            .next()
+            .ok_or("Invalid IPC address")?;
+        if !address.ip().is_loopback() {
+            return Err(format!("Not serving IPC on {address}: only localhost is allowed"));
+        }
+
+        let listener =
+            TcpListener::bind(address).map_err(|e| format!("Couldn't listen on {address}: {e}"))?;
+        let address = listener.local_addr().map_err(|e| e.to_string())?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        let (tx, calls) = crossbeam_channel::unbounded();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let token = token.to_string();
+        thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let tx = tx.clone();
+                        let stopped = stopped.clone();
+                        let token = token.clone();
+                        thread::spawn(move || serve(stream, tx, stopped, token));
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                    Err(_) => return,
+                }
+            }
+        });
+
+        Ok(Server { address, calls, stop })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Handles the calls received since the last poll.
+    pub fn poll(&self, handler: &mut impl Handler) {
+        for call in self.calls.try_iter() {
+            call.reply.send(handler.handle(&call.method, call.params)).ok();
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn serve(stream: TcpStream, calls: Sender<Call>, stop: Arc<AtomicBool>, token: String) {
+    // Accepted sockets may inherit non-blocking mode from the listener.
+    if stream.set_nonblocking(false).is_err()
+        || stream.set_read_timeout(Some(POLL_INTERVAL)).is_err()
+    {
+        return;
+    }
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reply = |response: Response| {
+        let Ok(mut response) = serde_json::to_string(&response) else {
+            return false;
+        };
+        response.push('\n');
+        writer.write_all(response.as_bytes()).is_ok()
+    };
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    let mut authenticated = false;
+
+    while !stop.load(Ordering::Relaxed) {
+        // A timeout keeps what was read so far in `line`, which stays within
+        // the limit until it is handled or refused.
+        let limit = (MAX_LINE_LEN + 1 - line.len()) as u64;
+        match reader.by_ref().take(limit).read_until(b'\n', &mut line) {
+            Ok(0) => return,
+            Ok(_) if line.ends_with(b"\n") => {}
+            Ok(_) if line.len() > MAX_LINE_LEN => {
+                let error = RpcError::new(RpcError::INVALID_REQUEST, "Line too long");
+                reply(Response::new(Value::Null, Err(error)));
+                return;
+            }
+            Ok(_) => continue,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                continue
+            }
+            Err(_) => return,
+        }
+
+        let request = String::from_utf8_lossy(&line).into_owned();
+        line.clear();
+        if request.trim().is_empty() {
+            continue;
+        }
+
+        let request = match parse(&request) {
+            Ok(request) => request,
+            Err(response) => {
+                reply(response);
+                return;
+            }
+        };
+
+        let result = if request.method == methods::AUTH {
+            match params::<AuthParams>(request.params) {
+                Ok(AuthParams { token: t }) if t == token => {
+                    authenticated = true;
+                    Ok(Value::Bool(true))
+                }
+                _ => {
+                    authenticated = false;
+                    Err(RpcError::new(RpcError::UNAUTHORIZED, "Wrong token"))
+                }
+            }
+        } else if authenticated {
+            call(&calls, request.method, request.params)
+        } else {
+            Err(RpcError::new(RpcError::UNAUTHORIZED, "The first call must be auth"))
+        };
+
+        let failed = !authenticated;
+        let sent = match request.id {
+            Some(id) => reply(Response::new(id, result)),
+            None => true,
+        };
+        if failed || !sent {
+            return;
+        }
+    }
+}
+
+/// Hands a call over to the owner of the server and waits for its result.
+fn call(calls: &Sender<Call>, method: String, params: Value) -> Result<Value, RpcError> {
+    let (reply, result) = crossbeam_channel::bounded(1);
+    calls
+        .send(Call { method, params, reply })
+        .map_err(|_| RpcError::new(RpcError::INTERNAL_ERROR, "The tool has stopped"))?;
+    result.recv_timeout(REPLY_TIMEOUT).map_err(|e| match e {
+        RecvTimeoutError::Timeout => {
+            RpcError::new(RpcError::INTERNAL_ERROR, "The tool didn't answer in time")
+        }
+        RecvTimeoutError::Disconnected => {
+            RpcError::new(RpcError::INTERNAL_ERROR, "The tool has stopped")
+        }
+    })?
+}
+
+/// Parses a line of JSON-RPC 2.0. Returns the error response to send before
+/// closing the connection if it isn't a request.
+pub(crate) fn parse(line: &str) -> Result<Request, Response> {
+    let value = serde_json::from_str::<Value>(line).map_err(|e| {
+        Response::new(Value::Null, Err(RpcError::new(RpcError::PARSE_ERROR, e.to_string())))
+    })?;
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    match serde_json::from_value::<Request>(value) {
+        Ok(request) if request.jsonrpc == "2.0" => Ok(request),
+        Ok(_) => Err(Response::new(
+            id,
+            Err(RpcError::new(RpcError::INVALID_REQUEST, "Only JSON-RPC 2.0 is supported")),
+        )),
+        Err(e) => {
+            Err(Response::new(id, Err(RpcError::new(RpcError::INVALID_REQUEST, e.to_string()))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::Outcome;
+
+    const TOKEN: &str = "hunter2";
+
+    fn parse_json(line: &str) -> Result<Request, Value> {
+        parse(line).map_err(|response| serde_json::to_value(response).unwrap())
+    }
+
+    /// Records the calls that reach the tool.
+    #[derive(Default)]
+    struct Calls(Vec<String>);
+
+    impl Handler for Calls {
+        fn handle(&mut self, method: &str, _: Value) -> Result<Value, RpcError> {
+            self.0.push(method.to_string());
+            Ok(Value::Null)
+        }
+    }
+
+    /// Sends `lines` on a new connection and returns the responses, once the
+    /// server hangs up. The server is polled meanwhile.
+    fn exchange(server: &Server, calls: &mut Calls, lines: &[String]) -> Vec<Response> {
+        let mut stream = TcpStream::connect(server.address()).unwrap();
+        stream.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+        stream.write_all(lines.concat().as_bytes()).unwrap();
+
+        let mut received = Vec::new();
+        let mut buf = [0; 1024];
+        for _ in 0..500 {
+            server.poll(calls);
+            match stream.read(&mut buf) {
+                Ok(0) => break,
+                // Lines the server didn't read when it hung up may reset the
+                // connection.
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => break,
+                Ok(n) => received.extend_from_slice(&buf[..n]),
+                Err(e)
+                    if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+                Err(e) => panic!("{e}"),
+            }
+        }
+        String::from_utf8(received)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> String {
+        format!("{}\n", json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+    }
+
+    fn error_code(response: &Response) -> Option<i64> {
+        match &response.outcome {
+            Outcome::Error(e) => Some(e.code),
+            Outcome::Result(_) => None,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let request =
+            parse_json(r#"{"jsonrpc": "2.0", "id": 1, "method": "echo", "params": [1, 2]}"#)
+                .unwrap();
+        assert_eq!(
+            (request.id, request.method, request.params),
+            (Some(json!(1)), "echo".into(), json!([1, 2]))
+        );
+
+        let request = parse_json(r#"{"jsonrpc": "2.0", "id": "a", "method": "echo"}"#).unwrap();
+        assert_eq!((request.id, request.params), (Some(json!("a")), Value::Null));
+
+        // Notifications have no id.
+        let request = parse_json(r#"{"jsonrpc": "2.0", "method": "echo"}"#).unwrap();
+        assert_eq!(request.id, None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error_code = |line: &str| parse_json(line).unwrap_err()["error"]["code"].as_i64();
+
+        assert_eq!(error_code("{"), Some(RpcError::PARSE_ERROR));
+        assert_eq!(error_code("POST / HTTP/1.1"), Some(RpcError::PARSE_ERROR));
+        assert_eq!(
+            error_code(r#"{"jsonrpc": "1.0", "id": 1, "method": "echo"}"#),
+            Some(RpcError::INVALID_REQUEST)
+        );
+        assert_eq!(error_code(r#"{"jsonrpc": "2.0", "id": 1}"#), Some(RpcError::INVALID_REQUEST));
+        assert_eq!(parse_json(r#"{"jsonrpc": "2.0", "id": 7}"#).unwrap_err()["id"], 7);
+    }
+
+    #[test]
+    fn test_auth() {
+        let server = Server::spawn("127.0.0.1:0", TOKEN).unwrap();
+        let mut calls = Calls::default();
+
+        let auth = request(1, methods::AUTH, json!({ "token": TOKEN }));
+        let call = request(2, "igt.get", Value::Null);
+        let responses = exchange(&server, &mut calls, &[auth.clone(), call.clone(), "{\n".into()]);
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0].outcome, Outcome::Result(json!(true)));
+        assert_eq!(responses[1].outcome, Outcome::Result(Value::Null));
+        assert_eq!(error_code(&responses[2]), Some(RpcError::PARSE_ERROR));
+        assert_eq!(calls.0, ["igt.get"]);
+
+        // Without auth, or with the wrong token, nothing reaches the tool.
+        let mut calls = Calls::default();
+        let responses = exchange(&server, &mut calls, &[call.clone(), auth.clone()]);
+        assert_eq!(
+            responses.iter().map(error_code).collect::<Vec<_>>(),
+            [Some(RpcError::UNAUTHORIZED)]
+        );
+
+        let wrong = request(1, methods::AUTH, json!({ "token": "hunter3" }));
+        let responses = exchange(&server, &mut calls, &[wrong, call.clone()]);
+        assert_eq!(
+            responses.iter().map(error_code).collect::<Vec<_>>(),
+            [Some(RpcError::UNAUTHORIZED)]
+        );
+        assert!(calls.0.is_empty());
+
+        assert!(Server::spawn("127.0.0.1:0", "").is_err());
+    }
+
+    #[test]
+    fn test_closes_on_http() {
+        let server = Server::spawn("127.0.0.1:0", TOKEN).unwrap();
+        let mut calls = Calls::default();
+
+        // What a browser sends for a cross-site form post of a JSON body.
+        let auth = request(1, methods::AUTH, json!({ "token": TOKEN }));
+        let call = request(2, "flag.set", json!({ "name": "no_death", "state": true }));
+        let http = format!(
+            "POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain\r\n\r\n",
+            server.address()
+        );
+        let responses = exchange(&server, &mut calls, &[http, auth, call]);
+
+        assert_eq!(
+            responses.iter().map(error_code).collect::<Vec<_>>(),
+            [Some(RpcError::PARSE_ERROR)]
+        );
+        assert!(calls.0.is_empty());
+    }
+
+    #[test]
+    fn test_closes_on_long_lines() {
+        let server = Server::spawn("127.0.0.1:0", TOKEN).unwrap();
+        let mut calls = Calls::default();
+
+        let auth = request(1, methods::AUTH, json!({ "token": TOKEN }));
+        let call = request(2, "igt.get", Value::Null);
+        let padding = " ".repeat(MAX_LINE_LEN - call.len() + 1);
+        let longest = format!("{padding}{call}");
+        let too_long = format!(" {longest}");
+        let responses = exchange(&server, &mut calls, &[auth, longest, too_long, call]);
+
+        assert_eq!(
+            responses.iter().map(error_code).collect::<Vec<_>>(),
+            [None, None, Some(RpcError::INVALID_REQUEST)]
+        );
+        assert_eq!(calls.0, ["igt.get"]);
+    }
+
+    #[test]
+    fn test_refuses_remote_addresses() {
+        let e = Server::spawn("0.0.0.0:0", TOKEN).err().unwrap();
+        assert!(e.contains("only localhost"));
+    }
+}
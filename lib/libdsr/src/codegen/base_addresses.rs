@@ -8,6 +8,9 @@ pub struct BaseAddresses {
     pub character_flags: usize,
     pub base_menu: usize,
     pub world_chr_debug: usize,
+    pub event_flags: usize,
+    pub game_man: usize,
+    pub field_area: usize,
 }
 
 impl BaseAddresses {
@@ -18,6 +21,9 @@ impl BaseAddresses {
             character_flags: self.character_flags + base,
             base_menu: self.base_menu + base,
             world_chr_debug: self.world_chr_debug + base,
+            event_flags: self.event_flags + base,
+            game_man: self.game_man + base,
+            field_area: self.field_area + base,
         }
     }
 }
@@ -63,5 +69,8 @@ pub const BASE_ADDRESSES_1_03_1: BaseAddresses = BaseAddresses {
     character_flags: 0x1c77e50,
     base_menu: 0x1c88d98,
     world_chr_debug: 0x1c77e88,
+    event_flags: 0x1c85318,
+    game_man: 0x1c8a540,
+    field_area: 0x1c86098,
 };
 
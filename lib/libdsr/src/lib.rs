@@ -1,6 +1,7 @@
 pub mod codegen;
 pub mod memedit;
 pub mod pointers;
+pub mod savefile;
 pub mod version;
 
 pub mod prelude {
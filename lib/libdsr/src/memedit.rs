@@ -26,6 +26,12 @@ impl<T> PointerChain<T> {
         }
     }
 
+    /// The base address followed by the offsets. Two chains with the same
+    /// path point to the same value.
+    pub fn path(&self) -> Vec<usize> {
+        [self.base as usize].into_iter().chain(self.offsets.iter().copied()).collect()
+    }
+
     fn safe_read(&self, addr: usize, offs: usize) -> Option<usize> {
         let mut value = 0usize;
         unsafe {
@@ -101,6 +107,14 @@ where
         Bitflag(c, mask)
     }
 
+    pub fn chain(&self) -> &PointerChain<T> {
+        &self.0
+    }
+
+    pub fn mask(&self) -> T {
+        self.1
+    }
+
     pub fn toggle(&self) {
         if let Some(x) = self.0.read() {
             self.0.write(x ^ self.1);
@@ -43,6 +43,78 @@ impl Display for CharacterStats {
     }
 }
 
+/// The game's event flags, addressed by their in-game ID (e.g. `16` for the
+/// Asylum Demon, `11010901` for the Taurus Demon).
+#[derive(Debug, Clone)]
+pub struct EventFlags {
+    base: usize,
+}
+
+impl EventFlags {
+    pub fn new(base: usize) -> Self {
+        EventFlags { base }
+    }
+
+    /// Returns the bit holding the given flag, or `None` if the ID doesn't
+    /// map to a known flag group/area.
+    pub fn flag(&self, id: u32) -> Option<Bitflag<u32>> {
+        let (offset, mask) = event_flag_offset(id)?;
+        Some(bitflag!(mask; self.base, 0, offset))
+    }
+
+    pub fn get(&self, id: u32) -> Option<bool> {
+        self.flag(id)?.get()
+    }
+}
+
+// Event flag groups and their offsets in the flags array. Group 0 only has
+// room for area 0.
+const EVENT_FLAG_GROUPS: [(u32, usize); 5] =
+    [(0, 0x00000), (1, 0x00500), (5, 0x05f00), (6, 0x0b900), (7, 0x11300)];
+
+// Event flag areas, in the order of their blocks within a group.
+const EVENT_FLAG_AREAS: [u32; 18] =
+    [0, 100, 101, 102, 110, 120, 121, 130, 131, 132, 140, 141, 150, 151, 160, 170, 180, 181];
+
+/// Computes the byte offset and bit mask of an event flag in the flags array.
+///
+/// IDs are 8 decimal digits `GAAASNNN`: a group, an area, a section and a
+/// number within the section. Groups and areas are laid out as fixed-size
+/// blocks; every 32 flags share a `u32`, numbered from its most significant
+/// bit.
+pub fn event_flag_offset(id: u32) -> Option<(usize, u32)> {
+    if id > 99_999_999 {
+        return None;
+    }
+
+    let group = id / 10_000_000;
+    let area = id / 10_000 % 1000;
+    let section = (id / 1000 % 10) as usize;
+    let number = (id % 1000) as usize;
+
+    let (_, group_offset) = EVENT_FLAG_GROUPS.iter().find(|(g, _)| *g == group)?;
+    if group == 0 && area != 0 {
+        return None;
+    }
+    let area_index = EVENT_FLAG_AREAS.iter().position(|a| *a == area)?;
+
+    let offset = group_offset + area_index * 0x500 + section * 128 + (number - number % 32) / 8;
+    let mask = 0x80000000u32 >> (number % 32);
+
+    Some((offset, mask))
+}
+
+/// Every ID accepted by [`event_flag_offset`], in increasing order.
+pub fn event_flag_ids() -> impl Iterator<Item = u32> {
+    EVENT_FLAG_GROUPS.iter().flat_map(|&(group, _)| {
+        let areas = if group == 0 { &EVENT_FLAG_AREAS[..1] } else { &EVENT_FLAG_AREAS[..] };
+        areas.iter().flat_map(move |&area| {
+            let base = group * 10_000_000 + area * 10_000;
+            (0..10_000).map(move |n| base + n)
+        })
+    })
+}
+
 #[derive(Debug)]
 pub struct PointerChains {
     pub all_no_damage: Bitflag<u8>,
@@ -60,6 +132,19 @@ pub struct PointerChains {
     pub igt: PointerChain<u32>,
     pub bonfire_warp_menu: Bitflag<u8>,
     pub position: (PointerChain<f32>, PointerChain<[f32; 3]>),
+    /// Yaw of the game camera, in radians like the character's angle.
+    pub camera_yaw: PointerChain<f32>,
+    pub event_flags: EventFlags,
+    /// Current map as `0xWWAABBRR` (world, area, block, region), e.g. the
+    /// Undead Asylum is `m18_01_00_00`.
+    pub map_id: PointerChain<u32>,
+    pub last_bonfire: PointerChain<u32>,
+    /// The player instance. It is null (or unreadable) while a loading
+    /// screen is up, which makes it the loading-state indicator.
+    pub player_ins: PointerChain<usize>,
+    /// Writing 1 requests a return to the title screen, as if quitting from
+    /// the system menu.
+    pub quitout: PointerChain<u8>,
 }
 
 impl From<BaseAddresses> for PointerChains {
@@ -71,6 +156,9 @@ impl From<BaseAddresses> for PointerChains {
             character_flags,
             base_menu,
             world_chr_debug,
+            event_flags,
+            game_man,
+            field_area,
         } = value;
 
         let off_all_no_damage = 9;
@@ -97,6 +185,12 @@ impl From<BaseAddresses> for PointerChains {
                 pointer_chain!(character_flags, 0x68, 0x68, 0x28, 0x4), //angle
                 pointer_chain!(character_flags, 0x68, 0x68, 0x28, 0x10), // position
             ),
+            camera_yaw: pointer_chain!(field_area, 0x20, 0x1c4),
+            event_flags: EventFlags::new(event_flags),
+            map_id: pointer_chain!(character_flags, 0xa20),
+            last_bonfire: pointer_chain!(game_man, 0xb80),
+            player_ins: pointer_chain!(character_flags, 0x68),
+            quitout: pointer_chain!(game_man, 0xb72),
         }
     }
 }
@@ -116,3 +210,33 @@ impl PointerChains {
         base_addresses.into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{event_flag_ids, event_flag_offset};
+
+    #[test]
+    fn test_event_flag_offset() {
+        assert_eq!(event_flag_offset(16), Some((0, 0x8000)));
+        assert_eq!(event_flag_offset(11010901), Some((0xf70, 0x4000000)));
+        assert_eq!(event_flag_offset(8), Some((0, 0x800000)));
+        assert_eq!(event_flag_offset(50000), None);
+        assert_eq!(event_flag_offset(21000000), None);
+    }
+
+    #[test]
+    fn test_event_flag_ids() {
+        let ids: Vec<u32> = event_flag_ids().collect();
+        assert_eq!(ids.len(), (1 + 4 * 18) * 10_000);
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert!(ids.iter().all(|&id| event_flag_offset(id).is_some()));
+        assert!(ids.contains(&16) && ids.contains(&11010901));
+        assert!(!ids.contains(&1020901));
+
+        // Distinct IDs never share a bit.
+        let mut bits: Vec<_> = ids.iter().map(|&id| event_flag_offset(id).unwrap()).collect();
+        bits.sort();
+        bits.dedup();
+        assert_eq!(bits.len(), ids.len());
+    }
+}
@@ -0,0 +1,205 @@
+//! Minimal reader for the BND4 containers used by `.sl2` save files.
+//!
+//! Only the layout found in save files is supported: little endian, named
+//! entries, no compression. The raw bytes are kept around so that entries
+//! can be rewritten in place without disturbing fields we don't parse.
+
+const MAGIC: &[u8; 4] = b"BND4";
+const HEADER_SIZE: usize = 0x40;
+const ENTRY_HEADER_SIZE: usize = 0x20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bnd4Entry {
+    pub name: String,
+    pub offset: usize,
+    pub size: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bnd4 {
+    raw: Vec<u8>,
+    entries: Vec<Bnd4Entry>,
+}
+
+impl Bnd4 {
+    pub fn parse(raw: Vec<u8>) -> Result<Bnd4, String> {
+        if raw.len() < HEADER_SIZE || &raw[..4] != MAGIC {
+            return Err("Not a BND4 file".to_string());
+        }
+
+        let count = read_u32(&raw, 0x0c)? as usize;
+        let entry_header_size = read_u64(&raw, 0x20)? as usize;
+        let unicode = raw[0x30] != 0;
+
+        if entry_header_size < 0x18 {
+            return Err(format!("Unsupported BND4 entry header size {entry_header_size:#x}"));
+        }
+
+        let entries = (0..count)
+            .map(|i| {
+                let header = i
+                    .checked_mul(entry_header_size)
+                    .and_then(|offset| offset.checked_add(HEADER_SIZE))
+                    .ok_or_else(|| format!("BND4 entry {i} header is out of bounds"))?;
+                let field = |offset| {
+                    header
+                        .checked_add(offset)
+                        .ok_or_else(|| format!("BND4 entry {i} header is out of bounds"))
+                };
+                let size = read_u64(&raw, field(0x08)?)? as usize;
+                let offset = read_u32(&raw, field(0x10)?)? as usize;
+                let name_offset = read_u32(&raw, field(0x14)?)? as usize;
+
+                if offset.checked_add(size).is_none_or(|end| end > raw.len()) {
+                    return Err(format!("BND4 entry {i} is out of bounds"));
+                }
+
+                Ok(Bnd4Entry { name: read_name(&raw, name_offset, unicode)?, offset, size })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Bnd4 { raw, entries })
+    }
+
+    pub fn entries(&self) -> &[Bnd4Entry] {
+        &self.entries
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&Bnd4Entry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    pub fn data(&self, entry: &Bnd4Entry) -> &[u8] {
+        &self.raw[entry.offset..entry.offset + entry.size]
+    }
+
+    /// Replaces the data of the entry `name`. Entries can't change size, as
+    /// every following offset would have to move.
+    pub fn set_data(&mut self, name: &str, data: &[u8]) -> Result<(), String> {
+        let entry = self.entry(name).ok_or_else(|| format!("No entry {name}"))?;
+        if entry.size != data.len() {
+            return Err(format!("Entry {name} is {:#x} bytes, got {:#x}", entry.size, data.len()));
+        }
+        let offset = entry.offset;
+        self.raw[offset..offset + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// Builds a container with the given entries, laid out like the game
+    /// writes its saves: headers, UTF-16 names, then 16-aligned data.
+    pub fn build(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+        let headers_end = HEADER_SIZE + entries.len() * ENTRY_HEADER_SIZE;
+        let mut raw = vec![0u8; headers_end];
+
+        raw[..4].copy_from_slice(MAGIC);
+        raw[0x0a] = 1;
+        raw[0x0c..0x10].copy_from_slice(&(entries.len() as u32).to_le_bytes());
+        raw[0x10..0x18].copy_from_slice(&(HEADER_SIZE as u64).to_le_bytes());
+        raw[0x18..0x20].copy_from_slice(b"00000001");
+        raw[0x20..0x28].copy_from_slice(&(ENTRY_HEADER_SIZE as u64).to_le_bytes());
+        raw[0x28..0x30].copy_from_slice(&(headers_end as u64).to_le_bytes());
+        raw[0x30] = 1;
+        raw[0x31] = 0x20;
+
+        let mut name_offsets = Vec::new();
+        for (name, _) in entries {
+            name_offsets.push(raw.len());
+            raw.extend(name.encode_utf16().chain([0]).flat_map(u16::to_le_bytes));
+        }
+
+        for (i, ((_, data), name_offset)) in entries.iter().zip(name_offsets).enumerate() {
+            raw.resize(raw.len().next_multiple_of(16), 0);
+            let header = HEADER_SIZE + i * ENTRY_HEADER_SIZE;
+            let offset = raw.len() as u32;
+            raw[header] = 0x50;
+            raw[header + 0x04..header + 0x08].copy_from_slice(&(-1i32).to_le_bytes());
+            raw[header + 0x08..header + 0x10].copy_from_slice(&(data.len() as u64).to_le_bytes());
+            raw[header + 0x10..header + 0x14].copy_from_slice(&offset.to_le_bytes());
+            raw[header + 0x14..header + 0x18].copy_from_slice(&(name_offset as u32).to_le_bytes());
+            raw.extend_from_slice(data);
+        }
+
+        raw
+    }
+}
+
+fn read_bytes<const N: usize>(raw: &[u8], offset: usize) -> Result<[u8; N], String> {
+    offset
+        .checked_add(N)
+        .and_then(|end| raw.get(offset..end))
+        .map(|b| b.try_into().unwrap())
+        .ok_or_else(|| format!("BND4 truncated at {offset:#x}"))
+}
+
+fn read_u32(raw: &[u8], offset: usize) -> Result<u32, String> {
+    read_bytes(raw, offset).map(u32::from_le_bytes)
+}
+
+fn read_u64(raw: &[u8], offset: usize) -> Result<u64, String> {
+    read_bytes(raw, offset).map(u64::from_le_bytes)
+}
+
+fn read_name(raw: &[u8], offset: usize, unicode: bool) -> Result<String, String> {
+    let bytes =
+        raw.get(offset..).ok_or_else(|| format!("BND4 name out of bounds at {offset:#x}"))?;
+
+    if unicode {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|&c| c != 0)
+            .collect();
+        Ok(String::from_utf16_lossy(&units))
+    } else {
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container() -> Vec<u8> {
+        Bnd4::build(&[("USER_DATA000".to_string(), vec![1; 32])])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bnd4 = Bnd4::parse(container()).unwrap();
+        let entry = bnd4.entry("USER_DATA000").unwrap();
+        assert_eq!(bnd4.data(entry), [1; 32]);
+    }
+
+    #[test]
+    fn test_corrupt_headers() {
+        // Entry headers past the end of the address space.
+        let mut raw = container();
+        raw[0x0c..0x10].copy_from_slice(&u32::MAX.to_le_bytes());
+        raw[0x20..0x28].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(Bnd4::parse(raw).is_err());
+
+        // An entry count larger than the file.
+        let mut raw = container();
+        raw[0x0c..0x10].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(Bnd4::parse(raw).is_err());
+
+        // Entry data and name out of bounds.
+        let header = HEADER_SIZE;
+        let mut raw = container();
+        raw[header + 0x08..header + 0x10].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(Bnd4::parse(raw).is_err());
+
+        let mut raw = container();
+        raw[header + 0x10..header + 0x14].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Bnd4::parse(raw).is_err());
+
+        let mut raw = container();
+        raw[header + 0x14..header + 0x18].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Bnd4::parse(raw).is_err());
+    }
+}
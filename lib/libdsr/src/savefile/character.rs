@@ -0,0 +1,203 @@
+//! Decoding of the game data stored in a character slot, beyond what the
+//! load menu shows.
+
+use super::{layout, read_i32, SlotSummary};
+use crate::pointers::{event_flag_ids, event_flag_offset};
+
+/// Names of the equipment slots, in the order they are stored.
+pub const EQUIP_SLOT_NAMES: [&str; layout::EQUIP_SLOTS] = [
+    "Left hand 1",
+    "Right hand 1",
+    "Left hand 2",
+    "Right hand 2",
+    "Arrows 1",
+    "Bolts 1",
+    "Arrows 2",
+    "Bolts 2",
+    "Head",
+    "Chest",
+    "Hands",
+    "Legs",
+    "Hair",
+    "Ring 1",
+    "Ring 2",
+    "Quick item 1",
+    "Quick item 2",
+    "Quick item 3",
+    "Quick item 4",
+    "Quick item 5",
+];
+
+const COVENANT_NAMES: [&str; 10] = [
+    "None",
+    "Way of White",
+    "Princess's Guard",
+    "Warrior of Sunlight",
+    "Darkwraith",
+    "Path of the Dragon",
+    "Gravelord Servant",
+    "Forest Hunter",
+    "Darkmoon Blade",
+    "Chaos Servant",
+];
+
+/// Marks unused inventory entries and empty equipment slots.
+const EMPTY: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemCategory {
+    Weapon,
+    Armor,
+    Ring,
+    Goods,
+    Unknown(u32),
+}
+
+impl ItemCategory {
+    fn from_raw(raw: u32) -> Self {
+        match raw {
+            0x00000000 => ItemCategory::Weapon,
+            0x10000000 => ItemCategory::Armor,
+            0x20000000 => ItemCategory::Ring,
+            0x40000000 => ItemCategory::Goods,
+            raw => ItemCategory::Unknown(raw),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InventoryItem {
+    pub category: ItemCategory,
+    pub id: u32,
+    pub quantity: u32,
+    pub durability: u32,
+}
+
+/// Everything the tools know how to read from a character slot.
+#[derive(Debug, Clone)]
+pub struct Character {
+    pub summary: SlotSummary,
+    pub covenant: u8,
+    /// Item IDs, indexed like [`EQUIP_SLOT_NAMES`]. `None` is an empty slot.
+    pub equipment: [Option<u32>; layout::EQUIP_SLOTS],
+    pub inventory: Vec<InventoryItem>,
+    event_flags: Vec<u8>,
+}
+
+impl Character {
+    /// Decodes a decrypted slot. Slots without a character are `None`.
+    pub fn parse(index: usize, data: &[u8]) -> Result<Option<Character>, String> {
+        let Some(summary) = SlotSummary::parse(index, data)? else {
+            return Ok(None);
+        };
+        let truncated = || format!("Character slot {index} is truncated");
+        let player = &data[layout::PLAYER_GAME_DATA..];
+
+        let covenant = *player.get(layout::COVENANT).ok_or_else(truncated)?;
+
+        let mut equipment = [None; layout::EQUIP_SLOTS];
+        for (i, item) in equipment.iter_mut().enumerate() {
+            let id = read_i32(player, layout::EQUIPMENT + i * 4).ok_or_else(truncated)? as u32;
+            *item = (id != EMPTY).then_some(id);
+        }
+
+        let inventory = (0..layout::INVENTORY_CAPACITY)
+            .map(|i| {
+                let entry = layout::INVENTORY + i * layout::INVENTORY_ENTRY_SIZE;
+                let field = |offset| read_i32(player, entry + offset).map(|v| v as u32);
+                Some((field(0x0)?, field(0x4)?, field(0x8)?, field(0x18)?))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(truncated)?
+            .into_iter()
+            .filter(|&(category, id, _, _)| category != EMPTY && id != EMPTY)
+            .map(|(category, id, quantity, durability)| InventoryItem {
+                category: ItemCategory::from_raw(category),
+                id,
+                quantity,
+                durability,
+            })
+            .collect();
+
+        let event_flags = data
+            .get(layout::EVENT_FLAGS..layout::EVENT_FLAGS + layout::EVENT_FLAGS_LEN)
+            .ok_or_else(truncated)?
+            .to_vec();
+
+        Ok(Some(Character { summary, covenant, equipment, inventory, event_flags }))
+    }
+
+    pub fn covenant_name(&self) -> &'static str {
+        COVENANT_NAMES.get(self.covenant as usize).copied().unwrap_or("Unknown")
+    }
+
+    /// The state of an event flag, or `None` for an invalid ID.
+    pub fn event_flag(&self, id: u32) -> Option<bool> {
+        let (offset, mask) = event_flag_offset(id)?;
+        let value = read_i32(&self.event_flags, offset)? as u32;
+        Some(value & mask != 0)
+    }
+
+    /// IDs of all the event flags that are set.
+    pub fn set_event_flags(&self) -> impl Iterator<Item = u32> + '_ {
+        event_flag_ids().filter(|&id| self.event_flag(id) == Some(true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::savefile::tests::slot;
+
+    /// A character slot large enough for everything `Character` reads.
+    fn full_slot(name: &str) -> Vec<u8> {
+        let mut data = slot(name, 30, 1000);
+        data.resize(layout::EVENT_FLAGS + layout::EVENT_FLAGS_LEN, 0);
+
+        let player = layout::PLAYER_GAME_DATA;
+        data[player + layout::COVENANT] = 3;
+        for i in 0..layout::EQUIP_SLOTS {
+            let offset = player + layout::EQUIPMENT + i * 4;
+            data[offset..offset + 4].copy_from_slice(&EMPTY.to_le_bytes());
+        }
+        for i in 0..layout::INVENTORY_CAPACITY {
+            let offset = player + layout::INVENTORY + i * layout::INVENTORY_ENTRY_SIZE;
+            data[offset..offset + 8].copy_from_slice(&[0xff; 8]);
+        }
+        data
+    }
+
+    fn write(data: &mut [u8], offset: usize, value: u32) {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn test_character() {
+        let mut data = full_slot("Solaire");
+        let player = layout::PLAYER_GAME_DATA;
+        write(&mut data, player + layout::EQUIPMENT + 4, 201000);
+        let item = player + layout::INVENTORY + 2 * layout::INVENTORY_ENTRY_SIZE;
+        for (offset, value) in [(0x0, 0x40000000), (0x4, 200), (0x8, 5), (0x18, 0)] {
+            write(&mut data, item + offset, value);
+        }
+        let (offset, mask) = event_flag_offset(11010901).unwrap();
+        write(&mut data, layout::EVENT_FLAGS + offset, mask);
+
+        let character = Character::parse(0, &data).unwrap().unwrap();
+        assert_eq!(character.summary.name, "Solaire");
+        assert_eq!(character.covenant_name(), "Warrior of Sunlight");
+        assert_eq!(character.equipment[0], None);
+        assert_eq!(character.equipment[1], Some(201000));
+        assert_eq!(
+            character.inventory,
+            [InventoryItem { category: ItemCategory::Goods, id: 200, quantity: 5, durability: 0 }]
+        );
+        assert_eq!(character.event_flag(11010901), Some(true));
+        assert_eq!(character.event_flag(16), Some(false));
+        assert_eq!(character.event_flag(21000000), None);
+        assert_eq!(character.set_event_flags().collect::<Vec<_>>(), [11010901]);
+
+        assert!(Character::parse(0, &slot("Solaire", 30, 1000)).is_err());
+        assert!(Character::parse(0, &full_slot("")).unwrap().is_none());
+    }
+}
@@ -0,0 +1,97 @@
+//! Encryption of the `USER_DATA` entries of DSR save files.
+//!
+//! Every entry is laid out as a 16 bytes checksum, a 16 bytes IV and the
+//! payload, encrypted with AES-128-CBC under a key shared by all saves. The
+//! checksum is the MD5 of the IV and encrypted payload.
+
+use aes::Aes128;
+use cbc::cipher::block_padding::NoPadding;
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use md5::{Digest, Md5};
+
+const KEY: [u8; 16] = [
+    0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0xfe, 0xdc, 0xba, 0x98, 0x76, 0x54, 0x32, 0x10,
+];
+
+pub(crate) const CHECKSUM_LEN: usize = 16;
+pub(crate) const IV_LEN: usize = 16;
+
+/// Computes the checksum of an entry.
+pub fn checksum(entry: &[u8]) -> Option<[u8; CHECKSUM_LEN]> {
+    Some(Md5::digest(entry.get(CHECKSUM_LEN..)?).into())
+}
+
+/// Whether the stored checksum of an entry matches its contents. A mismatch
+/// means the entry was corrupted or only partially written.
+pub fn verify_entry(entry: &[u8]) -> bool {
+    checksum(entry).is_some_and(|c| entry[..CHECKSUM_LEN] == c)
+}
+
+/// Decrypts an entry after verifying its checksum, returning its payload.
+pub fn decrypt_entry(entry: &[u8]) -> Result<Vec<u8>, String> {
+    let body = entry.get(CHECKSUM_LEN..).ok_or("Save entry too short")?;
+    if !verify_entry(entry) {
+        return Err("Save entry checksum mismatch".to_string());
+    }
+    let (iv, ciphertext) = body.split_at(IV_LEN.min(body.len()));
+    if iv.len() < IV_LEN || !ciphertext.len().is_multiple_of(16) {
+        return Err(format!("Invalid save entry size {:#x}", entry.len()));
+    }
+
+    let mut payload = ciphertext.to_vec();
+    cbc::Decryptor::<Aes128>::new(&KEY.into(), iv.into())
+        .decrypt_padded_mut::<NoPadding>(&mut payload)
+        .map_err(|e| format!("Couldn't decrypt save entry: {e}"))?;
+
+    Ok(payload)
+}
+
+/// Encrypts `payload` with the given IV into the entry layout, checksum
+/// included.
+pub fn encrypt_entry(payload: &[u8], iv: &[u8; IV_LEN]) -> Result<Vec<u8>, String> {
+    if !payload.len().is_multiple_of(16) {
+        return Err(format!("Save payload size {:#x} isn't a multiple of 16", payload.len()));
+    }
+
+    let mut entry = vec![0u8; CHECKSUM_LEN];
+    entry.extend_from_slice(iv);
+    entry.extend_from_slice(payload);
+
+    cbc::Encryptor::<Aes128>::new(&KEY.into(), iv.into())
+        .encrypt_padded_mut::<NoPadding>(&mut entry[CHECKSUM_LEN + IV_LEN..], payload.len())
+        .map_err(|e| format!("Couldn't encrypt save entry: {e}"))?;
+
+    let checksum = checksum(&entry).unwrap();
+    entry[..CHECKSUM_LEN].copy_from_slice(&checksum);
+    Ok(entry)
+}
+
+/// The IV of an entry.
+pub fn entry_iv(entry: &[u8]) -> Option<[u8; IV_LEN]> {
+    entry.get(CHECKSUM_LEN..CHECKSUM_LEN + IV_LEN)?.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let payload: Vec<u8> = (0..64u8).collect();
+        let iv = [7u8; IV_LEN];
+        let mut entry = encrypt_entry(&payload, &iv).unwrap();
+
+        assert_eq!(entry.len(), CHECKSUM_LEN + IV_LEN + payload.len());
+        assert_ne!(&entry[CHECKSUM_LEN + IV_LEN..], &payload[..]);
+        assert_eq!(entry_iv(&entry), Some(iv));
+        assert!(verify_entry(&entry));
+        assert_eq!(decrypt_entry(&entry).unwrap(), payload);
+
+        // Corruption anywhere after the checksum is detected.
+        entry[40] ^= 1;
+        assert!(!verify_entry(&entry));
+        assert!(decrypt_entry(&entry).is_err());
+
+        assert!(encrypt_entry(&payload[..10], &iv).is_err());
+    }
+}
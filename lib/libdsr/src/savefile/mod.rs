@@ -0,0 +1,327 @@
+//! Reading and editing of DSR `.sl2` save files.
+//!
+//! A save is a BND4 container with one encrypted `USER_DATA` entry per
+//! character slot, plus one for the menu state. None of this touches game
+//! memory, so it works on any platform.
+
+pub mod bnd4;
+pub mod character;
+pub mod crypto;
+
+use std::path::Path;
+
+use crate::pointers::CharacterStats;
+use bnd4::Bnd4;
+use character::Character;
+
+/// Number of character slots in a save.
+pub const SLOT_COUNT: usize = 10;
+
+/// Offsets into a decrypted character slot.
+pub mod layout {
+    /// Milliseconds of play time, as shown in the load menu.
+    pub const PLAYTIME: usize = 0x1c;
+    /// Start of the serialized player data. The offsets below are relative to
+    /// it and match the in-memory ones used by `PointerChains`.
+    pub const PLAYER_GAME_DATA: usize = 0x60;
+    pub const STATS: usize = 0x40;
+    /// UTF-16 character name, 13 characters plus terminator.
+    pub const NAME: usize = 0xa8;
+    pub const NAME_LEN: usize = 14;
+    /// Covenant index, one byte.
+    pub const COVENANT: usize = 0x113;
+    /// Equipped item IDs, one `u32` per equipment slot.
+    pub const EQUIPMENT: usize = 0x278;
+    pub const EQUIP_SLOTS: usize = 20;
+    /// Inventory entries: category, item ID, quantity, sort index, two
+    /// unknown fields and durability, all `u32`.
+    pub const INVENTORY: usize = 0x2e0;
+    pub const INVENTORY_ENTRY_SIZE: usize = 0x1c;
+    pub const INVENTORY_CAPACITY: usize = 2048;
+    /// Event flags array, relative to the start of the slot, laid out as
+    /// described by `event_flag_offset`.
+    pub const EVENT_FLAGS: usize = 0x1f000;
+    pub const EVENT_FLAGS_LEN: usize = 0x16d00;
+}
+
+/// Name of the BND4 entry holding character slot `index`.
+pub fn slot_entry_name(index: usize) -> String {
+    format!("USER_DATA{index:03}")
+}
+
+/// The menu-visible summary of a character slot.
+#[derive(Debug, Clone)]
+pub struct SlotSummary {
+    pub index: usize,
+    pub name: String,
+    pub level: i32,
+    pub playtime: u32,
+    pub stats: CharacterStats,
+}
+
+#[derive(Debug, Clone)]
+pub struct Savefile {
+    bnd4: Bnd4,
+}
+
+impl Savefile {
+    pub fn open(path: &Path) -> Result<Savefile, String> {
+        let raw =
+            std::fs::read(path).map_err(|e| format!("Couldn't read {}: {e}", path.display()))?;
+        Savefile::parse(raw).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn parse(raw: Vec<u8>) -> Result<Savefile, String> {
+        let bnd4 = Bnd4::parse(raw)?;
+        if bnd4.entry(&slot_entry_name(0)).is_none() {
+            return Err("No character slots in save file".to_string());
+        }
+        Ok(Savefile { bnd4 })
+    }
+
+    pub fn bnd4(&self) -> &Bnd4 {
+        &self.bnd4
+    }
+
+    /// Decrypted contents of character slot `index`.
+    pub fn slot_data(&self, index: usize) -> Result<Vec<u8>, String> {
+        let entry = self
+            .bnd4
+            .entry(&slot_entry_name(index))
+            .ok_or_else(|| format!("No character slot {index} in save file"))?;
+        crypto::decrypt_entry(self.bnd4.data(entry))
+    }
+
+    /// Re-encrypts `payload` into character slot `index`, keeping the slot's
+    /// IV and updating its checksum.
+    pub fn set_slot_data(&mut self, index: usize, payload: &[u8]) -> Result<(), String> {
+        let name = slot_entry_name(index);
+        let entry = self.bnd4.entry(&name).ok_or_else(|| format!("No character slot {index}"))?;
+        let iv = crypto::entry_iv(self.bnd4.data(entry))
+            .ok_or_else(|| format!("Character slot {index} is truncated"))?;
+        self.bnd4.set_data(&name, &crypto::encrypt_entry(payload, &iv)?)
+    }
+
+    /// Exports character slot `index` as a standalone, checksummed file.
+    pub fn export_slot(&self, index: usize) -> Result<Vec<u8>, String> {
+        let entry = self
+            .bnd4
+            .entry(&slot_entry_name(index))
+            .ok_or_else(|| format!("No character slot {index} in save file"))?;
+        let data = self.bnd4.data(entry);
+        if !crypto::verify_entry(data) {
+            return Err(format!("Character slot {index} is corrupted"));
+        }
+        Ok(data.to_vec())
+    }
+
+    /// Imports a slot exported by [`Savefile::export_slot`] into character
+    /// slot `index`, which may differ from the one it was exported from.
+    ///
+    /// The load menu keeps its own list of occupied slots, so a character
+    /// imported into an empty slot only shows up once the game has saved it.
+    pub fn import_slot(&mut self, index: usize, exported: &[u8]) -> Result<(), String> {
+        let payload =
+            crypto::decrypt_entry(exported).map_err(|e| format!("Invalid slot file: {e}"))?;
+        if SlotSummary::parse(index, &payload)?.is_none() {
+            return Err("The slot file contains no character".to_string());
+        }
+        self.set_slot_data(index, &payload)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bnd4.as_bytes()
+    }
+
+    /// Summary of character slot `index`, or `None` if the slot is empty.
+    pub fn slot(&self, index: usize) -> Result<Option<SlotSummary>, String> {
+        SlotSummary::parse(index, &self.slot_data(index)?)
+    }
+
+    /// All the decoded data of character slot `index`, or `None` if the slot
+    /// is empty.
+    pub fn character(&self, index: usize) -> Result<Option<Character>, String> {
+        Character::parse(index, &self.slot_data(index)?)
+    }
+
+    /// Summaries of all occupied character slots.
+    pub fn slots(&self) -> Result<Vec<SlotSummary>, String> {
+        (0..SLOT_COUNT)
+            .filter(|&i| self.bnd4.entry(&slot_entry_name(i)).is_some())
+            .filter_map(|i| self.slot(i).transpose())
+            .collect()
+    }
+}
+
+impl SlotSummary {
+    /// Reads the summary from a decrypted slot. Slots without a character
+    /// name are empty.
+    pub fn parse(index: usize, data: &[u8]) -> Result<Option<SlotSummary>, String> {
+        let player = data
+            .get(layout::PLAYER_GAME_DATA..)
+            .ok_or_else(|| format!("Character slot {index} is truncated"))?;
+
+        let name = player
+            .get(layout::NAME..layout::NAME + layout::NAME_LEN * 2)
+            .ok_or_else(|| format!("Character slot {index} is truncated"))?;
+        let name: Vec<u16> = name
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|&c| c != 0)
+            .collect();
+        if name.is_empty() {
+            return Ok(None);
+        }
+
+        let stats = read_stats(&player[layout::STATS..])
+            .ok_or_else(|| format!("Character slot {index} is truncated"))?;
+        let playtime = read_i32(data, layout::PLAYTIME)
+            .ok_or_else(|| format!("Character slot {index} is truncated"))?
+            as u32;
+
+        Ok(Some(SlotSummary {
+            index,
+            name: String::from_utf16_lossy(&name),
+            level: stats.level,
+            playtime,
+            stats,
+        }))
+    }
+}
+
+fn read_i32(data: &[u8], offset: usize) -> Option<i32> {
+    data.get(offset..offset + 4).map(|b| i32::from_le_bytes(b.try_into().unwrap()))
+}
+
+/// Reads a [`CharacterStats`] laid out as in memory.
+pub fn read_stats(data: &[u8]) -> Option<CharacterStats> {
+    let mut fields = (0..22).map(|i| read_i32(data, i * 4));
+    let mut next = || fields.next().flatten();
+
+    Some(CharacterStats {
+        vitality: next()?,
+        unk1: next()?,
+        attunement: next()?,
+        unk2: next()?,
+        endurance: next()?,
+        unk3: next()?,
+        strength: next()?,
+        unk4: next()?,
+        dexterity: next()?,
+        unk5: next()?,
+        intelligence: next()?,
+        unk6: next()?,
+        faith: next()?,
+        unk7: next()?,
+        unk8: next()?,
+        unk9: next()?,
+        unk10: next()?,
+        humanity: next()?,
+        resistance: next()?,
+        unk11: next()?,
+        level: next()?,
+        souls: next()?,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) const SLOT_SIZE: usize = 0x400;
+
+    /// Builds a decrypted slot with the given character.
+    pub(crate) fn slot(name: &str, level: i32, playtime: u32) -> Vec<u8> {
+        let mut data = vec![0u8; SLOT_SIZE];
+        data[layout::PLAYTIME..][..4].copy_from_slice(&playtime.to_le_bytes());
+
+        let player = layout::PLAYER_GAME_DATA;
+        for (i, c) in name.encode_utf16().enumerate() {
+            data[player + layout::NAME + i * 2..][..2].copy_from_slice(&c.to_le_bytes());
+        }
+        let vitality = player + layout::STATS;
+        data[vitality..][..4].copy_from_slice(&11i32.to_le_bytes());
+        let level_offset = player + layout::STATS + 20 * 4;
+        data[level_offset..][..4].copy_from_slice(&level.to_le_bytes());
+
+        data
+    }
+
+    /// Builds an encrypted save from decrypted slots.
+    pub(crate) fn save(slots: &[Vec<u8>]) -> Vec<u8> {
+        let entries: Vec<_> = slots
+            .iter()
+            .enumerate()
+            .map(|(i, data)| {
+                let iv = [i as u8; crypto::IV_LEN];
+                (slot_entry_name(i), crypto::encrypt_entry(data, &iv).unwrap())
+            })
+            .collect();
+        Bnd4::build(&entries)
+    }
+
+    #[test]
+    fn test_slots() {
+        let empty = vec![0u8; SLOT_SIZE];
+        let raw = save(&[slot("Solaire", 42, 3_600_000), empty, slot("Oscar", 1, 5)]);
+        let savefile = Savefile::parse(raw).unwrap();
+
+        assert_eq!(savefile.bnd4().entries().len(), 3);
+        assert_eq!(savefile.bnd4().entries()[2].name, "USER_DATA002");
+
+        let slots = savefile.slots().unwrap();
+        assert_eq!(slots.len(), 2);
+        assert_eq!(slots[0].name, "Solaire");
+        assert_eq!(slots[0].level, 42);
+        assert_eq!(slots[0].playtime, 3_600_000);
+        assert_eq!(slots[0].stats.vitality, 11);
+        assert_eq!(slots[1].index, 2);
+        assert_eq!(slots[1].name, "Oscar");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Savefile::parse(b"not a save".to_vec()).is_err());
+        assert!(Savefile::parse(Bnd4::build(&[("OTHER".to_string(), vec![0; 48])])).is_err());
+
+        let mut raw = save(&[slot("Solaire", 42, 0)]);
+        raw.truncate(raw.len() - 1);
+        assert!(Savefile::parse(raw).is_err());
+
+        // A slot too small or corrupt to be decrypted is an error, not a panic.
+        let raw = Bnd4::build(&[(slot_entry_name(0), vec![0; 40])]);
+        assert!(Savefile::parse(raw).unwrap().slots().is_err());
+    }
+
+    #[test]
+    fn test_export_import() {
+        let empty = vec![0u8; SLOT_SIZE];
+        let raw = save(&[slot("Solaire", 42, 1000), empty.clone(), slot("Oscar", 1, 5)]);
+        let mut savefile = Savefile::parse(raw).unwrap();
+
+        let exported = savefile.export_slot(0).unwrap();
+        savefile.import_slot(1, &exported).unwrap();
+        savefile.import_slot(2, &exported).unwrap();
+
+        // The edited file is a valid save, re-encrypted under each slot's IV.
+        let savefile = Savefile::parse(savefile.as_bytes().to_vec()).unwrap();
+        let names: Vec<_> = savefile.slots().unwrap().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, ["Solaire", "Solaire", "Solaire"]);
+        let entry = |i| savefile.bnd4().data(savefile.bnd4().entry(&slot_entry_name(i)).unwrap());
+        assert_eq!(crypto::entry_iv(entry(1)), Some([1; crypto::IV_LEN]));
+        assert_ne!(entry(0), entry(1));
+        assert_eq!(savefile.slot_data(0).unwrap(), savefile.slot_data(1).unwrap());
+
+        // Corrupted, empty or differently sized slots are refused.
+        let mut corrupted = exported.clone();
+        corrupted[100] ^= 0xff;
+        let mut target = Savefile::parse(save(&[slot("Oscar", 1, 5)])).unwrap();
+        assert!(target.import_slot(0, &corrupted).is_err());
+        let empty_export = crypto::encrypt_entry(&empty, &[0; crypto::IV_LEN]).unwrap();
+        assert!(target.import_slot(0, &empty_export).is_err());
+        let small = crypto::encrypt_entry(&slot("Oscar", 1, 5)[..0x200], &[0; 16]).unwrap();
+        assert!(target.import_slot(0, &small).is_err());
+        assert!(target.import_slot(3, &exported).is_err());
+        assert_eq!(target.slot(0).unwrap().unwrap().name, "Oscar");
+    }
+}
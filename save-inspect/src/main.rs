@@ -0,0 +1,122 @@
+//! Prints the characters of a DSR `.sl2` save, or the differences between
+//! two saves, without running the game.
+//!
+//! ```text
+//! save-inspect show <save.sl2> [--slot N] [--flags]
+//! save-inspect diff <a.sl2> <b.sl2> [--slot N]
+//! ```
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use libdsr::savefile::{Savefile, SLOT_COUNT};
+
+mod report;
+
+const USAGE: &str = "\
+Usage:
+  save-inspect show <save.sl2> [--slot N] [--flags]
+  save-inspect diff <a.sl2> <b.sl2> [--slot N]
+
+  --slot N   only the character slot N (1-10)
+  --flags    list every event flag that is set, instead of counting them";
+
+struct Args {
+    command: String,
+    files: Vec<PathBuf>,
+    slot: Option<usize>,
+    flags: bool,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+        let command = args.next().ok_or("Missing command")?;
+        let mut parsed = Args { command, files: Vec::new(), slot: None, flags: false };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--slot" => {
+                    let slot = args.next().ok_or("--slot needs a value")?;
+                    let slot = slot
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|s| (1..=SLOT_COUNT).contains(s))
+                        .ok_or_else(|| format!("Invalid slot {slot}"))?;
+                    parsed.slot = Some(slot - 1);
+                }
+                "--flags" => parsed.flags = true,
+                arg if arg.starts_with("--") => return Err(format!("Unknown option {arg}")),
+                file => parsed.files.push(file.into()),
+            }
+        }
+
+        let expected = match parsed.command.as_str() {
+            "show" => 1,
+            "diff" => 2,
+            command => return Err(format!("Unknown command {command}")),
+        };
+        if parsed.files.len() != expected {
+            return Err(format!("{} expects {expected} file(s)", parsed.command));
+        }
+        Ok(parsed)
+    }
+
+    fn slots(&self) -> Vec<usize> {
+        match self.slot {
+            Some(slot) => vec![slot],
+            None => (0..SLOT_COUNT).collect(),
+        }
+    }
+}
+
+fn show(args: &Args) -> Result<(), String> {
+    let save = Savefile::open(&args.files[0])?;
+    for index in args.slots() {
+        if let Some(character) = save.character(index)? {
+            println!("Slot {}", index + 1);
+            report::print(&report::report(&character, args.flags));
+        }
+    }
+    Ok(())
+}
+
+fn diff(args: &Args) -> Result<(), String> {
+    let a = Savefile::open(&args.files[0])?;
+    let b = Savefile::open(&args.files[1])?;
+
+    for index in args.slots() {
+        let report = |save: &Savefile| -> Result<_, String> {
+            Ok(save.character(index)?.map(|c| report::report(&c, true)))
+        };
+        let lines = match (report(&a)?, report(&b)?) {
+            (Some(a), Some(b)) => report::diff(&a, &b),
+            (Some(_), None) => vec!["- character".to_string()],
+            (None, Some(_)) => vec!["+ character".to_string()],
+            (None, None) => Vec::new(),
+        };
+
+        if !lines.is_empty() {
+            println!("Slot {}", index + 1);
+            for line in lines {
+                println!("{line}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let result =
+        Args::parse(std::env::args().skip(1)).and_then(|args| match args.command.as_str() {
+            "show" => show(&args),
+            _ => diff(&args),
+        });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
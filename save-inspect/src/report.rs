@@ -0,0 +1,193 @@
+//! Flattening of a character into labeled fields, for printing and diffing.
+
+use std::collections::HashMap;
+
+use libdsr::savefile::character::{Character, InventoryItem, ItemCategory, EQUIP_SLOT_NAMES};
+
+/// A character as `(section, label, value)` fields, in display order.
+pub(crate) type Report = Vec<(&'static str, String, String)>;
+
+pub(crate) fn report(character: &Character, with_flags: bool) -> Report {
+    let summary = &character.summary;
+    let stats = &summary.stats;
+    let secs = summary.playtime / 1000;
+
+    let mut fields: Report = vec![
+        ("Character", "Name".into(), summary.name.clone()),
+        ("Character", "Level".into(), summary.level.to_string()),
+        (
+            "Character",
+            "Playtime".into(),
+            format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60),
+        ),
+        ("Character", "Covenant".into(), character.covenant_name().to_string()),
+    ];
+
+    for (label, value) in [
+        ("Vitality", stats.vitality),
+        ("Attunement", stats.attunement),
+        ("Endurance", stats.endurance),
+        ("Strength", stats.strength),
+        ("Dexterity", stats.dexterity),
+        ("Resistance", stats.resistance),
+        ("Intelligence", stats.intelligence),
+        ("Faith", stats.faith),
+        ("Humanity", stats.humanity),
+        ("Souls", stats.souls),
+    ] {
+        fields.push(("Stats", label.into(), value.to_string()));
+    }
+
+    for (name, item) in EQUIP_SLOT_NAMES.iter().zip(&character.equipment) {
+        if let Some(id) = item {
+            fields.push(("Equipment", name.to_string(), id.to_string()));
+        }
+    }
+
+    for (label, value) in inventory(&character.inventory) {
+        fields.push(("Inventory", label, value));
+    }
+
+    if with_flags {
+        for id in character.set_event_flags() {
+            fields.push(("Event flags", id.to_string(), "set".into()));
+        }
+    } else {
+        let count = character.set_event_flags().count();
+        fields.push(("Event flags", "Set".into(), count.to_string()));
+    }
+
+    fields
+}
+
+/// Labels and values of inventory items. Copies of an item after the first
+/// are numbered, e.g. `Weapon 100000 #2`, so that each keeps its own field.
+fn inventory(items: &[InventoryItem]) -> Vec<(String, String)> {
+    let mut copies: HashMap<String, usize> = HashMap::new();
+    items
+        .iter()
+        .map(|item| {
+            let category = match item.category {
+                ItemCategory::Weapon => "Weapon".to_string(),
+                ItemCategory::Armor => "Armor".to_string(),
+                ItemCategory::Ring => "Ring".to_string(),
+                ItemCategory::Goods => "Goods".to_string(),
+                ItemCategory::Unknown(raw) => format!("{raw:#010x}"),
+            };
+            let mut label = format!("{category} {}", item.id);
+            let count = copies.entry(label.clone()).or_default();
+            *count += 1;
+            if *count > 1 {
+                label += &format!(" #{count}");
+            }
+
+            let mut value = format!("x{}", item.quantity);
+            if matches!(item.category, ItemCategory::Weapon | ItemCategory::Armor) {
+                value += &format!(", durability {}", item.durability);
+            }
+            (label, value)
+        })
+        .collect()
+}
+
+pub(crate) fn print(report: &Report) {
+    let mut section = "";
+    for (s, label, value) in report {
+        if *s != section {
+            section = s;
+            println!("  {section}:");
+        }
+        println!("    {label:<24} {value}");
+    }
+}
+
+/// The fields that differ between two reports, as printable lines. Fields
+/// missing on one side show as added or removed.
+pub(crate) fn diff(a: &Report, b: &Report) -> Vec<String> {
+    let key = |(section, label, _): &(&'static str, String, String)| (*section, label.clone());
+    let values_a: HashMap<_, _> = a.iter().map(|f| (key(f), &f.2)).collect();
+    let values_b: HashMap<_, _> = b.iter().map(|f| (key(f), &f.2)).collect();
+
+    let mut lines = Vec::new();
+    for field in a {
+        let (section, label, value) = field;
+        match values_b.get(&key(field)) {
+            Some(other) if *other != value => {
+                lines.push(format!("  {section} / {label}: {value} -> {other}"))
+            }
+            Some(_) => {}
+            None => lines.push(format!("- {section} / {label}: {value}")),
+        }
+    }
+    for field in b {
+        if !values_a.contains_key(&key(field)) {
+            let (section, label, value) = field;
+            lines.push(format!("+ {section} / {label}: {value}"));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(section: &'static str, label: &str, value: &str) -> (&'static str, String, String) {
+        (section, label.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = vec![
+            field("Character", "Level", "30"),
+            field("Stats", "Vitality", "11"),
+            field("Inventory", "Goods 200", "x5"),
+            field("Event flags", "16", "set"),
+        ];
+        let b = vec![
+            field("Character", "Level", "31"),
+            field("Stats", "Vitality", "11"),
+            field("Event flags", "16", "set"),
+            field("Event flags", "11010901", "set"),
+        ];
+
+        assert_eq!(
+            diff(&a, &b),
+            [
+                "  Character / Level: 30 -> 31",
+                "- Inventory / Goods 200: x5",
+                "+ Event flags / 11010901: set",
+            ]
+        );
+        assert!(diff(&a, &a).is_empty());
+    }
+
+    #[test]
+    fn test_inventory_copies() {
+        let item =
+            |category, id, durability| InventoryItem { category, id, quantity: 1, durability };
+        let a = inventory(&[
+            item(ItemCategory::Weapon, 100000, 100),
+            item(ItemCategory::Weapon, 100000, 50),
+            item(ItemCategory::Ring, 100000, 0),
+        ]);
+        assert_eq!(
+            a,
+            [
+                ("Weapon 100000".to_string(), "x1, durability 100".to_string()),
+                ("Weapon 100000 #2".to_string(), "x1, durability 50".to_string()),
+                ("Ring 100000".to_string(), "x1".to_string()),
+            ]
+        );
+
+        // Losing one copy shows in the diff.
+        let to_report = |fields: Vec<(String, String)>| -> Report {
+            fields.into_iter().map(|(label, value)| ("Inventory", label, value)).collect()
+        };
+        let b = inventory(&[item(ItemCategory::Weapon, 100000, 100)]);
+        assert_eq!(
+            diff(&to_report(a), &to_report(b)),
+            ["- Inventory / Weapon 100000 #2: x1, durability 50", "- Inventory / Ring 100000: x1"]
+        );
+    }
+}
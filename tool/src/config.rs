@@ -1,15 +1,31 @@
+use std::path::PathBuf;
 use std::str::FromStr;
 
+use crate::indicators::format::Template;
+use crate::indicators::IndicatorRegistry;
+use crate::livesplit::{self, LiveSplit};
+use crate::macros::{Step, Wait};
+use crate::palette::PaletteRegistry;
+use crate::restore::RestoreRegistry;
+use crate::savefile::backup::BackupSettings;
 use crate::widgets::character_stats::character_stats_edit;
 use crate::widgets::cycle_speed::cycle_speed;
 use crate::widgets::flag::flag_widget;
 use crate::widgets::group::group;
 use crate::widgets::label::label_widget;
-use crate::widgets::nudge_pos::nudge_position;
+use crate::widgets::macros::{macro_widget, Action};
+use crate::widgets::noclip::{noclip, NoclipKeys};
+use crate::widgets::nudge_pos::{nudge_position, NudgeKeys};
 use crate::widgets::open_menu::{open_menu, OpenMenuKind};
+use crate::widgets::palette::hotkeys;
 use crate::widgets::position::save_position;
+use crate::widgets::position_library::position_library;
+use crate::widgets::quitout::quitout;
+use crate::widgets::real_timer::real_timer;
 use crate::widgets::savefile_manager::savefile_manager;
+use crate::splits::SegmentSpec;
 use crate::widgets::souls::souls;
+use crate::widgets::splits::splits;
 use crate::widgets::warp_menu::warp_menu;
 use libdsr::prelude::*;
 use practice_tool_core::key::Key;
@@ -28,14 +44,22 @@ impl Config {
         toml::from_str::<Config>(cfg).map_err(|e| format!("TOML configuration parse error: {}", e))
     }
 
-    pub(crate) fn make_commands(self, chains: &PointerChains) -> Vec<Box<dyn Widget>> {
-        self.commands
-            .into_iter()
-            .map(|c| c.into_widget(&self.settings, chains))
-            .collect()
+    pub(crate) fn make_commands(self, ctx: &mut BuildContext) -> Vec<Box<dyn Widget>> {
+        self.commands.into_iter().map(|c| c.into_widget(&self.settings, ctx)).collect()
     }
 }
 
+/// What the commands are built with besides their config: the game memory,
+/// and the registries they put their indicators, actions and state changes
+/// in.
+pub(crate) struct BuildContext<'a> {
+    pub(crate) chains: &'a PointerChains,
+    pub(crate) indicators: &'a mut IndicatorRegistry,
+    pub(crate) livesplit: Option<&'a LiveSplit>,
+    pub(crate) restore: &'a RestoreRegistry,
+    pub(crate) palette: &'a mut PaletteRegistry,
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
@@ -43,8 +67,15 @@ impl Default for Config {
                 log_level: LevelFilterSerde(LevelFilter::DEBUG),
                 display: "0".parse().unwrap(),
                 hide: "rshift+0".parse().ok(),
+                palette: None,
                 show_console: false,
-                indicators: Indicator::default_set(),
+                indicators: IndicatorSettings::default_set(),
+                livesplit: None,
+                ipc: None,
+                backups: BackupSettings::default(),
+                load_dlls: Vec::new(),
+                load_dlls_delay_ms: 0,
+                keep_state_on_eject: false,
             },
             commands: Vec::new(),
         }
@@ -56,103 +87,181 @@ pub(crate) struct Settings {
     pub(crate) log_level: LevelFilterSerde,
     pub(crate) display: Key,
     pub(crate) hide: Option<Key>,
+    /// Opens the command palette.
+    #[serde(default)]
+    pub(crate) palette: Option<Key>,
     #[serde(default)]
     pub(crate) show_console: bool,
-    #[serde(default = "Indicator::default_set")]
-    pub(crate) indicators: Vec<Indicator>,
+    #[serde(default = "IndicatorSettings::default_set")]
+    pub(crate) indicators: Vec<IndicatorSettings>,
+    #[serde(default)]
+    pub(crate) livesplit: Option<LiveSplitSettings>,
+    #[serde(default)]
+    pub(crate) ipc: Option<IpcSettings>,
+    /// Backups of the save taken by the savefile manager.
+    #[serde(default)]
+    pub(crate) backups: BackupSettings,
+    /// Mod DLLs to load, in order, once the tool has started. Relative paths
+    /// are relative to the tool's DLL.
+    #[serde(default)]
+    pub(crate) load_dlls: Vec<PathBuf>,
+    #[serde(default)]
+    pub(crate) load_dlls_delay_ms: u64,
+    /// Leave flags and speed as they are when ejecting, instead of restoring
+    /// what they were before the tool changed them.
+    #[serde(default)]
+    pub(crate) keep_state_on_eject: bool,
+}
+
+/// Connection to the LiveSplit Server component, which receives IGT and the
+/// split timer's actions.
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct LiveSplitSettings {
+    #[serde(default = "LiveSplitSettings::default_address")]
+    pub(crate) address: String,
+}
+
+impl LiveSplitSettings {
+    fn default_address() -> String {
+        livesplit::DEFAULT_ADDRESS.to_string()
+    }
+}
+
+/// The JSON-RPC server external tools control the tool through.
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct IpcSettings {
+    #[serde(default = "IpcSettings::default_address")]
+    pub(crate) address: String,
+    /// Shared with the clients, which send it in their first call.
+    pub(crate) token: String,
+}
+
+impl IpcSettings {
+    fn default_address() -> String {
+        dsr_ipc::DEFAULT_ADDRESS.to_string()
+    }
+}
+
+/// The part of the config read by the dinput8 proxy. It is parsed on its
+/// own, before the tool starts, so that a config error in the rest of the
+/// file doesn't keep the game from getting DirectInput.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct ProxyConfig {
+    #[serde(default)]
+    settings: ProxySettings,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProxySettings {
+    /// Other `dinput8.dll` mods, renamed, to load and forward DirectInput
+    /// to. Relative paths are relative to the tool's DLL.
+    #[serde(default)]
+    dinput8_chain: Vec<PathBuf>,
+}
+
+impl ProxyConfig {
+    pub(crate) fn parse(cfg: &str) -> Result<Self, String> {
+        toml::from_str::<ProxyConfig>(cfg)
+            .map_err(|e| format!("TOML configuration parse error: {}", e))
+    }
+
+    pub(crate) fn dinput8_chain(&self) -> &[PathBuf] {
+        &self.settings.dinput8_chain
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 #[serde(try_from = "IndicatorConfig")]
-pub(crate) struct Indicator {
+pub(crate) struct IndicatorSettings {
     pub(crate) indicator: IndicatorType,
     pub(crate) enabled: bool,
+    pub(crate) layout: IndicatorLayout,
 }
 
-impl Indicator {
-    fn default_set() -> Vec<Indicator> {
+impl IndicatorSettings {
+    fn new(indicator: IndicatorType, enabled: bool) -> Self {
+        IndicatorSettings { indicator, enabled, layout: IndicatorLayout::default() }
+    }
+
+    fn default_set() -> Vec<IndicatorSettings> {
         vec![
-            Indicator {
-                indicator: IndicatorType::GameVersion,
-                enabled: true,
-            },
-            Indicator {
-                indicator: IndicatorType::Igt,
-                enabled: true,
-            },
-            Indicator {
-                indicator: IndicatorType::Position,
-                enabled: false,
-            },
-            Indicator {
-                indicator: IndicatorType::PositionChange,
-                enabled: false,
-            },
-            Indicator {
-                indicator: IndicatorType::Animation,
-                enabled: false,
-            },
-            Indicator {
-                indicator: IndicatorType::Fps,
-                enabled: false,
-            },
-            Indicator {
-                indicator: IndicatorType::FrameCount,
-                enabled: false,
-            },
-            Indicator {
-                indicator: IndicatorType::ImguiDebug,
-                enabled: false,
-            },
+            IndicatorSettings::new(IndicatorType::GameVersion, true),
+            IndicatorSettings::new(IndicatorType::Igt, true),
+            IndicatorSettings::new(IndicatorType::Position, false),
+            IndicatorSettings::new(IndicatorType::PositionChange, false),
+            IndicatorSettings::new(IndicatorType::Animation, false),
+            IndicatorSettings::new(IndicatorType::Fps, false),
+            IndicatorSettings::new(IndicatorType::FrameCount, false),
+            IndicatorSettings::new(IndicatorType::ImguiDebug, false),
         ]
     }
 }
 
+/// Where and how an indicator is drawn.
+#[derive(Debug, Clone)]
+pub(crate) struct IndicatorLayout {
+    /// Screen corner the indicator is placed against. Indicators without an
+    /// anchor are drawn below the tool title, in configuration order.
+    pub(crate) anchor: Option<IndicatorAnchor>,
+    /// Distance in pixels from the anchor corner.
+    pub(crate) offset: [f32; 2],
+    pub(crate) font_scale: f32,
+    pub(crate) color: Option<[f32; 4]>,
+    pub(crate) format: Option<Template>,
+}
+
+impl Default for IndicatorLayout {
+    fn default() -> Self {
+        IndicatorLayout { anchor: None, offset: [0., 0.], font_scale: 1.0, color: None, format: None }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum IndicatorAnchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 struct IndicatorConfig {
     indicator: String,
     enabled: bool,
+    anchor: Option<IndicatorAnchor>,
+    #[serde(default)]
+    offset: [f32; 2],
+    font_scale: Option<f32>,
+    color: Option<[f32; 4]>,
+    format: Option<Template>,
 }
 
-impl TryFrom<IndicatorConfig> for Indicator {
+impl TryFrom<IndicatorConfig> for IndicatorSettings {
     type Error = String;
 
     fn try_from(indicator: IndicatorConfig) -> Result<Self, Self::Error> {
-        match indicator.indicator.as_str() {
-            "igt" => Ok(Indicator {
-                indicator: IndicatorType::Igt,
-                enabled: indicator.enabled,
-            }),
-            "position" => Ok(Indicator {
-                indicator: IndicatorType::Position,
-                enabled: indicator.enabled,
-            }),
-            "position_change" => Ok(Indicator {
-                indicator: IndicatorType::PositionChange,
-                enabled: indicator.enabled,
-            }),
-            "game_version" => Ok(Indicator {
-                indicator: IndicatorType::GameVersion,
-                enabled: indicator.enabled,
-            }),
-            "imgui_debug" => Ok(Indicator {
-                indicator: IndicatorType::ImguiDebug,
-                enabled: indicator.enabled,
-            }),
-            "fps" => Ok(Indicator {
-                indicator: IndicatorType::Fps,
-                enabled: indicator.enabled,
-            }),
-            "framecount" => Ok(Indicator {
-                indicator: IndicatorType::FrameCount,
-                enabled: indicator.enabled,
-            }),
-            "animation" => Ok(Indicator {
-                indicator: IndicatorType::Animation,
-                enabled: indicator.enabled,
-            }),
-            value => Err(format!("Unrecognized indicator: {value}")),
+        let indicator_type = IndicatorType::from_name(&indicator.indicator);
+
+        let font_scale = indicator.font_scale.unwrap_or(1.0);
+        if font_scale <= 0.0 {
+            return Err(format!(
+                "Indicator \"{}\": font_scale must be positive",
+                indicator.indicator
+            ));
         }
+
+        Ok(IndicatorSettings {
+            indicator: indicator_type,
+            enabled: indicator.enabled,
+            layout: IndicatorLayout {
+                anchor: indicator.anchor,
+                offset: indicator.offset,
+                font_scale,
+                color: indicator.color,
+                format: indicator.format,
+            },
+        })
     }
 }
 
@@ -166,6 +275,36 @@ pub(crate) enum IndicatorType {
     Fps,
     FrameCount,
     Animation,
+    /// An indicator contributed by one of the configured widgets, looked up
+    /// by name once the commands have been built.
+    Widget(String),
+}
+
+impl IndicatorType {
+    pub(crate) const BUILTIN_NAMES: &'static [&'static str] = &[
+        "igt",
+        "position",
+        "position_change",
+        "game_version",
+        "imgui_debug",
+        "fps",
+        "framecount",
+        "animation",
+    ];
+
+    fn from_name(name: &str) -> IndicatorType {
+        match name {
+            "igt" => IndicatorType::Igt,
+            "position" => IndicatorType::Position,
+            "position_change" => IndicatorType::PositionChange,
+            "game_version" => IndicatorType::GameVersion,
+            "imgui_debug" => IndicatorType::ImguiDebug,
+            "fps" => IndicatorType::Fps,
+            "framecount" => IndicatorType::FrameCount,
+            "animation" => IndicatorType::Animation,
+            name => IndicatorType::Widget(name.to_string()),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -210,6 +349,9 @@ enum CfgCommand {
     SavefileManager {
         #[serde(rename = "savefile_manager")]
         hotkey_load: PlaceholderOption<Key>,
+        /// Quit out after loading a save, so the game reloads it.
+        #[serde(default)]
+        quitout: bool,
     },
     // ItemSpawner {
     //     #[serde(rename = "item_spawner")]
@@ -246,18 +388,67 @@ enum CfgCommand {
     //     kind: OpenMenuKind,
     //     hotkey: Option<Key>,
     // },
-    // Quitout {
-    //     #[serde(rename = "quitout")]
-    //     hotkey: PlaceholderOption<Key>,
-    // },
+    Quitout {
+        #[serde(rename = "quitout")]
+        hotkey: PlaceholderOption<Key>,
+    },
     // Target {
     //     #[serde(rename = "target")]
     //     hotkey: PlaceholderOption<Key>,
     // },
+    PositionLibrary {
+        #[serde(rename = "position_library")]
+        teleport: PlaceholderOption<Key>,
+    },
     NudgePosition {
         nudge: f32,
         nudge_up: Option<Key>,
         nudge_down: Option<Key>,
+        nudge_forward: Option<Key>,
+        nudge_back: Option<Key>,
+        nudge_left: Option<Key>,
+        nudge_right: Option<Key>,
+        nudge_x_pos: Option<Key>,
+        nudge_x_neg: Option<Key>,
+        nudge_z_pos: Option<Key>,
+        nudge_z_neg: Option<Key>,
+        #[serde(default = "default_rotate")]
+        rotate: f32,
+        rotate_left: Option<Key>,
+        rotate_right: Option<Key>,
+    },
+    Noclip {
+        #[serde(rename = "noclip")]
+        toggle: PlaceholderOption<Key>,
+        #[serde(default = "default_noclip_speed")]
+        speed: f32,
+        forward: Option<Key>,
+        back: Option<Key>,
+        left: Option<Key>,
+        right: Option<Key>,
+        up: Option<Key>,
+        down: Option<Key>,
+        faster: Option<Key>,
+        slower: Option<Key>,
+    },
+    Splits {
+        #[serde(rename = "splits")]
+        segments: Vec<SegmentSpec>,
+        split: Option<Key>,
+        reset: Option<Key>,
+        skip: Option<Key>,
+        undo: Option<Key>,
+    },
+    RealTimer {
+        #[serde(rename = "real_timer")]
+        pause: PlaceholderOption<Key>,
+        reset: Option<Key>,
+    },
+    Macro {
+        #[serde(rename = "macro")]
+        label: String,
+        hotkey: Option<Key>,
+        steps: Vec<MacroStep>,
     },
     Group {
         #[serde(rename = "group")]
@@ -266,11 +457,72 @@ enum CfgCommand {
     },
 }
 
+/// A step of a `macro` command.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MacroStep {
+    Run {
+        run: String,
+    },
+    Flag {
+        flag: FlagSpec,
+        state: bool,
+    },
+    Speed {
+        speed: f32,
+    },
+    Souls {
+        souls: u32,
+    },
+    Position {
+        position: [f32; 3],
+        angle: Option<f32>,
+    },
+    Wait {
+        wait: Wait,
+    },
+}
+
+impl MacroStep {
+    /// Builds the step of macro `label`. Actions run by name are checked
+    /// once every command has registered its own.
+    fn into_step(self, label: &str, ctx: &mut BuildContext) -> Step<Action> {
+        let chains = ctx.chains;
+        match self {
+            MacroStep::Run { run } => {
+                ctx.palette.require(label, &run);
+                Step::Run(Action::Run { name: run, actions: ctx.palette.actions() })
+            }
+            MacroStep::Flag { flag, state } => Step::Run(Action::Flag {
+                flag: (flag.getter)(chains).clone(),
+                label: flag.label,
+                state,
+            }),
+            MacroStep::Speed { speed } => Step::Run(Action::Speed(chains.speed.clone(), speed)),
+            MacroStep::Souls { souls } => Step::Run(Action::AddSouls(chains.souls.clone(), souls)),
+            MacroStep::Position { position, angle } => Step::Run(Action::Position {
+                ptr: chains.position.clone(),
+                pos: position,
+                angle,
+            }),
+            MacroStep::Wait { wait } => Step::Wait(wait),
+        }
+    }
+}
+
+fn default_noclip_speed() -> f32 {
+    10.
+}
+
+fn default_rotate() -> f32 {
+    90.
+}
+
 #[derive(Deserialize)]
 #[serde(try_from = "String")]
-struct FlagSpec {
-    label: String,
-    getter: fn(&PointerChains) -> &Bitflag<u8>,
+pub(crate) struct FlagSpec {
+    pub(crate) label: String,
+    pub(crate) getter: fn(&PointerChains) -> &Bitflag<u8>,
 }
 
 impl std::fmt::Debug for FlagSpec {
@@ -327,15 +579,35 @@ impl TryFrom<String> for FlagSpec {
 }
 
 impl CfgCommand {
-    fn into_widget(self, settings: &Settings, chains: &PointerChains) -> Box<dyn Widget> {
-        match self {
+    fn into_widget(self, settings: &Settings, ctx: &mut BuildContext) -> Box<dyn Widget> {
+        // The others also run every frame, and check for the palette before
+        // handling their hotkeys themselves.
+        let hotkeys_only = !matches!(
+            self,
+            CfgCommand::Position { .. }
+                | CfgCommand::Noclip { .. }
+                | CfgCommand::Splits { .. }
+                | CfgCommand::RealTimer { .. }
+                | CfgCommand::Macro { .. }
+                | CfgCommand::Group { .. }
+        );
+
+        let widget = match self {
             CfgCommand::Flag { flag, hotkey: key } => {
-                flag_widget(&flag.label, (flag.getter)(chains).clone(), key)
+                let bitflag = (flag.getter)(ctx.chains).clone();
+                flag_widget(&flag.label, bitflag, key, ctx.restore, ctx.palette)
             }
             CfgCommand::Label { label } => label_widget(label.as_str()),
             CfgCommand::SavefileManager {
                 hotkey_load: key_load,
-            } => savefile_manager(key_load.into_option(), settings.display),
+                quitout,
+            } => savefile_manager(
+                key_load.into_option(),
+                settings.display,
+                quitout.then(|| ctx.chains.quitout.clone()),
+                settings.backups,
+                ctx.palette,
+            ),
             // CfgCommand::ItemSpawner { hotkey_load: key_load } => Box::new(ItemSpawner::new(
             //     chains.spawn_item_func_ptr as usize,
             //     chains.map_item_man as usize,
@@ -343,24 +615,93 @@ impl CfgCommand {
             //     key_load.into_option(),
             //     settings.display,
             // )),
-            CfgCommand::Position { position, save } => {
-                save_position(chains.position.clone(), position.into_option(), save)
-            }
+            CfgCommand::Position { position, save } => save_position(
+                ctx.chains,
+                position.into_option(),
+                save,
+                ctx.indicators,
+                ctx.restore,
+                ctx.palette,
+            ),
+            CfgCommand::PositionLibrary { teleport } => position_library(
+                ctx.chains.position.clone(),
+                ctx.chains.map_id.clone(),
+                teleport.into_option(),
+                ctx.palette,
+            ),
             CfgCommand::NudgePosition {
                 nudge,
                 nudge_up,
                 nudge_down,
-            } => nudge_position(chains.position.clone(), nudge, nudge_up, nudge_down),
+                nudge_forward,
+                nudge_back,
+                nudge_left,
+                nudge_right,
+                nudge_x_pos,
+                nudge_x_neg,
+                nudge_z_pos,
+                nudge_z_neg,
+                rotate,
+                rotate_left,
+                rotate_right,
+            } => nudge_position(
+                ctx.chains.position.clone(),
+                nudge,
+                rotate,
+                nudge_up,
+                nudge_down,
+                NudgeKeys {
+                    forward: nudge_forward,
+                    back: nudge_back,
+                    left: nudge_left,
+                    right: nudge_right,
+                    x_pos: nudge_x_pos,
+                    x_neg: nudge_x_neg,
+                    z_pos: nudge_z_pos,
+                    z_neg: nudge_z_neg,
+                    rotate_left,
+                    rotate_right,
+                },
+                ctx.palette,
+            ),
+            CfgCommand::Noclip {
+                toggle,
+                speed,
+                forward,
+                back,
+                left,
+                right,
+                up,
+                down,
+                faster,
+                slower,
+            } => noclip(
+                ctx.chains,
+                toggle.into_option(),
+                speed,
+                NoclipKeys { forward, back, left, right, up, down, faster, slower },
+                ctx.restore,
+                ctx.palette,
+            ),
             CfgCommand::CharacterStats { value } => character_stats_edit(
-                chains.character_stats.clone(),
+                ctx.chains.character_stats.clone(),
                 value.into_option(),
                 settings.display,
             ),
-            CfgCommand::CycleSpeed { values, hotkey } => {
-                cycle_speed(values.as_slice(), chains.speed.clone(), hotkey)
+            CfgCommand::CycleSpeed { values, hotkey } => cycle_speed(
+                values.as_slice(),
+                ctx.chains.speed.clone(),
+                hotkey,
+                ctx.indicators,
+                ctx.restore,
+                ctx.palette,
+            ),
+            CfgCommand::Souls { amount, hotkey } => {
+                souls(amount, ctx.chains.souls.clone(), hotkey, ctx.palette)
+            }
+            CfgCommand::Quitout { hotkey } => {
+                quitout(ctx.chains.quitout.clone(), hotkey.into_option(), ctx.palette)
             }
-            CfgCommand::Souls { amount, hotkey } => souls(amount, chains.souls.clone(), hotkey),
-            // CfgCommand::Quitout { hotkey } => quitout(chains.quitout.clone(), hotkey.into_option()),
             // CfgCommand::OpenMenu { hotkey, kind } => {
             //     open_menu(kind, chains.travel_ptr, chains.attune_ptr, hotkey)
             // }
@@ -369,21 +710,44 @@ impl CfgCommand {
             //     chains.xa,
             //     hotkey.into_option(),
             // )),
-            CfgCommand::Group { label, commands } => group(
-                label.as_str(),
-                commands
-                    .into_iter()
-                    .map(|c| c.into_widget(settings, chains))
-                    .collect(),
-                settings.display,
+            CfgCommand::Splits { segments, split, reset, skip, undo } => splits(
+                segments,
+                ctx.chains,
+                split,
+                reset,
+                skip,
+                undo,
+                ctx.indicators,
+                ctx.livesplit.cloned(),
+                ctx.palette,
             ),
+            CfgCommand::RealTimer { pause, reset } => {
+                real_timer(ctx.chains, pause.into_option(), reset, ctx.indicators, ctx.palette)
+            }
+            CfgCommand::Macro { label, hotkey, steps } => {
+                let steps = steps.into_iter().map(|s| s.into_step(&label, ctx)).collect();
+                macro_widget(&label, steps, ctx.chains, hotkey, ctx.restore, ctx.palette)
+            }
+            CfgCommand::Group { label, commands } => {
+                ctx.palette.push_group(&label);
+                let commands = commands.into_iter().map(|c| c.into_widget(settings, ctx)).collect();
+                ctx.palette.pop_group();
+                group(label.as_str(), commands, settings.display)
+            }
+        };
+
+        if hotkeys_only {
+            hotkeys(widget)
+        } else {
+            widget
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Config;
+    use super::{CfgCommand, Config, IndicatorAnchor, MacroStep, ProxyConfig};
+    use crate::macros::Wait;
 
     #[test]
     fn test_parse_ok() {
@@ -409,4 +773,293 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_parse_indicator_layout() {
+        let config = Config::parse(
+            r#"commands = []
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            indicators = [
+              { indicator = "igt", enabled = true, anchor = "top_right", offset = [16.0, 16.0], font_scale = 1.5, color = [1.0, 0.8, 0.0, 1.0], format = "{minutes}:{seconds:02}" },
+              { indicator = "position", enabled = false },
+            ]
+            "#,
+        )
+        .unwrap();
+
+        let igt = &config.settings.indicators[0].layout;
+        assert!(matches!(igt.anchor, Some(IndicatorAnchor::TopRight)));
+        assert_eq!(igt.offset, [16.0, 16.0]);
+        assert_eq!(igt.font_scale, 1.5);
+        assert!(igt.format.is_some());
+        assert!(config.settings.indicators[1].layout.anchor.is_none());
+
+        assert!(Config::parse(
+            r#"commands = []
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            indicators = [ { indicator = "framecount", enabled = true, format = "{frames" } ]
+            "#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_livesplit() {
+        let config = Config::parse(
+            r#"commands = []
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            [settings.livesplit]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.settings.livesplit.unwrap().address, "127.0.0.1:16834");
+
+        let config = Config::parse(
+            r#"commands = []
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            "#,
+        )
+        .unwrap();
+        assert!(config.settings.livesplit.is_none());
+    }
+
+    #[test]
+    fn test_parse_ipc() {
+        let config = Config::parse(
+            r#"commands = []
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            [settings.ipc]
+            token = "hunter2"
+            "#,
+        )
+        .unwrap();
+        let ipc = config.settings.ipc.unwrap();
+        assert_eq!((ipc.address.as_str(), ipc.token.as_str()), ("127.0.0.1:16835", "hunter2"));
+
+        let config = Config::parse(
+            r#"commands = []
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            [settings.ipc]
+            address = "localhost:9000"
+            token = "hunter2"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.settings.ipc.unwrap().address, "localhost:9000");
+
+        // Without a token, anything able to reach the port could call it.
+        assert!(Config::parse(
+            r#"commands = []
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            [settings.ipc]
+            "#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_quitout() {
+        let config = Config::parse(
+            r#"commands = [
+              { savefile_manager = "home", quitout = true },
+              { savefile_manager = "end" },
+              { quitout = "p" },
+            ]
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            "#,
+        )
+        .unwrap();
+
+        assert!(matches!(config.commands[0], CfgCommand::SavefileManager { quitout: true, .. }));
+        assert!(matches!(config.commands[1], CfgCommand::SavefileManager { quitout: false, .. }));
+        assert!(matches!(config.commands[2], CfgCommand::Quitout { .. }));
+    }
+
+    #[test]
+    fn test_parse_nudges() {
+        let config = Config::parse(
+            r#"commands = [
+              { nudge = 1.0, nudge_up = "[", nudge_down = "]" },
+              { nudge = 0.1, nudge_forward = "alt+up", rotate = 45.0, rotate_left = "alt+pageup" },
+            ]
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            "#,
+        )
+        .unwrap();
+
+        let CfgCommand::NudgePosition { rotate, nudge_forward, .. } = &config.commands[0] else {
+            panic!("Expected a nudge command");
+        };
+        assert_eq!(*rotate, 90.0);
+        assert!(nudge_forward.is_none());
+
+        let CfgCommand::NudgePosition { rotate, nudge_forward, rotate_right, .. } =
+            &config.commands[1]
+        else {
+            panic!("Expected a nudge command");
+        };
+        assert_eq!(*rotate, 45.0);
+        assert!(nudge_forward.is_some());
+        assert!(rotate_right.is_none());
+    }
+
+    #[test]
+    fn test_parse_macro() {
+        let config = Config::parse(
+            r#"commands = [
+              { macro = "Setup", hotkey = "ctrl+m", steps = [
+                { position = [1.0, 2.0, 3.0], angle = 0.5 },
+                { wait = "load" },
+                { flag = "no_death", state = true },
+                { speed = 2.0 },
+                { run = "Practice / Save position" },
+                { wait = "igt_change" },
+                { wait = { frames = 30 } },
+                { souls = 10000 },
+                { wait = { ms = 500 } },
+              ] },
+            ]
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            "#,
+        )
+        .unwrap();
+
+        let CfgCommand::Macro { label, hotkey, steps } = &config.commands[0] else {
+            panic!("Expected a macro command");
+        };
+        assert_eq!(label, "Setup");
+        assert!(hotkey.is_some());
+        assert!(matches!(
+            steps[..],
+            [
+                MacroStep::Position { angle: Some(_), .. },
+                MacroStep::Wait { wait: Wait::Load },
+                MacroStep::Flag { state: true, .. },
+                MacroStep::Speed { .. },
+                MacroStep::Run { .. },
+                MacroStep::Wait { wait: Wait::IgtChange },
+                MacroStep::Wait { wait: Wait::Frames(30) },
+                MacroStep::Souls { souls: 10000 },
+                MacroStep::Wait { wait: Wait::Ms(500) },
+            ]
+        ));
+        let MacroStep::Flag { flag, .. } = &steps[2] else { unreachable!() };
+        assert_eq!(flag.label, "No death");
+
+        assert!(Config::parse(
+            r#"commands = [ { macro = "Bad", steps = [ { wait = "forever" } ] } ]
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            "#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_backups() {
+        let config = Config::parse(
+            r#"commands = []
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            [settings.backups]
+            keep = 5
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.settings.backups.keep, 5);
+        assert_eq!(config.settings.backups.max_size_mb, 100);
+    }
+
+    #[test]
+    fn test_parse_proxy() {
+        let config = ProxyConfig::parse(
+            r#"commands = [ { boh = 3 } ]
+            [settings]
+            dinput8_chain = ["dinput8_fps.dll", "C:\\mods\\dinput8_textures.dll"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.dinput8_chain().len(), 2);
+        assert_eq!(config.dinput8_chain()[0].to_str(), Some("dinput8_fps.dll"));
+
+        let config = ProxyConfig::parse(include_str!("../../dark_souls_remastered_tool.toml"));
+        assert!(config.unwrap().dinput8_chain().is_empty());
+        assert!(ProxyConfig::parse("commands = [").is_err());
+    }
+
+    #[test]
+    fn test_parse_load_dlls() {
+        let config = Config::parse(
+            r#"commands = []
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            load_dlls = ["fps_fix.dll", "textures\\loader.dll"]
+            load_dlls_delay_ms = 2000
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.settings.load_dlls.len(), 2);
+        assert_eq!(config.settings.load_dlls_delay_ms, 2000);
+
+        let config = Config::parse(
+            r#"commands = []
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            "#,
+        )
+        .unwrap();
+        assert!(config.settings.load_dlls.is_empty());
+        assert_eq!(config.settings.load_dlls_delay_ms, 0);
+    }
+
+    #[test]
+    fn test_parse_palette() {
+        let config = Config::parse(
+            r#"commands = []
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            palette = "ctrl+p"
+            "#,
+        )
+        .unwrap();
+        assert!(config.settings.palette.is_some());
+
+        let config = Config::parse(
+            r#"commands = []
+            [settings]
+            log_level = "DEBUG"
+            display = "0"
+            palette = "ctrl+notakey"
+            "#,
+        );
+        assert!(config.is_err());
+
+        let config = Config::parse(include_str!("../../dark_souls_remastered_tool.toml"));
+        assert!(config.unwrap().settings.palette.is_none());
+    }
 }
@@ -0,0 +1,351 @@
+use std::fmt::Write;
+
+use hudhook::tracing::error;
+use imgui::Ui;
+use libdsr::prelude::*;
+
+use super::format::{Template, Value};
+use super::Indicator;
+use crate::config::IndicatorType;
+
+/// Build the indicator for a built-in type. `format` overrides the default
+/// format string; if it references unknown placeholders, the error is logged
+/// and the default is used instead.
+pub(crate) fn make_indicator(
+    indicator: &IndicatorType,
+    format: Option<Template>,
+    chains: &PointerChains,
+) -> Box<dyn Indicator> {
+    match indicator {
+        IndicatorType::Igt => Box::new(Igt::new(chains.igt.clone(), format)),
+        IndicatorType::Position => Box::new(Position::new(chains.position.clone(), format)),
+        IndicatorType::PositionChange => {
+            Box::new(PositionChange::new(chains.position.1.clone(), format))
+        }
+        IndicatorType::GameVersion => Box::new(GameVersion::new(format)),
+        IndicatorType::ImguiDebug => Box::new(ImguiDebug),
+        IndicatorType::Fps => Box::new(Fps::new(format)),
+        IndicatorType::FrameCount => Box::new(FrameCount::new(format)),
+        IndicatorType::Animation => Box::new(Animation),
+    }
+}
+
+fn validated(label: &str, format: Option<Template>, fields: &[&str]) -> Option<Template> {
+    format.and_then(|t| match t.validate(fields) {
+        Ok(()) => Some(t),
+        Err(e) => {
+            error!("Indicator \"{label}\": {e}");
+            None
+        }
+    })
+}
+
+fn template(label: &str, format: Option<Template>, default: &str, fields: &[&str]) -> Template {
+    validated(label, format, fields)
+        .unwrap_or_else(|| Template::parse(default).expect("default indicator format"))
+}
+
+struct Igt {
+    ptr: PointerChain<u32>,
+    template: Template,
+    buf: String,
+}
+
+impl Igt {
+    const FIELDS: &'static [&'static str] = &["hours", "minutes", "seconds", "millis", "raw"];
+
+    fn new(ptr: PointerChain<u32>, format: Option<Template>) -> Self {
+        let template = template(
+            "IGT Timer",
+            format,
+            "IGT {hours:02}:{minutes:02}:{seconds:02}.{millis:02}",
+            Self::FIELDS,
+        );
+        Self {
+            ptr,
+            template,
+            buf: String::new(),
+        }
+    }
+}
+
+impl Indicator for Igt {
+    fn label(&self) -> &str {
+        "IGT Timer"
+    }
+
+    fn render(&mut self, ui: &Ui) {
+        if let Some(igt) = self.ptr.read() {
+            let total_seconds = igt / 1000;
+            self.template.render(&mut self.buf, |field| {
+                Some(Value::Int(match field {
+                    "hours" => total_seconds / 3600,
+                    "minutes" => total_seconds / 60 % 60,
+                    "seconds" => total_seconds % 60,
+                    "millis" => (igt % 1000) / 10,
+                    "raw" => igt,
+                    _ => return None,
+                } as i64))
+            });
+            ui.text(&self.buf);
+        }
+    }
+}
+
+struct Position {
+    ptr_angle: PointerChain<f32>,
+    ptr_pos: PointerChain<[f32; 3]>,
+    template: Option<Template>,
+    bufs: [String; 4],
+}
+
+impl Position {
+    const FIELDS: &'static [&'static str] = &["x", "y", "z", "angle"];
+
+    fn new(ptr: (PointerChain<f32>, PointerChain<[f32; 3]>), format: Option<Template>) -> Self {
+        // Without a custom format, the coordinates are drawn in their own colors.
+        let template = validated("Player Position", format, Self::FIELDS);
+        Self {
+            ptr_angle: ptr.0,
+            ptr_pos: ptr.1,
+            template,
+            bufs: Default::default(),
+        }
+    }
+}
+
+impl Indicator for Position {
+    fn label(&self) -> &str {
+        "Player Position"
+    }
+
+    fn render(&mut self, ui: &Ui) {
+        let (Some([x, y, z]), Some(a)) = (self.ptr_pos.read(), self.ptr_angle.read()) else {
+            return;
+        };
+
+        if let Some(template) = &self.template {
+            template.render(&mut self.bufs[0], |field| {
+                Some(Value::Float(match field {
+                    "x" => x,
+                    "y" => y,
+                    "z" => z,
+                    "angle" => a,
+                    _ => return None,
+                } as f64))
+            });
+            ui.text(&self.bufs[0]);
+            return;
+        }
+
+        for (buf, v) in self.bufs.iter_mut().zip([x, y, z, a]) {
+            buf.clear();
+            write!(buf, "{v:.3}").ok();
+        }
+
+        ui.text_colored([0.7048, 0.1228, 0.1734, 1.], &self.bufs[0]);
+        ui.same_line();
+        ui.text_colored([0.1161, 0.5327, 0.3512, 1.], &self.bufs[1]);
+        ui.same_line();
+        ui.text_colored([0.1445, 0.2852, 0.5703, 1.], &self.bufs[2]);
+        ui.same_line();
+        ui.text(&self.bufs[3]);
+    }
+}
+
+struct PositionChange {
+    ptr: PointerChain<[f32; 3]>,
+    prev: [f32; 3],
+    template: Template,
+    buf: String,
+}
+
+impl PositionChange {
+    const FIELDS: &'static [&'static str] = &["xyz", "xz", "y"];
+
+    fn new(ptr: PointerChain<[f32; 3]>, format: Option<Template>) -> Self {
+        let template = template(
+            "Player Velocity",
+            format,
+            "[XYZ] {xyz:.6} | [XZ] {xz:.6} | [Y] {y:.6}",
+            Self::FIELDS,
+        );
+        Self {
+            ptr,
+            prev: Default::default(),
+            template,
+            buf: String::new(),
+        }
+    }
+}
+
+impl Indicator for PositionChange {
+    fn label(&self) -> &str {
+        "Player Velocity"
+    }
+
+    fn render(&mut self, ui: &Ui) {
+        if let Some([x, y, z]) = self.ptr.read() {
+            let [px, py, pz] = self.prev;
+            let xyz = ((x - px).powf(2.0) + (y - py).powf(2.0) + (z - pz).powf(2.0)).sqrt();
+            let xz = ((x - px).powf(2.0) + (z - pz).powf(2.0)).sqrt();
+            let dy = y - py;
+
+            self.template.render(&mut self.buf, |field| {
+                Some(Value::Float(match field {
+                    "xyz" => xyz,
+                    "xz" => xz,
+                    "y" => dy,
+                    _ => return None,
+                } as f64))
+            });
+            ui.text(&self.buf);
+
+            self.prev = [x, y, z];
+        }
+    }
+}
+
+struct GameVersion {
+    buf: String,
+}
+
+impl GameVersion {
+    const FIELDS: &'static [&'static str] = &["major", "minor", "patch"];
+
+    fn new(format: Option<Template>) -> Self {
+        let template = template(
+            "Game Version",
+            format,
+            "Game Ver {major}.{minor:02}.{patch}",
+            Self::FIELDS,
+        );
+        let (maj, min, patch): (u32, u32, u32) = (*VERSION).into();
+        let mut buf = String::new();
+        template.render(&mut buf, |field| {
+            Some(Value::Int(match field {
+                "major" => maj,
+                "minor" => min,
+                "patch" => patch,
+                _ => return None,
+            } as i64))
+        });
+        Self { buf }
+    }
+}
+
+impl Indicator for GameVersion {
+    fn label(&self) -> &str {
+        "Game Version"
+    }
+
+    fn render(&mut self, ui: &Ui) {
+        ui.text(&self.buf);
+    }
+}
+
+struct Fps {
+    template: Template,
+    buf: String,
+}
+
+impl Fps {
+    fn new(format: Option<Template>) -> Self {
+        Self {
+            template: template("FPS", format, "FPS {fps:.1}", &["fps"]),
+            buf: String::new(),
+        }
+    }
+}
+
+impl Indicator for Fps {
+    fn label(&self) -> &str {
+        "FPS"
+    }
+
+    fn render(&mut self, ui: &Ui) {
+        let fps = ui.io().framerate as f64;
+        self.template.render(&mut self.buf, |field| match field {
+            "fps" => Some(Value::Float(fps)),
+            _ => None,
+        });
+        ui.text(&self.buf);
+    }
+}
+
+struct FrameCount {
+    count: u32,
+    template: Template,
+    buf: String,
+}
+
+impl FrameCount {
+    fn new(format: Option<Template>) -> Self {
+        Self {
+            count: 0,
+            template: template("Frame Counter", format, "Frame count {frames}", &["frames"]),
+            buf: String::new(),
+        }
+    }
+}
+
+impl Indicator for FrameCount {
+    fn label(&self) -> &str {
+        "Frame Counter"
+    }
+
+    fn tick(&mut self, _ui: &Ui) {
+        self.count += 1;
+    }
+
+    fn render(&mut self, ui: &Ui) {
+        let count = self.count as i64;
+        self.template.render(&mut self.buf, |field| match field {
+            "frames" => Some(Value::Int(count)),
+            _ => None,
+        });
+        ui.text(&self.buf);
+    }
+
+    fn resettable(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        self.count = 0;
+    }
+}
+
+// Display some imgui debug information. Very expensive.
+struct ImguiDebug;
+
+impl Indicator for ImguiDebug {
+    fn label(&self) -> &str {
+        "ImGui Debug Info"
+    }
+
+    fn render(&mut self, ui: &Ui) {
+        let io = ui.io();
+        ui.text(format!("Mouse position     {:?}", io.mouse_pos));
+        ui.text(format!("Mouse down         {:?}", io.mouse_down));
+        ui.text(format!("Want capture mouse {:?}", io.want_capture_mouse));
+        ui.text(format!("Want capture kbd   {:?}", io.want_capture_keyboard));
+        ui.text(format!("Want text input    {:?}", io.want_text_input));
+        ui.text(format!("Want set mouse pos {:?}", io.want_set_mouse_pos));
+        ui.text(format!("Any item active    {:?}", ui.is_any_item_active()));
+        ui.text(format!("Any item hovered   {:?}", ui.is_any_item_hovered()));
+        ui.text(format!("Any item focused   {:?}", ui.is_any_item_focused()));
+        ui.text(format!("Any mouse down     {:?}", ui.is_any_mouse_down()));
+    }
+}
+
+// There is no animation pointer chain yet, so this draws nothing.
+struct Animation;
+
+impl Indicator for Animation {
+    fn label(&self) -> &str {
+        "Animation"
+    }
+
+    fn render(&mut self, _ui: &Ui) {}
+}
@@ -0,0 +1,222 @@
+use std::fmt::Write;
+
+use serde::Deserialize;
+
+/// A value that can be substituted into a [`Template`] placeholder.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Field {
+        name: String,
+        width: usize,
+        zero_pad: bool,
+        precision: Option<usize>,
+    },
+}
+
+/// A format string with named placeholders, e.g. `IGT {hours:02}:{minutes:02}`.
+///
+/// Placeholders support a zero-padded width (`{frames:06}`), a plain width
+/// (`{x:8}`) and a precision for floats (`{x:.3}`, `{x:8.2}`). Literal braces
+/// are written as `{{` and `}}`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct Template(Vec<Part>);
+
+impl Template {
+    pub(crate) fn parse(s: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => return Err(format!("Unterminated placeholder in \"{s}\"")),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(parse_field(&field).map_err(|e| format!("{e} in \"{s}\""))?);
+                }
+                '}' => return Err(format!("Unmatched '}}' in \"{s}\"")),
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Template(parts))
+    }
+
+    /// Names of all placeholders in the template.
+    pub(crate) fn fields(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().filter_map(|p| match p {
+            Part::Field { name, .. } => Some(name.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    /// Checks that every placeholder is one of `known`.
+    pub(crate) fn validate(&self, known: &[&str]) -> Result<(), String> {
+        match self.fields().find(|f| !known.contains(f)) {
+            Some(f) => Err(format!(
+                "Unknown placeholder {{{f}}}, expected one of: {}",
+                known
+                    .iter()
+                    .map(|k| format!("{{{k}}}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Renders the template into `out`, which is cleared first. Placeholders
+    /// for which `lookup` returns `None` are rendered as `?`.
+    pub(crate) fn render(&self, out: &mut String, lookup: impl Fn(&str) -> Option<Value>) {
+        out.clear();
+        for part in &self.0 {
+            match part {
+                Part::Literal(s) => out.push_str(s),
+                Part::Field {
+                    name,
+                    width,
+                    zero_pad,
+                    precision,
+                } => {
+                    let width = *width;
+                    match (lookup(name), zero_pad, precision) {
+                        (Some(Value::Int(v)), true, _) => write!(out, "{v:0width$}"),
+                        (Some(Value::Int(v)), false, _) => write!(out, "{v:width$}"),
+                        (Some(Value::Float(v)), true, Some(p)) => write!(out, "{v:0width$.p$}"),
+                        (Some(Value::Float(v)), false, Some(p)) => write!(out, "{v:width$.p$}"),
+                        (Some(Value::Float(v)), true, None) => write!(out, "{v:0width$}"),
+                        (Some(Value::Float(v)), false, None) => write!(out, "{v:width$}"),
+                        (None, _, _) => write!(out, "?"),
+                    }
+                    .ok();
+                }
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Template {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Template::parse(&value)
+    }
+}
+
+fn parse_field(field: &str) -> Result<Part, String> {
+    let (name, spec) = match field.split_once(':') {
+        Some((name, spec)) => (name, spec),
+        None => (field, ""),
+    };
+
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("Invalid placeholder name \"{name}\""));
+    }
+
+    let (width, precision) = match spec.split_once('.') {
+        Some((width, precision)) => (width, Some(precision)),
+        None => (spec, None),
+    };
+
+    let zero_pad = width.starts_with('0');
+    let width = match width {
+        "" => 0,
+        w => w
+            .parse()
+            .map_err(|_| format!("Invalid width \"{w}\" for {{{name}}}"))?,
+    };
+    let precision = precision
+        .map(|p| {
+            p.parse()
+                .map_err(|_| format!("Invalid precision \"{p}\" for {{{name}}}"))
+        })
+        .transpose()?;
+
+    Ok(Part::Field {
+        name: name.to_string(),
+        width,
+        zero_pad,
+        precision,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(fmt: &str, lookup: impl Fn(&str) -> Option<Value>) -> String {
+        let mut out = String::new();
+        Template::parse(fmt).unwrap().render(&mut out, lookup);
+        out
+    }
+
+    #[test]
+    fn test_render() {
+        let igt = render(
+            "IGT {hours:02}:{minutes:02}:{seconds:02}.{millis:02}",
+            |f| match f {
+                "hours" => Some(Value::Int(1)),
+                "minutes" => Some(Value::Int(2)),
+                "seconds" => Some(Value::Int(34)),
+                "millis" => Some(Value::Int(5)),
+                _ => None,
+            },
+        );
+        assert_eq!(igt, "IGT 01:02:34.05");
+
+        let pos = render("{{{x:.3}}} {y:8.1}|{z:05}", |f| match f {
+            "x" => Some(Value::Float(1.23456)),
+            "y" => Some(Value::Float(-2.0)),
+            "z" => Some(Value::Int(42)),
+            _ => None,
+        });
+        assert_eq!(pos, "{1.235}     -2.0|00042");
+
+        assert_eq!(render("{missing}!", |_| None), "?!");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Template::parse("{unterminated").is_err());
+        assert!(Template::parse("unmatched}").is_err());
+        assert!(Template::parse("{bad name}").is_err());
+        assert!(Template::parse("{x:abc}").is_err());
+        assert!(Template::parse("{x:.y}").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let t = Template::parse("{frames} {fps}").unwrap();
+        assert!(t.validate(&["frames", "fps"]).is_ok());
+        assert!(t.validate(&["frames"]).is_err());
+    }
+}
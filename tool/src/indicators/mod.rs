@@ -0,0 +1,235 @@
+pub(crate) mod builtin;
+pub(crate) mod format;
+
+use std::collections::BTreeMap;
+
+use hudhook::tracing::{error, warn};
+use imgui::{Condition, StyleColor, Ui, WindowFlags};
+use libdsr::prelude::*;
+
+use crate::config::{IndicatorAnchor, IndicatorSettings, IndicatorType};
+use builtin::make_indicator;
+
+/// A line of information drawn on the HUD while the tool window is closed.
+///
+/// Each indicator type has its own implementation; the layout (position,
+/// color, scale) is handled by [`IndicatorSlot`], so implementors only draw
+/// their own text.
+pub(crate) trait Indicator: Send + Sync {
+    /// Label shown in the indicators popup.
+    fn label(&self) -> &str;
+
+    /// Called once per frame, whether or not the indicator is enabled.
+    fn tick(&mut self, _ui: &Ui) {}
+
+    /// Draw the indicator in the current window.
+    fn render(&mut self, ui: &Ui);
+
+    /// Whether the indicator can be reset from the indicators popup.
+    fn resettable(&self) -> bool {
+        false
+    }
+
+    fn reset(&mut self) {}
+}
+
+/// Implemented by widgets that contribute their own lines to the HUD.
+///
+/// The returned names are what users put in `settings.indicators` to enable
+/// the line, e.g. `{ indicator = "speed", enabled = true }`.
+pub(crate) trait HudProvider {
+    fn hud_indicators(&self) -> Vec<(&'static str, Box<dyn Indicator>)>;
+}
+
+/// Indicators contributed by widgets while the commands are being built.
+#[derive(Default)]
+pub(crate) struct IndicatorRegistry(BTreeMap<String, Box<dyn Indicator>>);
+
+impl IndicatorRegistry {
+    /// Register every indicator of `provider`. When the same name is
+    /// contributed more than once (e.g. several `position` commands), the
+    /// later ones get a numeric suffix: `saved_position`, `saved_position_2`...
+    pub(crate) fn register(&mut self, provider: &impl HudProvider) {
+        for (name, indicator) in provider.hud_indicators() {
+            let name = (1..)
+                .map(|i| match i {
+                    1 => name.to_string(),
+                    i => format!("{name}_{i}"),
+                })
+                .find(|n| !self.0.contains_key(n))
+                .unwrap();
+            self.0.insert(name, indicator);
+        }
+    }
+
+    fn take(&mut self, name: &str) -> Result<Box<dyn Indicator>, String> {
+        self.0.remove(name).ok_or_else(|| {
+            let available = IndicatorType::BUILTIN_NAMES
+                .iter()
+                .copied()
+                .chain(self.0.keys().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "Unrecognized indicator \"{name}\": it is not built in and no configured \
+                 command provides it. Available indicators: {available}"
+            )
+        })
+    }
+}
+
+/// Build the indicators listed in the settings, in order. Indicators that
+/// can't be resolved are skipped, and the errors are returned for display.
+pub(crate) fn build_slots(
+    settings: &[IndicatorSettings],
+    chains: &PointerChains,
+    mut registry: IndicatorRegistry,
+) -> (Vec<IndicatorSlot>, Vec<String>) {
+    let mut slots = Vec::new();
+    let mut errors = Vec::new();
+
+    for s in settings {
+        let indicator = match &s.indicator {
+            IndicatorType::Widget(name) => {
+                if s.layout.format.is_some() {
+                    warn!("Indicator \"{name}\" does not support custom formats");
+                }
+                registry.take(name)
+            }
+            indicator => Ok(make_indicator(indicator, s.layout.format.clone(), chains)),
+        };
+
+        match indicator {
+            Ok(indicator) => slots.push(IndicatorSlot::new(s.clone(), indicator)),
+            Err(e) => {
+                error!("{e}");
+                errors.push(e);
+            }
+        }
+    }
+
+    (slots, errors)
+}
+
+/// An indicator along with its user-configured settings.
+pub(crate) struct IndicatorSlot {
+    pub(crate) settings: IndicatorSettings,
+    pub(crate) indicator: Box<dyn Indicator>,
+}
+
+impl IndicatorSlot {
+    pub(crate) fn new(settings: IndicatorSettings, indicator: Box<dyn Indicator>) -> Self {
+        Self {
+            settings,
+            indicator,
+        }
+    }
+
+    /// Label shown in the indicators popup. Widget indicators use their
+    /// configured name, as a widget may contribute several similar lines.
+    pub(crate) fn label(&self) -> &str {
+        match &self.settings.indicator {
+            IndicatorType::Widget(name) => name,
+            _ => self.indicator.label(),
+        }
+    }
+
+    /// Draw the indicator in the current window, applying color and scale.
+    fn render_styled(&mut self, ui: &Ui) {
+        let layout = &self.settings.layout;
+        let color_token = layout
+            .color
+            .map(|c| ui.push_style_color(StyleColor::Text, c));
+        ui.set_window_font_scale(layout.font_scale);
+        self.indicator.render(ui);
+        ui.set_window_font_scale(1.0);
+        if let Some(token) = color_token {
+            token.pop();
+        }
+    }
+}
+
+/// Draw the enabled indicators that have no anchor, inside the current window.
+pub(crate) fn render_inline(ui: &Ui, slots: &mut [IndicatorSlot]) {
+    for slot in slots.iter_mut() {
+        if slot.settings.enabled && slot.settings.layout.anchor.is_none() {
+            slot.render_styled(ui);
+        }
+    }
+}
+
+/// Draw the enabled indicators that have an anchor, each in its own window
+/// placed relative to the corresponding corner of the screen.
+pub(crate) fn render_anchored(ui: &Ui, slots: &mut [IndicatorSlot]) {
+    let [dw, dh] = ui.io().display_size;
+
+    for (idx, slot) in slots.iter_mut().enumerate() {
+        if !slot.settings.enabled {
+            continue;
+        }
+
+        let Some(anchor) = slot.settings.layout.anchor else {
+            continue;
+        };
+
+        let [ox, oy] = slot.settings.layout.offset;
+        let (position, pivot) = match anchor {
+            IndicatorAnchor::TopLeft => ([ox, oy], [0., 0.]),
+            IndicatorAnchor::TopRight => ([dw - ox, oy], [1., 0.]),
+            IndicatorAnchor::BottomLeft => ([ox, dh - oy], [0., 1.]),
+            IndicatorAnchor::BottomRight => ([dw - ox, dh - oy], [1., 1.]),
+        };
+
+        ui.window(format!("##indicator_{idx}"))
+            .position(position, Condition::Always)
+            .position_pivot(pivot)
+            .bg_alpha(0.0)
+            .flags({
+                WindowFlags::NO_TITLE_BAR
+                    | WindowFlags::NO_RESIZE
+                    | WindowFlags::NO_MOVE
+                    | WindowFlags::NO_SCROLLBAR
+                    | WindowFlags::ALWAYS_AUTO_RESIZE
+                    | WindowFlags::NO_INPUTS
+            })
+            .build(|| slot.render_styled(ui));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Line;
+
+    impl Indicator for Line {
+        fn label(&self) -> &str {
+            "Line"
+        }
+
+        fn render(&mut self, _ui: &Ui) {}
+    }
+
+    struct Provider;
+
+    impl HudProvider for Provider {
+        fn hud_indicators(&self) -> Vec<(&'static str, Box<dyn Indicator>)> {
+            vec![("line", Box::new(Line))]
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = IndicatorRegistry::default();
+        registry.register(&Provider);
+        registry.register(&Provider);
+
+        assert!(registry.take("line").is_ok());
+        assert!(registry.take("line").is_err());
+
+        let err = registry.take("lnie").err().unwrap();
+        assert!(err.contains("\"lnie\""));
+        assert!(err.contains("line_2"));
+        assert!(err.contains("igt"));
+    }
+}
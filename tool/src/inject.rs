@@ -1,125 +1,660 @@
 use std::ffi::c_void;
+use std::fmt;
 use std::mem::{self, size_of};
-use std::path::PathBuf;
+use std::os::windows::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use windows::core::{s, w, Error, Result, HRESULT, HSTRING};
-use windows::Win32::Foundation::{CloseHandle, BOOL, HANDLE, MAX_PATH};
-use windows::Win32::System::Diagnostics::Debug::WriteProcessMemory;
+use windows::core::{s, w, Error, HSTRING, PCSTR};
+use windows::Win32::Foundation::{CloseHandle, BOOL, FILETIME, HANDLE, HWND, LPARAM, SYSTEMTIME};
+use windows::Win32::Storage::FileSystem::{
+    GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW, VS_FIXEDFILEINFO,
+};
+use windows::Win32::System::Diagnostics::Debug::{ReadProcessMemory, WriteProcessMemory};
 use windows::Win32::System::Diagnostics::ToolHelp::{
-    CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+    CreateToolhelp32Snapshot, Module32FirstW, Module32NextW, Process32FirstW, Process32NextW,
+    MODULEENTRY32W, PROCESSENTRY32W, TH32CS_SNAPMODULE, TH32CS_SNAPMODULE32, TH32CS_SNAPPROCESS,
 };
 use windows::Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress};
 use windows::Win32::System::Memory::{
     VirtualAllocEx, VirtualFreeEx, MEM_COMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_READWRITE,
 };
 use windows::Win32::System::Threading::{
-    CreateRemoteThread, GetExitCodeThread, OpenProcess, WaitForSingleObject, INFINITE,
-    PROCESS_ALL_ACCESS,
+    CreateRemoteThread, GetExitCodeThread, GetProcessTimes, OpenProcess, WaitForSingleObject,
+    INFINITE, PROCESS_ALL_ACCESS,
+};
+use windows::Win32::System::Time::{FileTimeToSystemTime, SystemTimeToTzSpecificLocalTime};
+use windows::Win32::UI::WindowsAndMessaging::{
+    EnumWindows, GetWindowThreadProcessId, IsWindowVisible,
 };
 
+use crate::launch::{ProcessEntry, ProcessList};
+
+/// The function of the tool's DLL that makes it eject itself.
+const EJECT_EXPORT: &str = "request_eject";
+
+/// How long the tool may take to eject once asked to. It ejects on its next
+/// frame, so this only runs out if the game is stuck or not rendering.
+const EJECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A module loaded in a process.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub name: String,
+    pub path: PathBuf,
+    pub base: usize,
+    pub size: u32,
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {:#x}", self.path.display(), self.base)
+    }
+}
+
+/// A running game, as listed to pick one to inject.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instance {
+    pub pid: u32,
+    /// Local start time.
+    pub started: Option<String>,
+    /// File version of the game executable.
+    pub version: Option<String>,
+    /// Whether the tool's DLL is loaded.
+    pub injected: bool,
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>8}  started {}  version {}",
+            self.pid,
+            self.started.as_deref().unwrap_or("?"),
+            self.version.as_deref().unwrap_or("?")
+        )?;
+        if self.injected {
+            write!(f, "  (injected)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of a successful injection or ejection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InjectReport {
+    pub pid: u32,
+    pub module: Module,
+}
+
+#[derive(Debug)]
+pub enum InjectError {
+    OpenProcess(u32, Error),
+    DllPath(PathBuf, std::io::Error),
+    ListModules(Error),
+    AlreadyInjected(Module),
+    NotInjected(String),
+    Kernel32(&'static str),
+    Alloc(Error),
+    WriteMemory(Error),
+    /// `WriteProcessMemory` succeeded but wrote fewer bytes than asked.
+    ShortWrite {
+        written: usize,
+        size: usize,
+    },
+    RemoteThread(&'static str, Error),
+    /// The module isn't in the target after `LoadLibraryW` returned.
+    LoadLibraryFailed(PathBuf),
+    /// The loaded module has no function to ask it to eject, e.g. it isn't
+    /// the tool.
+    NoEjectExport(Module, String),
+    /// The module was still loaded after asking it to eject.
+    EjectTimeout(Module),
+}
+
+impl fmt::Display for InjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InjectError::OpenProcess(pid, e) => write!(f, "Could not open process {pid}: {e}"),
+            InjectError::DllPath(path, e) => write!(f, "Invalid DLL {}: {e}", path.display()),
+            InjectError::ListModules(e) => write!(f, "Could not list the process modules: {e}"),
+            InjectError::AlreadyInjected(module) => write!(f, "Already injected: {module}"),
+            InjectError::NotInjected(name) => write!(f, "{name} is not loaded in the process"),
+            InjectError::Kernel32(function) => write!(f, "Could not find {function}"),
+            InjectError::Alloc(e) => write!(f, "Could not allocate memory in the process: {e}"),
+            InjectError::WriteMemory(e) => write!(f, "Could not write the DLL path: {e}"),
+            InjectError::ShortWrite { written, size } => {
+                write!(f, "Could not write the DLL path: wrote {written} of {size} bytes")
+            }
+            InjectError::RemoteThread(function, e) => {
+                write!(f, "Could not call {function} in the process: {e}")
+            }
+            InjectError::LoadLibraryFailed(path) => {
+                write!(f, "LoadLibraryW failed to load {}", path.display())
+            }
+            InjectError::NoEjectExport(module, e) => {
+                write!(f, "{module} can't be asked to eject: {e}")
+            }
+            InjectError::EjectTimeout(module) => {
+                write!(f, "{module} is still loaded after asking it to eject")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InjectError {}
+
+pub type Result<T> = std::result::Result<T, InjectError>;
+
 /// A process, open with the permissions appropriate for injection.
-pub struct Process(HANDLE);
+pub struct Process {
+    handle: HANDLE,
+    pid: u32,
+}
 
 impl Process {
-    /// Retrieve the process ID by executable name, returning the first match,
-    /// and open it with the appropriate permissions.
-    pub fn get_process_by_name(name: &str) -> Result<Self> {
-        unsafe { get_process_by_name64(name).map(Self) }
+    /// Open the process with the appropriate permissions.
+    pub fn open(pid: u32) -> Result<Self> {
+        let handle = unsafe { OpenProcess(PROCESS_ALL_ACCESS, BOOL(0), pid) }
+            .map_err(|e| InjectError::OpenProcess(pid, e))?;
+        Ok(Process { handle, pid })
     }
 
-    /// Inject the DLL in the process.
-    pub fn inject(&self, dll_path: PathBuf) -> Result<()> {
-        let proc_addr =
-            unsafe { GetProcAddress(GetModuleHandleW(w!("Kernel32"))?, s!("LoadLibraryW")) };
-        let dll_path = HSTRING::from(dll_path.canonicalize().unwrap().as_path());
+    /// List the modules loaded in the process.
+    pub fn modules(&self) -> Result<Vec<Module>> {
+        unsafe {
+            let snapshot =
+                CreateToolhelp32Snapshot(TH32CS_SNAPMODULE | TH32CS_SNAPMODULE32, self.pid)
+                    .map_err(InjectError::ListModules)?;
+            let mut module_entry32 = MODULEENTRY32W {
+                dwSize: mem::size_of::<MODULEENTRY32W>() as u32,
+                ..Default::default()
+            };
+
+            let mut modules = Vec::new();
+            let mut next = Module32FirstW(snapshot, &mut module_entry32);
+            while next.is_ok() {
+                modules.push(Module {
+                    name: from_wide(&module_entry32.szModule),
+                    path: from_wide(&module_entry32.szExePath).into(),
+                    base: module_entry32.modBaseAddr as usize,
+                    size: module_entry32.modBaseSize,
+                });
+                next = Module32NextW(snapshot, &mut module_entry32);
+            }
+
+            CloseHandle(snapshot).ok();
+            Ok(modules)
+        }
+    }
+
+    /// Describe the process, checking whether the DLL with the given file
+    /// name is loaded.
+    pub fn instance(&self, dll_name: &str) -> Instance {
+        let modules = self.modules().unwrap_or_default();
+        Instance {
+            pid: self.pid,
+            started: self.start_time(),
+            // The first module is the executable.
+            version: modules.first().and_then(|exe| file_version(&exe.path)),
+            injected: find_module(modules, dll_name).is_some(),
+        }
+    }
+
+    fn start_time(&self) -> Option<String> {
+        let mut created = FILETIME::default();
+        let mut unused = FILETIME::default();
+        let mut utc = SYSTEMTIME::default();
+        let mut local = SYSTEMTIME::default();
+        unsafe {
+            GetProcessTimes(self.handle, &mut created, &mut unused, &mut unused, &mut unused)
+                .ok()?;
+            FileTimeToSystemTime(&created, &mut utc).ok()?;
+            SystemTimeToTzSpecificLocalTime(None, &utc, &mut local).ok()?;
+        }
+        Some(format!(
+            "{}-{:02}-{:02} {:02}:{:02}:{:02}",
+            local.wYear, local.wMonth, local.wDay, local.wHour, local.wMinute, local.wSecond
+        ))
+    }
+
+    /// Find a loaded module by its file name.
+    pub fn find_module(&self, name: &str) -> Result<Option<Module>> {
+        Ok(find_module(self.modules()?, name))
+    }
+
+    /// Inject the DLL in the process, and check that it got loaded.
+    pub fn inject(&self, dll_path: &Path) -> Result<InjectReport> {
+        let dll_path =
+            dll_path.canonicalize().map_err(|e| InjectError::DllPath(dll_path.to_path_buf(), e))?;
+        let name = file_name(&dll_path);
+        if let Some(module) = self.find_module(&name)? {
+            return Err(InjectError::AlreadyInjected(module));
+        }
+
+        let wide_path: Vec<u16> = dll_path.as_os_str().encode_wide().chain([0]).collect();
+        let size = wide_path.len() * size_of::<u16>();
         let dll_path_buf = unsafe {
-            VirtualAllocEx(
-                self.0,
-                None,
-                (MAX_PATH as usize) * size_of::<u16>(),
-                MEM_RESERVE | MEM_COMMIT,
-                PAGE_READWRITE,
-            )
+            VirtualAllocEx(self.handle, None, size, MEM_RESERVE | MEM_COMMIT, PAGE_READWRITE)
         };
+        if dll_path_buf.is_null() {
+            return Err(InjectError::Alloc(Error::from_win32()));
+        }
 
         let mut bytes_written = 0usize;
-        let res = unsafe {
+        let written = unsafe {
             WriteProcessMemory(
-                self.0,
+                self.handle,
                 dll_path_buf,
-                dll_path.as_ptr() as *const c_void,
-                (MAX_PATH as usize) * size_of::<u16>(),
+                wide_path.as_ptr() as *const c_void,
+                size,
                 Some(&mut bytes_written),
             )
         };
+        let result = match written {
+            Ok(()) if bytes_written == size => {
+                self.call_remote(s!("LoadLibraryW"), "LoadLibraryW", dll_path_buf)
+            }
+            Ok(()) => Err(InjectError::ShortWrite { written: bytes_written, size }),
+            Err(e) => Err(InjectError::WriteMemory(e)),
+        };
+        unsafe { VirtualFreeEx(self.handle, dll_path_buf, 0, MEM_RELEASE).ok() };
 
-        let thread = unsafe {
-            CreateRemoteThread(
-                self.0,
-                None,
-                0,
-                proc_addr.map(|proc_addr| {
-                    mem::transmute::<
-                        unsafe extern "system" fn() -> isize,
-                        unsafe extern "system" fn(*mut c_void) -> u32,
-                    >(proc_addr)
-                }),
-                Some(dll_path_buf),
-                0,
-                None,
+        // The thread's exit code only holds the low half of the module
+        // handle, so the module list tells whether the DLL got loaded.
+        result?;
+        match self.find_module(&name)? {
+            Some(module) => Ok(InjectReport { pid: self.pid, module }),
+            None => Err(InjectError::LoadLibraryFailed(dll_path)),
+        }
+    }
+
+    /// Ask the tool's DLL in the process to eject itself, and wait for it to
+    /// be unloaded. The tool unhooks and restores the game state first, which
+    /// calling `FreeLibrary` from here would skip, crashing the game.
+    ///
+    /// The function is looked up in the export table of the module loaded
+    /// in the process, not in the DLL on disk, which may have been rebuilt
+    /// since it was injected.
+    pub fn eject(&self, dll_path: &Path) -> Result<InjectReport> {
+        let name = file_name(dll_path);
+        let module =
+            self.find_module(&name)?.ok_or_else(|| InjectError::NotInjected(name.clone()))?;
+
+        let read = |offset: usize, buf: &mut [u8]| {
+            offset + buf.len() <= module.size as usize
+                && self.read_memory(module.base + offset, buf)
+        };
+        let offset = export_offset(read, EJECT_EXPORT)
+            .map_err(|e| InjectError::NoEjectExport(module.clone(), e))?;
+        let start = unsafe {
+            mem::transmute::<usize, unsafe extern "system" fn(*mut c_void) -> u32>(
+                module.base + offset,
             )
-        }?;
+        };
+        self.run_remote(start, "request_eject", std::ptr::null())?;
+
+        let started = Instant::now();
+        while self.find_module(&name)?.is_some() {
+            if started.elapsed() > EJECT_TIMEOUT {
+                return Err(InjectError::EjectTimeout(module));
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        Ok(InjectReport { pid: self.pid, module })
+    }
+
+    /// Run a Kernel32 function taking one pointer in a thread of the process,
+    /// and return its exit code.
+    fn call_remote(
+        &self,
+        function: PCSTR,
+        function_name: &'static str,
+        param: *const c_void,
+    ) -> Result<u32> {
+        let proc_addr = unsafe {
+            GetModuleHandleW(w!("Kernel32"))
+                .ok()
+                .and_then(|kernel32| GetProcAddress(kernel32, function))
+        }
+        .ok_or(InjectError::Kernel32(function_name))?;
+
+        // Kernel32 is mapped at the same address in every process.
+        let start = unsafe {
+            mem::transmute::<
+                unsafe extern "system" fn() -> isize,
+                unsafe extern "system" fn(*mut c_void) -> u32,
+            >(proc_addr)
+        };
+        self.run_remote(start, function_name, param)
+    }
+
+    /// Read `buf.len()` bytes of the process at `address`.
+    fn read_memory(&self, address: usize, buf: &mut [u8]) -> bool {
+        let mut bytes_read = 0;
+        let read = unsafe {
+            ReadProcessMemory(
+                self.handle,
+                address as *const c_void,
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len(),
+                Some(&mut bytes_read),
+            )
+        };
+        read.is_ok() && bytes_read == buf.len()
+    }
+
+    /// Run a function of the process in a new thread of it, and return its
+    /// exit code.
+    fn run_remote(
+        &self,
+        start: unsafe extern "system" fn(*mut c_void) -> u32,
+        function_name: &'static str,
+        param: *const c_void,
+    ) -> Result<u32> {
+        let thread =
+            unsafe { CreateRemoteThread(self.handle, None, 0, Some(start), Some(param), 0, None) }
+                .map_err(|e| InjectError::RemoteThread(function_name, e))?;
 
         unsafe {
             WaitForSingleObject(thread, INFINITE);
             let mut exit_code = 0u32;
-            GetExitCodeThread(thread, &mut exit_code as *mut u32)?;
-            CloseHandle(thread)?;
-            VirtualFreeEx(self.0, dll_path_buf, 0, MEM_RELEASE)?;
-
-            Ok(())
+            let result = GetExitCodeThread(thread, &mut exit_code as *mut u32);
+            CloseHandle(thread).ok();
+            result.map_err(|e| InjectError::RemoteThread(function_name, e))?;
+            Ok(exit_code)
         }
     }
 
     /// Retrieve the process handle.
     pub fn handle(&self) -> HANDLE {
-        self.0
+        self.handle
     }
 }
 
-unsafe fn get_process_by_name64(name_str: &str) -> Result<HANDLE> {
-    let name = HSTRING::from(name_str);
+impl Drop for Process {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.handle).ok() };
+    }
+}
+
+/// The file name of a DLL path, as it appears in the module list.
+pub fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
 
-    let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0)?;
-    let mut process_entry32 = PROCESSENTRY32W {
-        dwSize: mem::size_of::<PROCESSENTRY32W>() as u32,
-        ..Default::default()
+/// The offset of an exported function from the base of a loaded 64-bit
+/// module, found in its export table. `read` reads the module at an offset
+/// from its base.
+fn export_offset(
+    read: impl Fn(usize, &mut [u8]) -> bool,
+    function: &str,
+) -> std::result::Result<usize, String> {
+    let read_bytes = |offset: usize, buf: &mut [u8]| {
+        read(offset, buf).then_some(()).ok_or_else(|| format!("can't read it at {offset:#x}"))
     };
+    let read_u16 = |offset: usize| {
+        let mut buf = [0; 2];
+        read_bytes(offset, &mut buf).map(|()| u16::from_le_bytes(buf) as usize)
+    };
+    let read_u32 = |offset: usize| {
+        let mut buf = [0; 4];
+        read_bytes(offset, &mut buf).map(|()| u32::from_le_bytes(buf) as usize)
+    };
+
+    // IMAGE_DOS_HEADER.e_lfanew, then the PE signature and IMAGE_FILE_HEADER.
+    let nt_headers = read_u32(0x3c)?;
+    if read_u32(nt_headers)? != 0x4550 {
+        return Err("not a PE image".to_string());
+    }
+    let optional_header = nt_headers + 24;
+    if read_u16(optional_header)? != 0x20b {
+        return Err("not a 64-bit image".to_string());
+    }
+
+    // The first data directory of IMAGE_OPTIONAL_HEADER64 is the exports.
+    let exports = read_u32(optional_header + 112)?;
+    let exports_size = read_u32(optional_header + 116)?;
+    if exports == 0 {
+        return Err("no export table".to_string());
+    }
+
+    // IMAGE_EXPORT_DIRECTORY
+    let function_count = read_u32(exports + 0x14)?;
+    let name_count = read_u32(exports + 0x18)?;
+    let functions = read_u32(exports + 0x1c)?;
+    let names = read_u32(exports + 0x20)?;
+    let ordinals = read_u32(exports + 0x24)?;
+
+    let mut name = vec![0; function.len() + 1];
+    for i in 0..name_count {
+        if !read(read_u32(names + 4 * i)?, &mut name)
+            || name[..function.len()] != *function.as_bytes()
+            || name[function.len()] != 0
+        {
+            continue;
+        }
+
+        let ordinal = read_u16(ordinals + 2 * i)?;
+        if ordinal >= function_count {
+            return Err(format!("{function} has an invalid ordinal"));
+        }
+        let offset = read_u32(functions + 4 * ordinal)?;
+        if (exports..exports + exports_size).contains(&offset) {
+            return Err(format!("{function} is forwarded to another DLL"));
+        }
+        return Ok(offset);
+    }
+    Err(format!("no {function} export"))
+}
+
+/// The module with the given file name. Module names are case-insensitive.
+fn find_module(modules: Vec<Module>, name: &str) -> Option<Module> {
+    modules.into_iter().find(|module| module.name.eq_ignore_ascii_case(name))
+}
+
+/// The file version of an executable, e.g. `1.3.1.0`.
+fn file_version(path: &Path) -> Option<String> {
+    let path = HSTRING::from(path);
+    unsafe {
+        let size = GetFileVersionInfoSizeW(&path, None);
+        if size == 0 {
+            return None;
+        }
+        let mut data = vec![0u8; size as usize];
+        GetFileVersionInfoW(&path, 0, size, data.as_mut_ptr() as *mut c_void).ok()?;
+
+        let mut info: *mut c_void = std::ptr::null_mut();
+        let mut len = 0u32;
+        let found = VerQueryValueW(data.as_ptr() as *const c_void, w!("\\"), &mut info, &mut len);
+        if !found.as_bool() || info.is_null() || (len as usize) < size_of::<VS_FIXEDFILEINFO>() {
+            return None;
+        }
+        let info = &*(info as *const VS_FIXEDFILEINFO);
+        Some(format!(
+            "{}.{}.{}.{}",
+            info.dwFileVersionMS >> 16,
+            info.dwFileVersionMS & 0xffff,
+            info.dwFileVersionLS >> 16,
+            info.dwFileVersionLS & 0xffff
+        ))
+    }
+}
 
-    if Process32FirstW(snapshot, &mut process_entry32).is_err() {
-        CloseHandle(snapshot)?;
-        return Err(Error::from_win32());
+fn from_wide(buf: &[u16]) -> String {
+    let len = buf.iter().position(|&x| x == 0).unwrap_or(buf.len());
+    String::from_utf16_lossy(&buf[..len])
+}
+
+/// The processes running on the system.
+pub struct SystemProcesses;
+
+impl ProcessList for SystemProcesses {
+    fn processes(&self) -> std::result::Result<Vec<ProcessEntry>, String> {
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0)
+                .map_err(|e| format!("Couldn't list processes: {e}"))?;
+            let mut process_entry32 = PROCESSENTRY32W {
+                dwSize: mem::size_of::<PROCESSENTRY32W>() as u32,
+                ..Default::default()
+            };
+
+            let mut processes = Vec::new();
+            let mut next = Process32FirstW(snapshot, &mut process_entry32);
+            while next.is_ok() {
+                processes.push(ProcessEntry {
+                    pid: process_entry32.th32ProcessID,
+                    name: from_wide(&process_entry32.szExeFile),
+                });
+                next = Process32NextW(snapshot, &mut process_entry32);
+            }
+
+            CloseHandle(snapshot).ok();
+            Ok(processes)
+        }
     }
 
-    let pid = loop {
-        let zero_idx = process_entry32
-            .szExeFile
-            .iter()
-            .position(|&x| x == 0)
-            .unwrap_or(process_entry32.szExeFile.len());
-        let process_name = HSTRING::from_wide(&process_entry32.szExeFile[..zero_idx])?;
+    fn has_window(&self, pid: u32) -> bool {
+        struct Search {
+            pid: u32,
+            found: bool,
+        }
 
-        if name == process_name {
-            break Ok(process_entry32.th32ProcessID);
+        unsafe extern "system" fn callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
+            let search = &mut *(lparam.0 as *mut Search);
+            let mut window_pid = 0u32;
+            GetWindowThreadProcessId(hwnd, Some(&mut window_pid));
+            if window_pid == search.pid && IsWindowVisible(hwnd).as_bool() {
+                search.found = true;
+                // Stop enumerating.
+                return BOOL(0);
+            }
+            BOOL(1)
         }
 
-        if Process32NextW(snapshot, &mut process_entry32).is_err() {
-            CloseHandle(snapshot)?;
-            break Err(Error::from_hresult(HRESULT(-1)));
+        let mut search = Search { pid, found: false };
+        // Stopping early reports an error, which is not one.
+        unsafe { EnumWindows(Some(callback), LPARAM(&mut search as *mut Search as isize)).ok() };
+        search.found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(name: &str, base: usize) -> Module {
+        Module { name: name.into(), path: format!("C:\\{name}").into(), base, size: 0x1000 }
+    }
+
+    #[test]
+    fn test_find_module() {
+        let modules = vec![
+            module("DarkSoulsRemastered.exe", 0x140000000),
+            module("KERNEL32.DLL", 0x7ff800000000),
+            module("dark_souls_remastered_tool_binaries.dll", 0x7ff900000000),
+        ];
+
+        let found = find_module(modules.clone(), "kernel32.dll").unwrap();
+        assert_eq!(found.base, 0x7ff800000000);
+        let found = find_module(modules.clone(), "dark_souls_remastered_tool_binaries.dll");
+        assert_eq!(found.unwrap().base, 0x7ff900000000);
+        assert!(find_module(modules, "dinput8.dll").is_none());
+
+        let path = Path::new("C:\\tool\\dark_souls_remastered_tool_binaries.dll");
+        assert_eq!(file_name(path), "dark_souls_remastered_tool_binaries.dll");
+    }
+
+    /// A 64-bit image with only headers and an export table, the exports'
+    /// offsets given as is.
+    fn image(exports: &[(&str, u32)]) -> Vec<u8> {
+        let mut image = vec![0u8; 0x400];
+        let mut put = |offset: usize, bytes: &[u8]| {
+            image[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+        put(0x3c, &0x40u32.to_le_bytes());
+        put(0x40, b"PE\0\0");
+        put(0x58, &0x20bu16.to_le_bytes());
+        put(0x58 + 112, &0x100u32.to_le_bytes());
+        put(0x58 + 116, &0x100u32.to_le_bytes());
+
+        let count = (exports.len() as u32).to_le_bytes();
+        put(0x114, &count);
+        put(0x118, &count);
+        put(0x11c, &0x140u32.to_le_bytes());
+        put(0x120, &0x160u32.to_le_bytes());
+        put(0x124, &0x180u32.to_le_bytes());
+
+        let mut name_offset = 0x200;
+        for (i, (name, offset)) in exports.iter().enumerate() {
+            put(0x140 + 4 * i, &offset.to_le_bytes());
+            put(0x160 + 4 * i, &(name_offset as u32).to_le_bytes());
+            put(0x180 + 2 * i, &(i as u16).to_le_bytes());
+            put(name_offset, name.as_bytes());
+            name_offset += name.len() + 1;
         }
-    }?;
+        image
+    }
 
-    CloseHandle(snapshot)?;
+    fn read(image: &[u8]) -> impl Fn(usize, &mut [u8]) -> bool + '_ {
+        |offset, buf| match image.get(offset..offset + buf.len()) {
+            Some(bytes) => {
+                buf.copy_from_slice(bytes);
+                true
+            }
+            None => false,
+        }
+    }
 
-    OpenProcess(PROCESS_ALL_ACCESS, BOOL(0), pid)
+    #[test]
+    fn test_export_offset() {
+        let image = image(&[("request", 0x1000), ("request_eject", 0x2340), ("forwarded", 0x180)]);
+        assert_eq!(export_offset(read(&image), "request_eject"), Ok(0x2340));
+        assert_eq!(export_offset(read(&image), "request"), Ok(0x1000));
+        assert_eq!(export_offset(read(&image), "request_ej"), Err("no request_ej export".into()));
+        assert_eq!(
+            export_offset(read(&image), "forwarded"),
+            Err("forwarded is forwarded to another DLL".into())
+        );
+
+        assert_eq!(
+            export_offset(read(&image[..0x120]), "request_eject"),
+            Err("can't read it at 0x120".into())
+        );
+        let mut image = image;
+        image[0x40] = 0;
+        assert_eq!(export_offset(read(&image), "request_eject"), Err("not a PE image".into()));
+    }
+
+    #[test]
+    fn test_instance_display() {
+        let mut instance = Instance {
+            pid: 1234,
+            started: Some("2024-03-01 20:15:00".into()),
+            version: Some("1.3.1.0".into()),
+            injected: false,
+        };
+        assert_eq!(instance.to_string(), "    1234  started 2024-03-01 20:15:00  version 1.3.1.0");
+        instance.version = None;
+        instance.injected = true;
+        assert!(instance.to_string().ends_with("version ?  (injected)"));
+    }
+
+    #[test]
+    fn test_error_messages() {
+        let module = module("dark_souls_remastered_tool_binaries.dll", 0x7ff900000000);
+        assert_eq!(
+            InjectError::AlreadyInjected(module).to_string(),
+            "Already injected: C:\\dark_souls_remastered_tool_binaries.dll at 0x7ff900000000"
+        );
+        assert_eq!(
+            InjectError::NotInjected("tool.dll".into()).to_string(),
+            "tool.dll is not loaded in the process"
+        );
+        assert_eq!(
+            InjectError::ShortWrite { written: 12, size: 96 }.to_string(),
+            "Could not write the DLL path: wrote 12 of 96 bytes"
+        );
+    }
 }
@@ -0,0 +1,153 @@
+//! Carries out the calls of the IPC server on the game.
+//!
+//! The server is polled once per frame from the render loop, so calls touch
+//! the game from the same thread as the widgets.
+
+use dsr_ipc::{
+    methods, params, result, AccountParams, FlagParams, Handler, LoadSavefileParams, Position,
+    RpcError, SetFlagParams, Stats,
+};
+use libdsr::prelude::*;
+use practice_tool_core::crossbeam_channel::Sender;
+use serde_json::Value;
+
+use crate::config::FlagSpec;
+use crate::positions::same_block;
+use crate::restore::RestoreRegistry;
+use crate::savefile::backup::{write_savefile, BackupSettings, Backups};
+use crate::savefile::library::Library;
+use crate::savefile::{find_accounts, saves_root, Account};
+
+pub(crate) struct GameHandler<'a> {
+    pub(crate) chains: &'a PointerChains,
+    pub(crate) restore: &'a RestoreRegistry,
+    pub(crate) backups: BackupSettings,
+    pub(crate) log_tx: &'a Sender<String>,
+}
+
+impl GameHandler<'_> {
+    fn flag(&self, name: String) -> Result<(String, &Bitflag<u8>), RpcError> {
+        let spec = FlagSpec::try_from(name).map_err(RpcError::tool)?;
+        Ok((spec.label, (spec.getter)(self.chains)))
+    }
+
+    fn map_id(&self) -> Option<u32> {
+        self.chains.map_id.read().filter(|&map_id| map_id != 0)
+    }
+
+    fn save_position(&self) -> Option<Position> {
+        let (angle, pos) = &self.chains.position;
+        Some(Position { map_id: self.map_id(), pos: pos.read()?, angle: angle.read()? })
+    }
+
+    fn load_position(&self, position: Position) -> Result<bool, RpcError> {
+        if let Some(saved) = position.map_id {
+            if !self.map_id().is_some_and(|current| same_block(saved, current)) {
+                return Err(RpcError::tool("Position saved in another map"));
+            }
+        }
+        let (angle, pos) = &self.chains.position;
+        Ok(pos.write(position.pos).is_some() && angle.write(position.angle).is_some())
+    }
+
+    fn stats(&self) -> Option<Stats> {
+        let s = self.chains.character_stats.read()?;
+        Some(Stats {
+            level: Some(s.level),
+            souls: Some(s.souls),
+            vitality: Some(s.vitality),
+            attunement: Some(s.attunement),
+            endurance: Some(s.endurance),
+            strength: Some(s.strength),
+            dexterity: Some(s.dexterity),
+            resistance: Some(s.resistance),
+            intelligence: Some(s.intelligence),
+            faith: Some(s.faith),
+            humanity: Some(s.humanity),
+        })
+    }
+
+    fn set_stats(&self, stats: Stats) -> bool {
+        let Some(mut s) = self.chains.character_stats.read() else {
+            return false;
+        };
+        for (value, field) in [
+            (stats.level, &mut s.level),
+            (stats.souls, &mut s.souls),
+            (stats.vitality, &mut s.vitality),
+            (stats.attunement, &mut s.attunement),
+            (stats.endurance, &mut s.endurance),
+            (stats.strength, &mut s.strength),
+            (stats.dexterity, &mut s.dexterity),
+            (stats.resistance, &mut s.resistance),
+            (stats.intelligence, &mut s.intelligence),
+            (stats.faith, &mut s.faith),
+            (stats.humanity, &mut s.humanity),
+        ] {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        self.chains.character_stats.write(s).is_some()
+    }
+
+    fn account(id: Option<&str>) -> Result<Account, String> {
+        let accounts = find_accounts(&saves_root()?)?;
+        let mut accounts = accounts.into_iter();
+        match id {
+            None => accounts.next(),
+            Some(id) => accounts.find(|account| account.id == id),
+        }
+        .ok_or_else(|| format!("No save folder for account {}", id.unwrap_or_default()))
+    }
+
+    fn load_savefile(&self, params: LoadSavefileParams) -> Result<(), String> {
+        let account = Self::account(params.account.as_deref())?;
+        let library = Library::load(&account.dir)?;
+        // Only saves of the library, not any path the caller comes up with.
+        if !library.scan().contains(&params.save) {
+            return Err(format!("No save {} in the library", params.save));
+        }
+        let data = std::fs::read(library.path(&params.save))
+            .map_err(|e| format!("Couldn't read {}: {e}", params.save))?;
+        write_savefile(&account.savefile_path(), &data, &Backups::new(&account.dir, self.backups))?;
+        self.log_tx.send(format!("Loaded {} from IPC", params.save)).ok();
+
+        if params.quitout && self.chains.quitout.write(1).is_none() {
+            return Err("Couldn't quit out".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Handler for GameHandler<'_> {
+    fn handle(&mut self, method: &str, p: Value) -> Result<Value, RpcError> {
+        match method {
+            methods::FLAG_GET => {
+                let FlagParams { name } = params(p)?;
+                result(self.flag(name)?.1.get())
+            }
+            methods::FLAG_SET => {
+                let SetFlagParams { name, state } = params(p)?;
+                let (label, flag) = self.flag(name)?;
+                self.restore.record(&label, flag);
+                flag.set(state);
+                result(flag.get() == Some(state))
+            }
+            methods::IGT_GET => result(self.chains.igt.read()),
+            methods::POSITION_SAVE => result(self.save_position()),
+            methods::POSITION_LOAD => result(self.load_position(params(p)?)?),
+            methods::STATS_GET => result(self.stats()),
+            methods::STATS_SET => result(self.set_stats(params(p)?)),
+            methods::SAVEFILE_LIST => {
+                let AccountParams { account } = params(p)?;
+                let account = Self::account(account.as_deref()).map_err(RpcError::tool)?;
+                result(Library::load(&account.dir).map_err(RpcError::tool)?.scan())
+            }
+            methods::SAVEFILE_LOAD => {
+                result(self.load_savefile(params(p)?).map_err(RpcError::tool)?)
+            }
+            method => Err(RpcError::method_not_found(method)),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Starting the game and waiting until it can be injected.
+//!
+//! Process discovery goes through [`ProcessList`], so that the waiting logic
+//! doesn't depend on the system's process list.
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const PROCESS_NAME: &str = "DarkSoulsRemastered.exe";
+pub const STEAM_APP_ID: u32 = 570940;
+
+/// A running process.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessEntry {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// Source of the running processes and their windows.
+pub trait ProcessList {
+    fn processes(&self) -> Result<Vec<ProcessEntry>, String>;
+
+    /// Whether the process has a visible top-level window.
+    fn has_window(&self, pid: u32) -> bool;
+}
+
+/// The PIDs of the processes with the given executable name.
+pub fn find_processes(list: &impl ProcessList, name: &str) -> Result<Vec<u32>, String> {
+    Ok(list
+        .processes()?
+        .into_iter()
+        .filter(|p| p.name.eq_ignore_ascii_case(name))
+        .map(|p| p.pid)
+        .collect())
+}
+
+/// How long to wait for the game, and how often to retry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// How long to wait for the process and its window. Zero looks once.
+    pub timeout: Duration,
+    /// Delay between looks at the process list, and between injections.
+    pub interval: Duration,
+    /// How many times to try injecting before giving up.
+    pub attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { timeout: Duration::ZERO, interval: Duration::from_millis(500), attempts: 1 }
+    }
+}
+
+/// Waits until a process with the given name, and the given PID if any,
+/// has a window, and returns its PID. Several such processes are an error
+/// unless a PID picks one.
+pub fn wait_for_window(
+    list: &impl ProcessList,
+    name: &str,
+    pid: Option<u32>,
+    policy: &RetryPolicy,
+) -> Result<u32, String> {
+    let target = match pid {
+        Some(pid) => format!("{name} (pid {pid})"),
+        None => name.to_string(),
+    };
+
+    let started = Instant::now();
+    loop {
+        let pids: Vec<u32> = find_processes(list, name)?
+            .into_iter()
+            .filter(|&p| pid.is_none_or(|pid| pid == p))
+            .collect();
+        let ready: Vec<u32> = pids.iter().copied().filter(|&p| list.has_window(p)).collect();
+        match ready[..] {
+            [pid] => return Ok(pid),
+            [] => {}
+            _ => {
+                let pids = ready.iter().map(u32::to_string).collect::<Vec<_>>().join(", ");
+                return Err(format!("Several {name} are running, pick one with --pid: {pids}"));
+            }
+        }
+
+        if started.elapsed() >= policy.timeout {
+            return Err(match pids.len() {
+                0 if policy.timeout.is_zero() => format!("{target} isn't running"),
+                0 => format!("{target} didn't start in time"),
+                _ => format!("{target} didn't open its window in time"),
+            });
+        }
+        thread::sleep(policy.interval);
+    }
+}
+
+/// Calls `f` until it succeeds, up to `policy.attempts` times.
+pub fn retry<T, E>(policy: &RetryPolicy, mut f: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt >= policy.attempts => return Err(e),
+            Err(_) => {
+                attempt += 1;
+                thread::sleep(policy.interval);
+            }
+        }
+    }
+}
+
+/// How to start the game.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Launch {
+    Steam,
+    Direct(PathBuf),
+}
+
+impl Launch {
+    pub fn start(&self) -> Result<(), String> {
+        let result = match self {
+            Launch::Steam => Command::new("cmd")
+                .args(["/C", "start", "", &format!("steam://rungameid/{STEAM_APP_ID}")])
+                .spawn(),
+            Launch::Direct(exe) => {
+                let mut command = Command::new(exe);
+                if let Some(dir) = exe.parent() {
+                    command.current_dir(dir);
+                }
+                command.spawn()
+            }
+        };
+        result.map(|_| ()).map_err(|e| format!("Couldn't launch the game: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    /// Processes that show up, and then open their window, after a number of
+    /// looks at the list.
+    struct FakeProcesses {
+        looks: Cell<u32>,
+        starts_after: u32,
+        window_after: u32,
+        instances: u32,
+    }
+
+    fn fake(starts_after: u32, window_after: u32) -> FakeProcesses {
+        FakeProcesses { looks: Cell::new(0), starts_after, window_after, instances: 1 }
+    }
+
+    impl ProcessList for FakeProcesses {
+        fn processes(&self) -> Result<Vec<ProcessEntry>, String> {
+            self.looks.set(self.looks.get() + 1);
+            let mut processes = vec![ProcessEntry { pid: 4, name: "System".into() }];
+            if self.looks.get() > self.starts_after {
+                for pid in 1234..1234 + self.instances {
+                    processes.push(ProcessEntry { pid, name: "darksoulsremastered.exe".into() });
+                }
+            }
+            Ok(processes)
+        }
+
+        fn has_window(&self, pid: u32) -> bool {
+            pid >= 1234 && self.looks.get() > self.window_after
+        }
+    }
+
+    fn policy(timeout_ms: u64) -> RetryPolicy {
+        RetryPolicy {
+            timeout: Duration::from_millis(timeout_ms),
+            interval: Duration::from_millis(1),
+            attempts: 3,
+        }
+    }
+
+    #[test]
+    fn test_wait_for_window() {
+        let list = fake(2, 4);
+        assert!(find_processes(&list, PROCESS_NAME).unwrap().is_empty());
+        assert_eq!(wait_for_window(&list, PROCESS_NAME, None, &policy(5000)).unwrap(), 1234);
+        assert_eq!(list.looks.get(), 5);
+
+        let running = fake(0, 0);
+        assert_eq!(wait_for_window(&running, PROCESS_NAME, None, &policy(0)).unwrap(), 1234);
+    }
+
+    #[test]
+    fn test_wait_for_window_timeout() {
+        let never = fake(u32::MAX, 0);
+        let e = wait_for_window(&never, PROCESS_NAME, None, &policy(0)).unwrap_err();
+        assert!(e.contains("isn't running"));
+        assert_eq!(never.looks.get(), 1);
+        let e = wait_for_window(&never, PROCESS_NAME, None, &policy(20)).unwrap_err();
+        assert!(e.contains("didn't start"));
+
+        let no_window = fake(0, u32::MAX);
+        let e = wait_for_window(&no_window, PROCESS_NAME, None, &policy(20)).unwrap_err();
+        assert!(e.contains("window"));
+    }
+
+    #[test]
+    fn test_wait_for_window_instances() {
+        let several = FakeProcesses { instances: 3, ..fake(0, 0) };
+        assert_eq!(find_processes(&several, PROCESS_NAME).unwrap(), [1234, 1235, 1236]);
+        let e = wait_for_window(&several, PROCESS_NAME, None, &policy(0)).unwrap_err();
+        assert!(e.ends_with("--pid: 1234, 1235, 1236"));
+
+        assert_eq!(wait_for_window(&several, PROCESS_NAME, Some(1235), &policy(0)).unwrap(), 1235);
+        let e = wait_for_window(&several, PROCESS_NAME, Some(99), &policy(0)).unwrap_err();
+        assert_eq!(e, "DarkSoulsRemastered.exe (pid 99) isn't running");
+    }
+
+    #[test]
+    fn test_retry() {
+        let mut calls = 0;
+        let result = retry(&policy(0), || {
+            calls += 1;
+            if calls < 3 {
+                Err(format!("attempt {calls}"))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result, Ok(3));
+
+        let mut calls = 0;
+        let result: Result<(), _> = retry(&policy(0), || {
+            calls += 1;
+            Err(format!("attempt {calls}"))
+        });
+        assert_eq!(result, Err("attempt 3".to_string()));
+    }
+}
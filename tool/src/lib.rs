@@ -1,11 +1,21 @@
 mod config;
+mod indicators;
+mod ipc;
+mod livesplit;
+mod macros;
+mod palette;
+mod positions;
+mod proxy;
+mod restore;
+mod savefile;
+mod scripting;
+mod splits;
 mod widgets;
 mod tool;
 mod util;
 
 use tool::Tool;
 
-use once_cell::sync::Lazy;
 use std::ffi::c_void;
 use std::thread;
 
@@ -13,37 +23,9 @@ use hudhook::hooks::dx11::ImguiDx11Hooks;
 use hudhook::tracing::{error, trace};
 use hudhook::{eject, Hudhook};
 
-use windows::core::{s, w, GUID, HRESULT, PCWSTR};
-use windows::Win32::Foundation::{HINSTANCE, MAX_PATH};
-use windows::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW};
-use windows::Win32::System::SystemInformation::GetSystemDirectoryW;
+use windows::Win32::Foundation::HINSTANCE;
 use windows::Win32::System::SystemServices::DLL_PROCESS_ATTACH;
 
-type FDirectInput8Create = unsafe extern "stdcall" fn(
-    hinst: HINSTANCE,
-    dwversion: u32,
-    riidltf: *const GUID,
-    ppvout: *mut *mut c_void,
-    punkouter: HINSTANCE,
-) -> HRESULT;
-
-static DIRECTINPUT8CREATE: Lazy<FDirectInput8Create> = Lazy::new(|| unsafe {
-    let mut dinput8_path = [0u16; MAX_PATH as usize];
-    let count = GetSystemDirectoryW(Some(&mut dinput8_path)) as usize;
-
-    // If count == 0, this will be fun
-    std::ptr::copy_nonoverlapping(
-        w!("\\dinput8.dll").0,
-        dinput8_path[count..].as_mut_ptr(),
-        12,
-    );
-
-    let dinput8 = LoadLibraryW(PCWSTR(dinput8_path.as_ptr())).unwrap();
-    let directinput8create = std::mem::transmute(GetProcAddress(dinput8, s!("DirectInput8Create")));
-
-    directinput8create
-});
-
 fn start_tool(hmodule: HINSTANCE) {
     let tool = Tool::new();
 
@@ -58,12 +40,20 @@ fn start_tool(hmodule: HINSTANCE) {
     }
 }
 
+/// Ejects the tool on the next frame. The injector's `--eject` runs it in a
+/// thread of the game: freeing the DLL from outside would leave the hooks
+/// pointing at unloaded code.
+#[no_mangle]
+pub extern "system" fn request_eject(_: *mut c_void) -> u32 {
+    tool::request_eject();
+    0
+}
+
 #[no_mangle]
 #[allow(clippy::missing_safety_doc)]
 pub unsafe extern "stdcall" fn DllMain(hmodule: HINSTANCE, reason: u32, _: *mut c_void) {
     if reason == DLL_PROCESS_ATTACH {
         trace!("DllMain()");
-        Lazy::force(&DIRECTINPUT8CREATE);
         thread::spawn(move || start_tool(hmodule));
     }
 }
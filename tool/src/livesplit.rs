@@ -0,0 +1,237 @@
+//! Client for the LiveSplit Server component.
+//!
+//! LiveSplit Server accepts one text command per line over TCP. The client
+//! keeps the game time of LiveSplit in sync with IGT and forwards the split
+//! timer's actions. While the split timer runs, game time is IGT since its
+//! start, so that both timers agree. The connection lives on its own thread and is retried
+//! in the background, so the game never waits on LiveSplit.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use hudhook::tracing::debug;
+use practice_tool_core::crossbeam_channel::{self, RecvTimeoutError, Sender};
+
+/// Default address of LiveSplit Server.
+pub(crate) const DEFAULT_ADDRESS: &str = "127.0.0.1:16834";
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+const GAME_TIME_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Command {
+    StartTimer,
+    Split,
+    SkipSplit,
+    Unsplit,
+    Reset,
+    /// Switch the timer to game time.
+    InitGameTime,
+    /// Stop LiveSplit from advancing game time on its own; the client sets
+    /// it explicitly instead.
+    PauseGameTime,
+    /// Game time in milliseconds.
+    SetGameTime(u32),
+}
+
+impl Command {
+    fn line(&self) -> String {
+        match self {
+            Command::StartTimer => "starttimer".to_string(),
+            Command::Split => "split".to_string(),
+            Command::SkipSplit => "skipsplit".to_string(),
+            Command::Unsplit => "unsplit".to_string(),
+            Command::Reset => "reset".to_string(),
+            Command::InitGameTime => "initgametime".to_string(),
+            Command::PauseGameTime => "pausegametime".to_string(),
+            Command::SetGameTime(ms) => {
+                let mut line = "setgametime ".to_string();
+                write!(
+                    line,
+                    "{}:{:02}:{:02}.{:03}",
+                    ms / 3600000,
+                    ms / 60000 % 60,
+                    ms / 1000 % 60,
+                    ms % 1000
+                )
+                .ok();
+                line
+            }
+        }
+    }
+}
+
+/// Handle to the connection thread. Cloning it is cheap; the thread exits
+/// once every handle has been dropped.
+#[derive(Clone)]
+pub(crate) struct LiveSplit {
+    tx: Sender<Command>,
+    last_game_time: Option<(Instant, u32)>,
+    /// IGT that game time counts from, shared by every handle.
+    origin: Arc<AtomicU32>,
+}
+
+impl LiveSplit {
+    /// Start connecting to `address`. Connection changes are reported on
+    /// `log`.
+    pub(crate) fn connect(address: &str, log: Sender<String>) -> Self {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let address = address.to_string();
+
+        thread::spawn(move || {
+            let mut stream: Option<TcpStream> = None;
+            let mut last_attempt: Option<Instant> = None;
+
+            loop {
+                if stream.is_none() && last_attempt.is_none_or(|t| t.elapsed() >= RETRY_INTERVAL) {
+                    last_attempt = Some(Instant::now());
+                    stream = open(&address);
+                    if stream.is_some() {
+                        log.send(format!("Connected to LiveSplit at {address}"))
+                            .ok();
+                    }
+                }
+
+                let command = match rx.recv_timeout(RETRY_INTERVAL) {
+                    Ok(command) => command,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                };
+
+                // Commands issued while disconnected are dropped: replaying
+                // stale splits later would be worse than missing them.
+                let Some(s) = &mut stream else {
+                    continue;
+                };
+
+                // LiveSplit drops the game time setup whenever the timer
+                // starts or resets, so it's sent again each time.
+                let result = match command {
+                    Command::StartTimer | Command::Reset => {
+                        send(s, command).and_then(|()| init_game_time(s))
+                    }
+                    command => send(s, command),
+                };
+                if let Err(e) = result {
+                    debug!("LiveSplit write error: {e}");
+                    log.send("Lost connection to LiveSplit".to_string()).ok();
+                    stream = None;
+                }
+            }
+        });
+
+        LiveSplit {
+            tx,
+            last_game_time: None,
+            origin: Default::default(),
+        }
+    }
+
+    pub(crate) fn send(&self, command: Command) {
+        self.tx.send(command).ok();
+    }
+
+    /// Push the current IGT, counted from the origin. Calls are throttled,
+    /// so this can be called every frame.
+    pub(crate) fn set_game_time(&mut self, igt: u32) {
+        let time = igt.saturating_sub(self.origin.load(Ordering::Relaxed));
+        let due = match self.last_game_time {
+            Some((at, last)) => last != time && at.elapsed() >= GAME_TIME_INTERVAL,
+            None => true,
+        };
+
+        if due {
+            self.last_game_time = Some((Instant::now(), time));
+            self.send(Command::SetGameTime(time));
+        }
+    }
+
+    /// Count game time from `igt` on, e.g. the start of a run. Zero pushes
+    /// IGT as it is.
+    pub(crate) fn set_origin(&self, igt: u32) {
+        self.origin.store(igt, Ordering::Relaxed);
+    }
+}
+
+fn open(address: &str) -> Option<TcpStream> {
+    let addr = address.to_socket_addrs().ok()?.next()?;
+    let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).ok()?;
+    stream.set_nodelay(true).ok();
+    init_game_time(&mut stream).ok()?;
+    Some(stream)
+}
+
+fn init_game_time(stream: &mut TcpStream) -> std::io::Result<()> {
+    send(stream, Command::InitGameTime)?;
+    send(stream, Command::PauseGameTime)
+}
+
+fn send(stream: &mut TcpStream, command: Command) -> std::io::Result<()> {
+    let mut line = command.line();
+    line.push_str("\r\n");
+    stream.write_all(line.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn test_command_lines() {
+        assert_eq!(
+            Command::SetGameTime(3723456).line(),
+            "setgametime 1:02:03.456"
+        );
+        assert_eq!(Command::SetGameTime(0).line(), "setgametime 0:00:00.000");
+        assert_eq!(Command::Split.line(), "split");
+    }
+
+    #[test]
+    fn test_mock_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (log_tx, log_rx) = crossbeam_channel::unbounded();
+
+        let mut livesplit = LiveSplit::connect(&address, log_tx);
+        let (conn, _) = listener.accept().unwrap();
+        conn.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut lines = BufReader::new(conn).lines().map(Result::unwrap);
+
+        assert_eq!(lines.next().unwrap(), "initgametime");
+        assert_eq!(lines.next().unwrap(), "pausegametime");
+
+        livesplit.send(Command::StartTimer);
+        livesplit.set_game_time(61500);
+        // Throttled: neither sent.
+        livesplit.set_game_time(61516);
+        livesplit.set_game_time(61500);
+        livesplit.send(Command::Split);
+        livesplit.send(Command::Reset);
+        // The origin is shared with clones, like the split timer's.
+        livesplit.clone().set_origin(60000);
+        std::thread::sleep(GAME_TIME_INTERVAL);
+        livesplit.set_game_time(61500);
+
+        assert_eq!(lines.next().unwrap(), "starttimer");
+        assert_eq!(lines.next().unwrap(), "initgametime");
+        assert_eq!(lines.next().unwrap(), "pausegametime");
+        assert_eq!(lines.next().unwrap(), "setgametime 0:01:01.500");
+        assert_eq!(lines.next().unwrap(), "split");
+        assert_eq!(lines.next().unwrap(), "reset");
+        assert_eq!(lines.next().unwrap(), "initgametime");
+        assert_eq!(lines.next().unwrap(), "pausegametime");
+        assert_eq!(lines.next().unwrap(), "setgametime 0:00:01.500");
+
+        let connected = log_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(connected.contains(&address));
+    }
+}
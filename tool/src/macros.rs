@@ -0,0 +1,256 @@
+//! Sequences of actions and waits, run one frame at a time.
+
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+/// How long a macro waits for IGT to change, or for a loading screen to come
+/// and go, before giving up.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A pause between the steps of a macro.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Wait {
+    Frames(u32),
+    Ms(u64),
+    /// Until IGT differs from its value when the wait started.
+    IgtChange,
+    /// Until a loading screen, e.g. from a warp or a quitout, has started
+    /// and ended.
+    Load,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Step<A> {
+    Run(A),
+    Wait(Wait),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Waiting {
+    Frames(u32),
+    Until(Instant),
+    IgtChange { from: Option<u32>, since: Instant },
+    Load { started: bool, since: Instant },
+}
+
+#[derive(Debug)]
+struct Running {
+    index: usize,
+    waiting: Option<Waiting>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Sequence<A> {
+    steps: Vec<Step<A>>,
+    running: Option<Running>,
+}
+
+impl<A> Sequence<A> {
+    pub(crate) fn new(steps: Vec<Step<A>>) -> Self {
+        Sequence { steps, running: None }
+    }
+
+    /// Starts from the first step, also when already running.
+    pub(crate) fn start(&mut self) {
+        self.running = Some(Running { index: 0, waiting: None });
+    }
+
+    /// The step being run and the number of steps, while running.
+    pub(crate) fn progress(&self) -> Option<(usize, usize)> {
+        self.running.as_ref().map(|r| (r.index + 1, self.steps.len()))
+    }
+
+    /// Advances by one frame: runs actions until a wait that isn't over yet.
+    /// `igt` is the current IGT, and `loading` tells whether a loading screen
+    /// is up. Returns whether the sequence finished in this frame. A failed
+    /// action or a wait that timed out stops it.
+    pub(crate) fn tick(
+        &mut self,
+        now: Instant,
+        igt: Option<u32>,
+        loading: bool,
+        mut run: impl FnMut(&A) -> Result<(), String>,
+    ) -> Result<bool, String> {
+        let Some(running) = &mut self.running else {
+            return Ok(false);
+        };
+
+        loop {
+            let Some(step) = self.steps.get(running.index) else {
+                self.running = None;
+                return Ok(true);
+            };
+
+            match (step, running.waiting) {
+                (Step::Run(action), _) => {
+                    if let Err(e) = run(action) {
+                        self.running = None;
+                        return Err(e);
+                    }
+                }
+                (Step::Wait(wait), None) => {
+                    running.waiting = Some(match *wait {
+                        Wait::Frames(frames) => Waiting::Frames(frames),
+                        Wait::Ms(ms) => Waiting::Until(now + Duration::from_millis(ms)),
+                        Wait::IgtChange => Waiting::IgtChange { from: igt, since: now },
+                        Wait::Load => Waiting::Load { started: loading, since: now },
+                    });
+                    if !matches!(wait, Wait::Frames(0) | Wait::Ms(0)) {
+                        return Ok(false);
+                    }
+                }
+                (Step::Wait(_), Some(waiting)) => {
+                    let over = match waiting {
+                        Waiting::Frames(remaining) => {
+                            let remaining = remaining.saturating_sub(1);
+                            running.waiting = Some(Waiting::Frames(remaining));
+                            remaining == 0
+                        }
+                        Waiting::Until(until) => now >= until,
+                        Waiting::IgtChange { from, since } => {
+                            if now.duration_since(since) > WAIT_TIMEOUT {
+                                self.running = None;
+                                return Err("Timed out waiting for IGT to change".to_string());
+                            }
+                            igt.is_some() && igt != from
+                        }
+                        Waiting::Load { started, since } => {
+                            if now.duration_since(since) > WAIT_TIMEOUT {
+                                self.running = None;
+                                return Err("Timed out waiting for a loading screen".to_string());
+                            }
+                            let started = started || loading;
+                            running.waiting = Some(Waiting::Load { started, since });
+                            started && !loading
+                        }
+                    };
+                    if !over {
+                        return Ok(false);
+                    }
+                }
+            }
+
+            running.index += 1;
+            running.waiting = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a frame per `(ms, igt, loading)`, and returns the actions run in
+    /// each.
+    fn run_frames(
+        sequence: &mut Sequence<&'static str>,
+        frames: &[(u64, Option<u32>, bool)],
+    ) -> Vec<Vec<&'static str>> {
+        let start = Instant::now();
+        frames
+            .iter()
+            .map(|&(ms, igt, loading)| {
+                let mut ran = Vec::new();
+                sequence
+                    .tick(start + Duration::from_millis(ms), igt, loading, |action| {
+                        ran.push(*action);
+                        Ok(())
+                    })
+                    .unwrap();
+                ran
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_waits() {
+        let mut sequence = Sequence::new(vec![
+            Step::Run("position"),
+            Step::Wait(Wait::Load),
+            Step::Run("no_death"),
+            Step::Wait(Wait::Frames(2)),
+            Step::Run("speed"),
+            Step::Wait(Wait::Ms(100)),
+            Step::Run("souls"),
+            Step::Wait(Wait::IgtChange),
+            Step::Run("igt"),
+            Step::Wait(Wait::Frames(0)),
+            Step::Run("done"),
+        ]);
+        assert_eq!(sequence.progress(), None);
+        assert!(run_frames(&mut sequence, &[(0, Some(10), false)])[0].is_empty());
+
+        sequence.start();
+        let ran = run_frames(
+            &mut sequence,
+            &[
+                (0, Some(10), false),
+                // The loading screen shows up a few frames after the warp.
+                (16, Some(10), false),
+                (33, None, true),
+                (50, None, true),
+                (66, Some(0), false),
+                (83, Some(16), false),
+                (100, Some(33), false),
+                (166, Some(100), false),
+                (200, Some(133), false),
+                // IGT stops, e.g. in a menu.
+                (216, Some(133), false),
+                (233, Some(150), false),
+            ],
+        );
+        assert_eq!(
+            ran,
+            [
+                vec!["position"],
+                vec![],
+                vec![],
+                vec![],
+                vec!["no_death"],
+                vec![],
+                vec!["speed"],
+                vec![],
+                vec!["souls"],
+                vec![],
+                vec!["igt", "done"],
+            ]
+        );
+        assert_eq!(sequence.progress(), None);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut sequence =
+            Sequence::new(vec![Step::Run("fails"), Step::Wait(Wait::Frames(1)), Step::Run("no")]);
+        sequence.start();
+        let now = Instant::now();
+        assert_eq!(
+            sequence.tick(now, None, false, |_| Err("boom".to_string())),
+            Err("boom".to_string())
+        );
+        assert_eq!(sequence.progress(), None);
+
+        for wait in [Wait::Load, Wait::IgtChange] {
+            let mut sequence = Sequence::new(vec![Step::Wait(wait), Step::Run("no")]);
+            sequence.start();
+            assert_eq!(sequence.tick(now, Some(10), false, |_| Ok(())), Ok(false));
+            assert_eq!(sequence.progress(), Some((1, 2)));
+            assert!(sequence.tick(now + WAIT_TIMEOUT * 2, Some(10), false, |_| Ok(())).is_err());
+            assert_eq!(sequence.progress(), None);
+        }
+    }
+
+    #[test]
+    fn test_parse_wait() {
+        #[derive(Deserialize)]
+        struct Steps {
+            steps: Vec<Wait>,
+        }
+        let steps: Steps =
+            toml::from_str(r#"steps = [{ frames = 30 }, { ms = 500 }, "igt_change", "load"]"#)
+                .unwrap();
+        assert_eq!(steps.steps, [Wait::Frames(30), Wait::Ms(500), Wait::IgtChange, Wait::Load]);
+    }
+}
@@ -1,4 +1,10 @@
 mod inject;
+mod launch;
+
+use std::num::{IntErrorKind, ParseIntError};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 
 use hudhook::tracing::trace;
 use tracing_subscriber::filter::LevelFilter;
@@ -7,8 +13,80 @@ use windows::core::PCSTR;
 use windows::Win32::Foundation::HWND;
 use windows::Win32::UI::WindowsAndMessaging::{MessageBoxA, MB_ICONERROR, MB_OK};
 
-fn err_to_string<T: std::fmt::Display>(e: T) -> String {
-    format!("Error: {}", e)
+use crate::launch::{Launch, RetryPolicy, PROCESS_NAME};
+
+const USAGE: &str = "\
+Usage: dark_souls_remastered_tool [options]
+
+  --dll PATH          DLL to inject, by default the one next to this executable
+  --launch steam      start the game through Steam, then inject
+  --launch PATH       start the game executable at PATH, then inject
+  --timeout SECS      how long to wait for the game window (default: 120 when
+                      launching, otherwise the game must already be running)
+  --interval MS       delay between retries (default: 500)
+  --retries N         how many times to try injecting (default: 1)
+  --eject             ask the tool in the game to eject, like its Eject button
+  --pid PID           the game process to use, when several are running
+  --list              list the running games and whether they are injected";
+
+/// The tool's DLL, whatever file `--dll` points to. A game hosting it is not
+/// injected again.
+const TOOL_DLL: &str = "dark_souls_remastered_tool_binaries.dll";
+
+/// Launching the game can't wait forever for it.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    dll: Option<PathBuf>,
+    launch: Option<Launch>,
+    retry: RetryPolicy,
+    eject: bool,
+    pid: Option<u32>,
+    list: bool,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut timeout = None;
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--dll" => parsed.dll = Some(value()?.into()),
+                "--launch" => {
+                    parsed.launch = Some(match value()?.as_str() {
+                        "steam" => Launch::Steam,
+                        path => Launch::Direct(path.into()),
+                    })
+                }
+                "--timeout" => timeout = Some(Duration::from_secs(parse_number(&arg, value()?)?)),
+                "--interval" => {
+                    parsed.retry.interval = Duration::from_millis(parse_number(&arg, value()?)?)
+                }
+                "--retries" => parsed.retry.attempts = parse_number::<u32>(&arg, value()?)?.max(1),
+                "--eject" => parsed.eject = true,
+                "--pid" => parsed.pid = Some(parse_number(&arg, value()?)?),
+                "--list" => parsed.list = true,
+                arg => return Err(format!("Unknown argument {arg}")),
+            }
+        }
+
+        parsed.retry.timeout = match (timeout, &parsed.launch) {
+            (Some(timeout), _) => timeout,
+            (None, Some(_)) => LAUNCH_TIMEOUT,
+            (None, None) => Duration::ZERO,
+        };
+        Ok(parsed)
+    }
+}
+
+fn parse_number<T: FromStr<Err = ParseIntError>>(arg: &str, value: String) -> Result<T, String> {
+    value.parse().map_err(|e: ParseIntError| match e.kind() {
+        IntErrorKind::PosOverflow => format!("Value for {arg} is too large: {value}"),
+        _ => format!("Invalid value for {arg}: {value}"),
+    })
 }
 
 fn main() {
@@ -20,7 +98,11 @@ fn main() {
         .with_thread_names(true)
         .init();
 
-    if let Err(e) = perform_injection() {
+    let result = Args::parse(std::env::args().skip(1))
+        .map_err(|e| format!("{e}\n\n{USAGE}"))
+        .and_then(|args| perform_injection(&args));
+
+    if let Err(e) = result {
         let error_msg = format!("{}\0", e);
         unsafe {
             MessageBoxA(
@@ -33,7 +115,7 @@ fn main() {
     }
 }
 
-fn perform_injection() -> Result<(), String> {
+fn default_dll_path() -> PathBuf {
     let mut dll_path = std::env::current_exe().unwrap();
     dll_path.pop();
     dll_path.push("dark_souls_remastered_tool_binaries.dll");
@@ -44,12 +126,102 @@ fn perform_injection() -> Result<(), String> {
         dll_path.set_extension("dll");
     }
 
-    let dll_path = dll_path.canonicalize().map_err(err_to_string)?;
-    trace!("Injecting {:?}", dll_path);
+    dll_path
+}
 
-    inject::Process::get_process_by_name("DarkSoulsRemastered.exe")
-        .map_err(|e| format!("Could not find process: {e:?}"))?
-        .inject(dll_path)
-        .map_err(|e| format!("Could not inject DLL: {e:?}"))?;
+fn list_instances() -> Result<(), String> {
+    let pids = launch::find_processes(&inject::SystemProcesses, PROCESS_NAME)?;
+    if pids.is_empty() {
+        println!("{PROCESS_NAME} isn't running");
+    }
+    for pid in pids {
+        match inject::Process::open(pid) {
+            Ok(process) => println!("{}", process.instance(TOOL_DLL)),
+            Err(e) => println!("{pid:>8}  {e}"),
+        }
+    }
     Ok(())
 }
+
+fn perform_injection(args: &Args) -> Result<(), String> {
+    if args.list {
+        return list_instances();
+    }
+
+    let dll_path = args.dll.clone().unwrap_or_else(default_dll_path);
+
+    if args.eject {
+        let pid =
+            launch::wait_for_window(&inject::SystemProcesses, PROCESS_NAME, args.pid, &args.retry)
+                .map_err(|e| format!("Could not find process: {e}"))?;
+        let report = inject::Process::open(pid)
+            .and_then(|process| process.eject(&dll_path))
+            .map_err(|e| format!("Could not eject DLL: {e}"))?;
+        println!("Ejected {} from {}", report.module, report.pid);
+        return Ok(());
+    }
+
+    if let Some(launch) = &args.launch {
+        trace!("Launching {:?}", launch);
+        launch.start()?;
+    }
+
+    let pid =
+        launch::wait_for_window(&inject::SystemProcesses, PROCESS_NAME, args.pid, &args.retry)
+            .map_err(|e| format!("Could not find process: {e}"))?;
+
+    let instance = inject::Process::open(pid).map_err(|e| e.to_string())?.instance(TOOL_DLL);
+    if instance.injected {
+        return Err(format!("The tool is already injected in process {pid}"));
+    }
+    trace!("Injecting {:?} into {}", dll_path, instance);
+
+    let report = launch::retry(&args.retry, || {
+        inject::Process::open(pid).and_then(|process| process.inject(&dll_path))
+    })
+    .map_err(|e| format!("Could not inject DLL: {e}"))?;
+    println!("Injected {} into {}", report.module, report.pid);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse(&[]).unwrap();
+        assert_eq!(args, Args::default());
+
+        let args = parse(&["--launch", "steam", "--retries", "3", "--dll", "tool.dll"]).unwrap();
+        assert_eq!(args.launch, Some(Launch::Steam));
+        assert_eq!(args.dll, Some(PathBuf::from("tool.dll")));
+        assert_eq!(args.retry.timeout, LAUNCH_TIMEOUT);
+        assert_eq!(args.retry.attempts, 3);
+
+        let args =
+            parse(&["--launch", "game.exe", "--timeout", "30", "--interval", "100"]).unwrap();
+        assert_eq!(args.launch, Some(Launch::Direct("game.exe".into())));
+        assert_eq!(args.retry.timeout, Duration::from_secs(30));
+        assert_eq!(args.retry.interval, Duration::from_millis(100));
+
+        assert!(parse(&["--timeout"]).is_err());
+        assert!(parse(&["--timeout", "soon"]).is_err());
+        assert!(parse(&["--eject"]).unwrap().eject);
+        let args = parse(&["--pid", "1234", "--list"]).unwrap();
+        assert_eq!(args.pid, Some(1234));
+        assert!(args.list);
+        assert!(parse(&["--pid", "first"]).is_err());
+        assert!(parse(&["--pid", "-1"]).is_err());
+        assert_eq!(
+            parse(&["--pid", "4294967296"]),
+            Err("Value for --pid is too large: 4294967296".to_string())
+        );
+        assert!(parse(&["--retries", "4294967297"]).is_err());
+        assert!(parse(&["--inject"]).is_err());
+    }
+}
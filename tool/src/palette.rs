@@ -0,0 +1,305 @@
+//! Actions of the config's commands, runnable by name from the command
+//! palette and from macros.
+//!
+//! Commands register their actions while their widgets are built, the same
+//! way they register indicators. Actions share the game state of their
+//! widget, and those that need the widget itself, e.g. to start a macro,
+//! pull a [`Trigger`] it checks every frame.
+//!
+//! While the palette is open, its search field gets the keys: the commands
+//! keep running every frame, but ignore their hotkeys.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+static TYPING: AtomicBool = AtomicBool::new(false);
+
+/// Whether the commands should handle their hotkeys: not while typing in the
+/// palette, where letters and arrows would press them.
+pub(crate) fn hotkeys_enabled() -> bool {
+    !TYPING.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_typing(typing: bool) {
+    TYPING.store(typing, Ordering::Relaxed);
+}
+
+/// Something the palette can run.
+pub(crate) trait PaletteAction: Send + Sync {
+    fn run(&mut self) -> Result<(), String>;
+}
+
+/// A widget that has actions to list in the palette.
+pub(crate) trait PaletteProvider {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)>;
+}
+
+/// Asks a widget to do something on its next frame.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Trigger(Arc<AtomicBool>);
+
+impl Trigger {
+    /// Whether the trigger was pulled since the last call.
+    pub(crate) fn take(&self) -> bool {
+        self.0.swap(false, Ordering::Relaxed)
+    }
+}
+
+impl PaletteAction for Trigger {
+    fn run(&mut self) -> Result<(), String> {
+        self.0.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+struct PaletteEntry {
+    name: String,
+    action: Box<dyn PaletteAction>,
+}
+
+/// The registered actions, shared by the palette and the macros.
+#[derive(Clone, Default)]
+pub(crate) struct Actions(Arc<Mutex<Vec<PaletteEntry>>>);
+
+impl Actions {
+    /// The names of the actions, in registration order.
+    pub(crate) fn names(&self) -> Vec<String> {
+        self.0.lock().unwrap().iter().map(|e| e.name.clone()).collect()
+    }
+
+    pub(crate) fn run(&self, name: &str) -> Result<(), String> {
+        let mut entries = self.0.lock().unwrap();
+        let entry = entries
+            .iter_mut()
+            .find(|e| e.name == name)
+            .ok_or_else(|| format!("No action named \"{name}\""))?;
+        entry.action.run()
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct PaletteRegistry {
+    groups: Vec<String>,
+    actions: Actions,
+    /// Actions run by name, e.g. by macros, and what runs them.
+    required: Vec<(String, String)>,
+}
+
+impl PaletteRegistry {
+    /// Register every action of `provider`, named after the groups it is in,
+    /// e.g. `Practice / No Death`. When the same name is registered more than
+    /// once (e.g. several `position` commands), the later ones get a numeric
+    /// suffix: `Save position`, `Save position (2)`...
+    pub(crate) fn register(&mut self, provider: &impl PaletteProvider) {
+        let mut entries = self.actions.0.lock().unwrap();
+        for (name, action) in provider.palette_actions() {
+            let name = self.groups.iter().chain([&name]).cloned().collect::<Vec<_>>().join(" / ");
+            let name = (1..)
+                .map(|i| match i {
+                    1 => name.clone(),
+                    i => format!("{name} ({i})"),
+                })
+                .find(|n| entries.iter().all(|e| &e.name != n))
+                .unwrap();
+            entries.push(PaletteEntry { name, action });
+        }
+    }
+
+    /// Puts the actions registered until the matching [`Self::pop_group`] in
+    /// group `label`.
+    pub(crate) fn push_group(&mut self, label: &str) {
+        self.groups.push(label.to_string());
+    }
+
+    pub(crate) fn pop_group(&mut self) {
+        self.groups.pop();
+    }
+
+    /// Every action, including those registered after this call.
+    pub(crate) fn actions(&self) -> Actions {
+        self.actions.clone()
+    }
+
+    /// Notes that `user` runs the action `name`, which may be registered
+    /// later on: see [`Self::missing`].
+    pub(crate) fn require(&mut self, user: &str, name: &str) {
+        self.required.push((user.to_string(), name.to_string()));
+    }
+
+    /// An error for every required action that wasn't registered, once all
+    /// the commands are built.
+    pub(crate) fn missing(&self) -> Vec<String> {
+        let names = self.actions.names();
+        self.required
+            .iter()
+            .filter(|(_, name)| !names.contains(name))
+            .map(|(user, name)| format!("{user}: no action named \"{name}\""))
+            .collect()
+    }
+}
+
+/// Scores how well `name` matches `query`, whose characters must all appear
+/// in it in order, ignoring case. Consecutive characters and characters
+/// starting a word score higher, and so do shorter names.
+pub(crate) fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let name = name.chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut next = 0;
+    let mut last_match = None;
+
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let q = q.to_lowercase().collect::<String>();
+        let found = (next..name.len()).find(|&i| name[i].to_lowercase().eq(q.chars()))?;
+
+        score += 1;
+        if last_match.is_some_and(|last| last + 1 == found) {
+            score += 6;
+        }
+        if found == 0 || !name[found - 1].is_alphanumeric() {
+            score += 4;
+        }
+        score -= (found - next).min(10) as i32;
+
+        last_match = Some(found);
+        next = found + 1;
+    }
+
+    Some(score * 100 - name.len() as i32)
+}
+
+/// The indices of the names matching `query`, best match first. An empty
+/// query matches every name, in order.
+pub(crate) fn search<'a>(names: impl IntoIterator<Item = &'a str>, query: &str) -> Vec<usize> {
+    let mut matches = names
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, name)| Some((i, fuzzy_score(query, name)?)))
+        .collect::<Vec<_>>();
+    if !query.trim().is_empty() {
+        matches.sort_by(|(_, a), (_, b)| b.cmp(a));
+    }
+    matches.into_iter().map(|(i, _)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Provider(&'static [&'static str]);
+
+    impl PaletteProvider for Provider {
+        fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+            self.0
+                .iter()
+                .map(|&name| (name.to_string(), Box::new(Trigger::default()) as _))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = PaletteRegistry::default();
+        registry.register(&Provider(&["No Death"]));
+        registry.push_group("Practice");
+        registry.register(&Provider(&["Save position", "Load position"]));
+        registry.push_group("Speed");
+        registry.register(&Provider(&["Cycle speed"]));
+        registry.pop_group();
+        registry.pop_group();
+        registry.register(&Provider(&["Quitout"]));
+        registry.push_group("Practice");
+        registry.register(&Provider(&["Save position", "Load position"]));
+        registry.pop_group();
+
+        assert_eq!(
+            registry.actions().names(),
+            [
+                "No Death",
+                "Practice / Save position",
+                "Practice / Load position",
+                "Practice / Speed / Cycle speed",
+                "Quitout",
+                "Practice / Save position (2)",
+                "Practice / Load position (2)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_actions() {
+        let mut registry = PaletteRegistry::default();
+        let actions = registry.actions();
+
+        let trigger = Trigger::default();
+        struct Start(Trigger);
+        impl PaletteProvider for Start {
+            fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+                vec![("Start".to_string(), Box::new(self.0.clone()))]
+            }
+        }
+        registry.push_group("Macros");
+        registry.register(&Start(trigger.clone()));
+        registry.pop_group();
+
+        // Registered after the handle was taken.
+        assert_eq!(actions.names(), ["Macros / Start"]);
+        actions.run("Macros / Start").unwrap();
+        assert!(trigger.take());
+        assert_eq!(actions.run("Start"), Err("No action named \"Start\"".to_string()));
+    }
+
+    #[test]
+    fn test_missing() {
+        let mut registry = PaletteRegistry::default();
+        registry.require("Setup", "Practice / No Death");
+        registry.require("Setup", "No Death");
+        registry.push_group("Practice");
+        registry.register(&Provider(&["No Death"]));
+        registry.pop_group();
+        registry.require("Reset", "Quitout");
+
+        assert_eq!(
+            registry.missing(),
+            ["Setup: no action named \"No Death\"", "Reset: no action named \"Quitout\""]
+        );
+        registry.register(&Provider(&["Quitout"]));
+        assert_eq!(registry.missing(), ["Setup: no action named \"No Death\""]);
+    }
+
+    #[test]
+    fn test_trigger() {
+        let trigger = Trigger::default();
+        let mut action: Box<dyn PaletteAction> = Box::new(trigger.clone());
+        assert!(!trigger.take());
+        action.run().unwrap();
+        assert!(trigger.take());
+        assert!(!trigger.take());
+    }
+
+    #[test]
+    fn test_search() {
+        let names = [
+            "No Death",
+            "No Gravity",
+            "Practice / Save position",
+            "Practice / Load position",
+            "Add 10000 souls",
+            "Quitout",
+        ];
+        let search = |query| search(names, query);
+
+        assert_eq!(search(""), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(search("nodeath"), [0]);
+        assert_eq!(search("NO DEATH"), [0]);
+        assert_eq!(search("lp")[0], 3);
+        assert_eq!(search("load"), [3]);
+        assert_eq!(search("pos"), [2, 3]);
+        assert_eq!(search("souls"), [4]);
+        assert_eq!(search("qo"), [5]);
+        assert!(search("xyz").is_empty());
+
+        // Word starts and runs of characters beat scattered matches.
+        assert!(fuzzy_score("ng", "No Gravity") > fuzzy_score("ng", "Loading"));
+        assert!(fuzzy_score("grav", "No Gravity") > fuzzy_score("grav", "go right and veer"));
+    }
+}
@@ -0,0 +1,263 @@
+//! Saved positions and the map they belong to.
+//!
+//! Named positions are grouped by map in a library, which is a JSON file
+//! next to the DLL. Exports use the same format, so a library or part of it
+//! can be shared and merged into another one.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct NamedPosition {
+    pub(crate) name: String,
+    pub(crate) pos: [f32; 3],
+    pub(crate) angle: f32,
+}
+
+/// Positions keyed by map name, e.g. `m15_01_00_00` for Sen's Fortress.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct PositionLibrary {
+    maps: BTreeMap<String, Vec<NamedPosition>>,
+}
+
+impl PositionLibrary {
+    /// Loads a library file. A missing file is an empty library.
+    pub(crate) fn load(path: &Path) -> Result<PositionLibrary, String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("Couldn't parse {}: {e}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(PositionLibrary::default()),
+            Err(e) => Err(format!("Couldn't read {}: {e}", path.display())),
+        }
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, content).map_err(|e| format!("Couldn't write {}: {e}", path.display()))
+    }
+
+    pub(crate) fn positions(&self, map: &str) -> &[NamedPosition] {
+        self.maps.get(map).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Adds a position to a map, replacing any position with the same name.
+    pub(crate) fn insert(&mut self, map: &str, position: NamedPosition) {
+        let positions = self.maps.entry(map.to_string()).or_default();
+        match positions.iter_mut().find(|p| p.name == position.name) {
+            Some(existing) => *existing = position,
+            None => positions.push(position),
+        }
+    }
+
+    pub(crate) fn remove(&mut self, map: &str, name: &str) {
+        if let Some(positions) = self.maps.get_mut(map) {
+            positions.retain(|p| p.name != name);
+            if positions.is_empty() {
+                self.maps.remove(map);
+            }
+        }
+    }
+
+    /// Merges the positions of another library file into this one. Returns
+    /// the number of positions imported.
+    pub(crate) fn import(&mut self, path: &Path) -> Result<usize, String> {
+        if !path.is_file() {
+            return Err(format!("{} doesn't exist", path.display()));
+        }
+        let other = PositionLibrary::load(path)?;
+        let mut count = 0;
+        for (map, positions) in other.maps {
+            for position in positions {
+                self.insert(&map, position);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// A position with the map it was saved in, and the bonfire the player
+/// last rested at, which gets them back to that map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct StoredPosition {
+    pub(crate) pos: [f32; 3],
+    pub(crate) angle: f32,
+    pub(crate) map_id: Option<u32>,
+    pub(crate) bonfire: Option<u32>,
+}
+
+/// What loading a stored position takes, given the current map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LoadPlan {
+    /// Same map block: write the coordinates.
+    Apply,
+    /// Another map: warp to the bonfire, then write the coordinates.
+    Warp(u32),
+    /// Another map and no way to get there, or the current map is unknown.
+    Refuse,
+}
+
+impl StoredPosition {
+    pub(crate) fn plan_load(&self, current_map: Option<u32>) -> LoadPlan {
+        let Some(saved_map) = self.map_id else {
+            return LoadPlan::Apply;
+        };
+        match (current_map, self.bonfire) {
+            (Some(current), _) if same_block(saved_map, current) => LoadPlan::Apply,
+            (Some(_), Some(bonfire)) if bonfire_in_map(bonfire, saved_map) => {
+                LoadPlan::Warp(bonfire)
+            }
+            _ => LoadPlan::Refuse,
+        }
+    }
+}
+
+/// Whether two map IDs are in the same map block, which share coordinates.
+pub(crate) fn same_block(a: u32, b: u32) -> bool {
+    a >> 8 == b >> 8
+}
+
+/// Whether a bonfire is in the map of `map_id`. Bonfire IDs start with the
+/// world and area of their map, e.g. `1511950` is in `m15_01_00_00`.
+pub(crate) fn bonfire_in_map(bonfire: u32, map_id: u32) -> bool {
+    let [_, _, area, world] = map_id.to_le_bytes();
+    bonfire / 100_000 == world as u32 && bonfire / 10_000 % 10 == area as u32
+}
+
+/// Moves `pos` by `forward`, `right` and `up` units relative to a facing
+/// `angle`, in radians. An angle of 0 faces +Z, with +X on the right.
+pub(crate) fn offset_relative(
+    pos: [f32; 3],
+    angle: f32,
+    [forward, right, up]: [f32; 3],
+) -> [f32; 3] {
+    let (sin, cos) = angle.sin_cos();
+    [pos[0] + forward * sin + right * cos, pos[1] + up, pos[2] + forward * cos - right * sin]
+}
+
+/// Turns a facing `angle`, in radians, by `degrees` to the right, wrapped to
+/// the game's range of -π to π.
+pub(crate) fn rotate(angle: f32, degrees: f32) -> f32 {
+    use std::f32::consts::{PI, TAU};
+    let turned = (angle + degrees.to_radians() + PI).rem_euclid(TAU) - PI;
+    if turned == -PI {
+        PI
+    } else {
+        turned
+    }
+}
+
+/// Formats a map ID as read from memory, e.g. `0x0f010000` is `m15_01_00_00`.
+pub(crate) fn map_name(map_id: u32) -> String {
+    let [region, block, area, world] = map_id.to_le_bytes();
+    format!("m{world:02}_{area:02}_{block:02}_{region:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(name: &str, x: f32) -> NamedPosition {
+        NamedPosition { name: name.to_string(), pos: [x, 0., 0.], angle: 0. }
+    }
+
+    #[test]
+    fn test_map_name() {
+        assert_eq!(map_name(0x0f010000), "m15_01_00_00");
+        assert_eq!(map_name(0x12010001), "m18_01_00_01");
+    }
+
+    #[test]
+    fn test_bonfire_in_map() {
+        assert!(bonfire_in_map(1511950, 0x0f010000));
+        assert!(bonfire_in_map(1511950, 0x0f010100));
+        assert!(!bonfire_in_map(1511950, 0x0f000000));
+        assert!(!bonfire_in_map(1011962, 0x0f010000));
+        assert!(!bonfire_in_map(0, 0x0f010000));
+    }
+
+    #[test]
+    fn test_offset_relative() {
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5);
+        let origin = [1., 2., 3.];
+        assert!(close(offset_relative(origin, 0., [1., 0., 0.]), [1., 2., 4.]));
+        assert!(close(offset_relative(origin, 0., [0., 1., -1.]), [2., 1., 3.]));
+        let quarter = std::f32::consts::FRAC_PI_2;
+        assert!(close(offset_relative(origin, quarter, [1., 0., 0.]), [2., 2., 3.]));
+        assert!(close(offset_relative(origin, quarter, [0., 1., 0.]), [1., 2., 2.]));
+    }
+
+    #[test]
+    fn test_rotate() {
+        use std::f32::consts::{FRAC_PI_2, PI};
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        assert!(close(rotate(0., 90.), FRAC_PI_2));
+        assert!(close(rotate(0., -90.), -FRAC_PI_2));
+        assert!(close(rotate(FRAC_PI_2, 90.), PI));
+        assert!(close(rotate(PI, 90.), -FRAC_PI_2));
+        assert!(close(rotate(-FRAC_PI_2, -135.), 3. * PI / 4.));
+        assert!(close(rotate(1., 360.), 1.));
+    }
+
+    #[test]
+    fn test_plan_load() {
+        let stored = StoredPosition {
+            pos: [1., 2., 3.],
+            angle: 0.,
+            map_id: Some(0x0f010000),
+            bonfire: Some(1511950),
+        };
+        assert_eq!(stored.plan_load(Some(0x0f010000)), LoadPlan::Apply);
+        assert_eq!(stored.plan_load(Some(0x0f010001)), LoadPlan::Apply);
+        assert_eq!(stored.plan_load(Some(0x0a010000)), LoadPlan::Warp(1511950));
+        assert_eq!(stored.plan_load(None), LoadPlan::Refuse);
+
+        let no_bonfire = StoredPosition { bonfire: None, ..stored };
+        assert_eq!(no_bonfire.plan_load(Some(0x0a010000)), LoadPlan::Refuse);
+        let zero_bonfire = StoredPosition { bonfire: Some(0), ..stored };
+        assert_eq!(zero_bonfire.plan_load(Some(0x0a010000)), LoadPlan::Refuse);
+        // The last bonfire was in another map, so warping wouldn't get there.
+        let other_bonfire = StoredPosition { bonfire: Some(1011962), ..stored };
+        assert_eq!(other_bonfire.plan_load(Some(0x0a010000)), LoadPlan::Refuse);
+        assert_eq!(other_bonfire.plan_load(Some(0x0f010000)), LoadPlan::Apply);
+        let no_map = StoredPosition { map_id: None, ..stored };
+        assert_eq!(no_map.plan_load(Some(0x0a010000)), LoadPlan::Apply);
+    }
+
+    #[test]
+    fn test_library() {
+        let dir = std::env::temp_dir().join(format!("dsr-tool-positions-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut library = PositionLibrary::default();
+        library.insert("m15_01_00_00", position("Boulder skip", 1.));
+        library.insert("m15_01_00_00", position("Elevator", 2.));
+        library.insert("m15_01_00_00", position("Boulder skip", 3.));
+        library.insert("m10_01_00_00", position("Bridge", 4.));
+        assert_eq!(library.positions("m15_01_00_00").len(), 2);
+        assert_eq!(library.positions("m15_01_00_00")[0].pos[0], 3.);
+        assert!(library.positions("m12_00_00_00").is_empty());
+
+        library.save(&dir.join("positions.json")).unwrap();
+        assert_eq!(PositionLibrary::load(&dir.join("positions.json")).unwrap(), library);
+        assert_eq!(PositionLibrary::load(&dir.join("missing.json")).unwrap().maps.len(), 0);
+
+        let mut shared = PositionLibrary::default();
+        shared.insert("m15_01_00_00", position("Elevator", 5.));
+        shared.insert("m12_00_00_00", position("Sif", 6.));
+        shared.save(&dir.join("shared.json")).unwrap();
+
+        assert_eq!(library.import(&dir.join("shared.json")).unwrap(), 2);
+        assert_eq!(library.positions("m15_01_00_00")[1].pos[0], 5.);
+        assert_eq!(library.positions("m12_00_00_00").len(), 1);
+        assert!(library.import(&dir.join("missing.json")).is_err());
+
+        library.remove("m12_00_00_00", "Sif");
+        library.remove("m12_00_00_00", "Sif");
+        assert!(!library.maps.contains_key("m12_00_00_00"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,276 @@
+//! Loading as a `dinput8.dll` proxy.
+//!
+//! Dropped in the game directory as `dinput8.dll`, the tool starts on its own
+//! from `DllMain` and forwards the DirectInput exports to the next
+//! `dinput8.dll`: the first DLL of `settings.dinput8_chain` that exports
+//! them, or the system one. The forwarding target is only loaded on the first
+//! call to an export, outside of the loader lock.
+//!
+//! However the tool got loaded, it then loads the DLLs of
+//! `settings.load_dlls`, for mods that don't need to be loaded early.
+
+use std::ffi::{c_void, OsString};
+use std::os::windows::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use practice_tool_core::crossbeam_channel::Sender;
+use windows::core::{s, Error, GUID, HRESULT, HSTRING, PCSTR};
+use windows::Win32::Foundation::{
+    CLASS_E_CLASSNOTAVAILABLE, E_FAIL, HINSTANCE, HMODULE, MAX_PATH, S_FALSE,
+};
+use windows::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW};
+use windows::Win32::System::SystemInformation::GetSystemDirectoryW;
+
+use crate::config::ProxyConfig;
+use crate::util;
+
+type FDirectInput8Create = unsafe extern "system" fn(
+    hinst: HINSTANCE,
+    dwversion: u32,
+    riidltf: *const GUID,
+    ppvout: *mut *mut c_void,
+    punkouter: *mut c_void,
+) -> HRESULT;
+type FDllCanUnloadNow = unsafe extern "system" fn() -> HRESULT;
+type FDllGetClassObject =
+    unsafe extern "system" fn(*const GUID, *const GUID, *mut *mut c_void) -> HRESULT;
+type FDllRegisterServer = unsafe extern "system" fn() -> HRESULT;
+type FGetdfDIJoystick = unsafe extern "system" fn() -> *const c_void;
+
+/// The exports of the `dinput8.dll` calls are forwarded to. Only
+/// `DirectInput8Create` is required.
+struct Dinput8 {
+    direct_input8_create: FDirectInput8Create,
+    dll_can_unload_now: Option<FDllCanUnloadNow>,
+    dll_get_class_object: Option<FDllGetClassObject>,
+    dll_register_server: Option<FDllRegisterServer>,
+    dll_unregister_server: Option<FDllRegisterServer>,
+    get_df_di_joystick: Option<FGetdfDIJoystick>,
+}
+
+static DINPUT8: Lazy<Option<Dinput8>> = Lazy::new(|| match load() {
+    Ok(dinput8) => Some(dinput8),
+    Err(e) => {
+        log(e);
+        None
+    }
+});
+
+/// Messages from loading, which can happen before logging is set up. They
+/// are shown in the log once the tool starts.
+static LOGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn log(message: String) {
+    LOGS.lock().unwrap().push(message);
+}
+
+/// Takes the messages logged while loading the proxy so far.
+pub(crate) fn take_logs() -> Vec<String> {
+    std::mem::take(&mut *LOGS.lock().unwrap())
+}
+
+/// The system's `dinput8.dll`.
+fn system_dinput8_path() -> Result<PathBuf, String> {
+    let mut buf = vec![0u16; MAX_PATH as usize];
+    loop {
+        // The length without the terminator, or the size needed with it when
+        // the buffer is too small.
+        let len = unsafe { GetSystemDirectoryW(Some(&mut buf)) } as usize;
+        if len == 0 {
+            return Err(format!("Couldn't find the system directory: {}", Error::from_win32()));
+        }
+        if len < buf.len() {
+            return Ok(PathBuf::from(OsString::from_wide(&buf[..len])).join("dinput8.dll"));
+        }
+        buf.resize(len, 0);
+    }
+}
+
+/// Resolves DLL paths from the config relative to the directory of the
+/// tool's DLL.
+fn resolve_paths(dir: &Path, paths: &[PathBuf]) -> Vec<PathBuf> {
+    paths.iter().filter(|path| !path.as_os_str().is_empty()).map(|path| dir.join(path)).collect()
+}
+
+fn dll_dir() -> Result<PathBuf, String> {
+    util::get_dll_path()
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .ok_or_else(|| "Couldn't find the DLL directory".to_string())
+}
+
+fn chain_paths() -> Result<Vec<PathBuf>, String> {
+    let dir = dll_dir()?;
+    let config = match std::fs::read_to_string(dir.join("dark_souls_remastered_tool.toml")) {
+        Ok(content) => ProxyConfig::parse(&content)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => ProxyConfig::default(),
+        Err(e) => return Err(format!("Couldn't read config file: {e}")),
+    };
+    Ok(resolve_paths(&dir, config.dinput8_chain()))
+}
+
+fn load_library(path: &Path) -> Result<HMODULE, String> {
+    unsafe { LoadLibraryW(&HSTRING::from(path)) }
+        .map_err(|e| format!("Couldn't load {}: {e}", path.display()))
+}
+
+unsafe fn export<T>(module: HMODULE, name: PCSTR) -> Option<T> {
+    GetProcAddress(module, name).map(|f| std::mem::transmute_copy(&f))
+}
+
+/// Whether a module forwards to somewhere other than this DLL, which would
+/// call itself forever.
+unsafe fn forwards_elsewhere(module: HMODULE) -> bool {
+    export::<FDirectInput8Create>(module, s!("DirectInput8Create"))
+        .is_some_and(|f| f as *const () != DirectInput8Create as *const ())
+}
+
+fn load() -> Result<Dinput8, String> {
+    let chain = chain_paths().unwrap_or_else(|e| {
+        log(format!("Not chain-loading dinput8 mods: {e}"));
+        Vec::new()
+    });
+
+    let mut target = None;
+    for path in chain {
+        match load_library(&path) {
+            Ok(module) if target.is_none() && unsafe { forwards_elsewhere(module) } => {
+                log(format!("Forwarding DirectInput to {}", path.display()));
+                target = Some(module);
+            }
+            Ok(_) => log(format!("Loaded {}", path.display())),
+            Err(e) => log(e),
+        }
+    }
+
+    let module = match target {
+        Some(module) => module,
+        None => load_library(&system_dinput8_path()?)?,
+    };
+
+    unsafe {
+        Ok(Dinput8 {
+            direct_input8_create: export(module, s!("DirectInput8Create"))
+                .ok_or("dinput8.dll doesn't export DirectInput8Create")?,
+            dll_can_unload_now: export(module, s!("DllCanUnloadNow")),
+            dll_get_class_object: export(module, s!("DllGetClassObject")),
+            dll_register_server: export(module, s!("DllRegisterServer")),
+            dll_unregister_server: export(module, s!("DllUnregisterServer")),
+            get_df_di_joystick: export(module, s!("GetdfDIJoystick")),
+        })
+    }
+}
+
+/// Loads the DLLs in order, after a delay, in a thread of their own. Each
+/// success or failure is sent to the log.
+pub(crate) fn load_dlls(paths: &[PathBuf], delay: Duration, log_tx: Sender<String>) {
+    if paths.is_empty() {
+        return;
+    }
+    let paths = match dll_dir() {
+        Ok(dir) => resolve_paths(&dir, paths),
+        Err(e) => {
+            log_tx.send(format!("Not loading DLLs: {e}")).ok();
+            return;
+        }
+    };
+
+    thread::spawn(move || {
+        thread::sleep(delay);
+        for path in paths {
+            let message = match load_library(&path) {
+                Ok(_) => format!("Loaded {}", path.display()),
+                Err(e) => e,
+            };
+            log_tx.send(message).ok();
+        }
+    });
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "system" fn DirectInput8Create(
+    hinst: HINSTANCE,
+    dwversion: u32,
+    riidltf: *const GUID,
+    ppvout: *mut *mut c_void,
+    punkouter: *mut c_void,
+) -> HRESULT {
+    match &*DINPUT8 {
+        Some(dinput8) => {
+            (dinput8.direct_input8_create)(hinst, dwversion, riidltf, ppvout, punkouter)
+        }
+        None => E_FAIL,
+    }
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "system" fn DllCanUnloadNow() -> HRESULT {
+    match DINPUT8.as_ref().and_then(|d| d.dll_can_unload_now) {
+        Some(f) => f(),
+        None => S_FALSE,
+    }
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "system" fn DllGetClassObject(
+    rclsid: *const GUID,
+    riid: *const GUID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    match DINPUT8.as_ref().and_then(|d| d.dll_get_class_object) {
+        Some(f) => f(rclsid, riid, ppv),
+        None => CLASS_E_CLASSNOTAVAILABLE,
+    }
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "system" fn DllRegisterServer() -> HRESULT {
+    match DINPUT8.as_ref().and_then(|d| d.dll_register_server) {
+        Some(f) => f(),
+        None => E_FAIL,
+    }
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "system" fn DllUnregisterServer() -> HRESULT {
+    match DINPUT8.as_ref().and_then(|d| d.dll_unregister_server) {
+        Some(f) => f(),
+        None => E_FAIL,
+    }
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "system" fn GetdfDIJoystick() -> *const c_void {
+    match DINPUT8.as_ref().and_then(|d| d.get_df_di_joystick) {
+        Some(f) => f(),
+        None => std::ptr::null(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_paths() {
+        let dir = Path::new("C:\\Games\\DARK SOULS REMASTERED");
+        let chain = [
+            PathBuf::from("dinput8_fps.dll"),
+            PathBuf::new(),
+            PathBuf::from("C:\\mods\\dinput8_textures.dll"),
+        ];
+        assert_eq!(
+            resolve_paths(dir, &chain),
+            [dir.join("dinput8_fps.dll"), PathBuf::from("C:\\mods\\dinput8_textures.dll"),]
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! Game state to put back when ejecting.
+//!
+//! Widgets record what they are about to change right before every write.
+//! Only the first recording of a value is kept, keyed on the memory it is
+//! in, so widgets that change the same state (e.g. the gravity flag and
+//! noclip) share its original value whatever label they give it.
+//!
+//! Only toggle-like state is recorded, such as flags and speed. Values that
+//! also change through play, like souls, character stats or IGT, are left
+//! as they are: writing them back would undo progress, or land on another
+//! character after loading a save.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use libdsr::memedit::{Bitflag, PointerChain};
+
+/// A readable description of a value, and a way to write it back which
+/// reports whether it worked.
+pub(crate) type Snapshot = (String, Box<dyn Fn() -> bool + Send>);
+
+/// Where a value is in memory: the path of its pointer chain, and the bits
+/// of a flag or `None` for a whole value.
+pub(crate) type RestoreKey = (Vec<usize>, Option<u8>);
+
+/// State that can be read now and written back later.
+pub(crate) trait Restorable {
+    fn key(&self) -> RestoreKey;
+    fn snapshot(&self) -> Option<Snapshot>;
+}
+
+impl Restorable for Bitflag<u8> {
+    fn key(&self) -> RestoreKey {
+        (self.chain().path(), Some(self.mask()))
+    }
+
+    fn snapshot(&self) -> Option<Snapshot> {
+        let value = self.get()?;
+        let flag = self.clone();
+        let restore = move || {
+            flag.set(value);
+            flag.get() == Some(value)
+        };
+        Some((if value { "on" } else { "off" }.to_string(), Box::new(restore)))
+    }
+}
+
+/// Snapshots a whole value, described by `describe`.
+fn snapshot_value<T: Clone + Send + 'static>(
+    chain: &PointerChain<T>,
+    describe: impl Fn(&T) -> String,
+) -> Option<Snapshot> {
+    let value = chain.read()?;
+    let chain = chain.clone();
+    Some((describe(&value), Box::new(move || chain.write(value.clone()).is_some())))
+}
+
+impl Restorable for PointerChain<f32> {
+    fn key(&self) -> RestoreKey {
+        (self.path(), None)
+    }
+
+    fn snapshot(&self) -> Option<Snapshot> {
+        snapshot_value(self, |value| format!("{value:.2}"))
+    }
+}
+
+impl Restorable for PointerChain<u32> {
+    fn key(&self) -> RestoreKey {
+        (self.path(), None)
+    }
+
+    fn snapshot(&self) -> Option<Snapshot> {
+        snapshot_value(self, u32::to_string)
+    }
+}
+
+struct Original {
+    key: RestoreKey,
+    label: String,
+    value: String,
+    restore: Box<dyn Fn() -> bool + Send>,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct RestoreRegistry(Arc<Mutex<Vec<Original>>>);
+
+impl RestoreRegistry {
+    /// Records the current state of `target` under `label`, unless its
+    /// state was already recorded.
+    pub(crate) fn record(&self, label: &str, target: &impl Restorable) {
+        let key = target.key();
+        let mut originals = self.0.lock().unwrap();
+        if originals.iter().any(|o| o.key == key) {
+            return;
+        }
+        if let Some((value, restore)) = target.snapshot() {
+            originals.push(Original { key, label: label.to_string(), value, restore });
+        }
+    }
+
+    /// Writes back every recorded value, latest first, and forgets them.
+    /// Returns one line per value.
+    pub(crate) fn restore_all(&self) -> Vec<String> {
+        let originals = std::mem::take(&mut *self.0.lock().unwrap());
+        originals
+            .into_iter()
+            .rev()
+            .map(|Original { label, value, restore, .. }| {
+                if restore() {
+                    format!("Restored {label} to {value}")
+                } else {
+                    format!("Couldn't restore {label} to {value}")
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for RestoreRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let originals = self.0.lock().unwrap();
+        f.debug_list().entries(originals.iter().map(|o| (&o.label, &o.value))).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    /// A value in memory that may become unreachable, like a pointer chain
+    /// during a loading screen.
+    #[derive(Clone, Default)]
+    struct Value(Arc<AtomicU32>);
+
+    impl Value {
+        const UNREACHABLE: u32 = u32::MAX;
+    }
+
+    impl Restorable for Value {
+        fn key(&self) -> RestoreKey {
+            (vec![Arc::as_ptr(&self.0) as usize], None)
+        }
+
+        fn snapshot(&self) -> Option<Snapshot> {
+            let value = self.0.load(Ordering::SeqCst);
+            if value == Value::UNREACHABLE {
+                return None;
+            }
+            let target = self.clone();
+            Some((
+                value.to_string(),
+                Box::new(move || target.0.swap(value, Ordering::SeqCst) != Value::UNREACHABLE),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_restore_all() {
+        let registry = RestoreRegistry::default();
+        let speed = Value::default();
+        let gravity = Value::default();
+
+        speed.0.store(1, Ordering::SeqCst);
+        registry.record("Speed", &speed);
+        speed.0.store(2, Ordering::SeqCst);
+        registry.record("Speed", &speed);
+        speed.0.store(5, Ordering::SeqCst);
+
+        gravity.0.store(Value::UNREACHABLE, Ordering::SeqCst);
+        registry.record("No Gravity", &gravity);
+        gravity.0.store(0, Ordering::SeqCst);
+        registry.record("No Gravity", &gravity);
+        gravity.0.store(1, Ordering::SeqCst);
+        // The same value under another label keeps the first recording.
+        registry.record("Noclip", &gravity.clone());
+
+        assert_eq!(registry.restore_all(), ["Restored No Gravity to 0", "Restored Speed to 1"]);
+        assert_eq!(speed.0.load(Ordering::SeqCst), 1);
+        assert_eq!(gravity.0.load(Ordering::SeqCst), 0);
+        assert!(registry.restore_all().is_empty());
+
+        registry.record("Speed", &speed);
+        speed.0.store(Value::UNREACHABLE, Ordering::SeqCst);
+        assert_eq!(registry.restore_all(), ["Couldn't restore Speed to 1"]);
+    }
+}
@@ -0,0 +1,321 @@
+//! Backups of the game's save, taken before the tool overwrites it.
+//!
+//! Backups are copies of `DRAKS0005.sl2` in a `backups` folder next to it,
+//! named after the UTC time they were taken. Old ones are pruned to a
+//! maximum count and total size.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libdsr::savefile::{crypto, Savefile};
+use serde::Deserialize;
+
+const BACKUP_DIR: &str = "backups";
+const BACKUP_PREFIX: &str = "DRAKS0005_";
+const BACKUP_EXTENSION: &str = "bak";
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub(crate) struct BackupSettings {
+    /// Number of backups to keep.
+    pub(crate) keep: usize,
+    /// Total size of the backups, in megabytes. The newest backup is kept
+    /// even if it's larger.
+    pub(crate) max_size_mb: u64,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        BackupSettings { keep: 20, max_size_mb: 100 }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Backup {
+    pub(crate) path: PathBuf,
+    /// When the backup was taken, as `YYYY-MM-DD HH:MM:SS`.
+    pub(crate) label: String,
+    pub(crate) size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Backups {
+    dir: PathBuf,
+    settings: BackupSettings,
+}
+
+impl Backups {
+    /// The backups of the save in the account folder `dir`.
+    pub(crate) fn new(dir: &Path, settings: BackupSettings) -> Self {
+        Backups { dir: dir.join(BACKUP_DIR), settings }
+    }
+
+    /// Copies `savefile` to a new backup, then prunes old backups. Returns
+    /// `None` if there is no save to back up yet.
+    pub(crate) fn backup(&self, savefile: &Path) -> Result<Option<PathBuf>, String> {
+        self.backup_at(savefile, SystemTime::now())
+    }
+
+    /// Copies `savefile` to a new backup without pruning, ahead of a write
+    /// that may not happen. The backup is then kept with [`Backups::prune`]
+    /// or dropped with [`Backups::discard`].
+    pub(crate) fn copy(&self, savefile: &Path) -> Result<Option<PathBuf>, String> {
+        self.copy_at(savefile, SystemTime::now())
+    }
+
+    /// Deletes a backup taken for a write that didn't happen.
+    pub(crate) fn discard(&self, backup: &Path) -> Result<(), String> {
+        std::fs::remove_file(backup)
+            .map_err(|e| format!("Couldn't delete {}: {e}", backup.display()))
+    }
+
+    fn backup_at(&self, savefile: &Path, now: SystemTime) -> Result<Option<PathBuf>, String> {
+        let path = self.copy_at(savefile, now)?;
+        if path.is_some() {
+            self.prune()?;
+        }
+        Ok(path)
+    }
+
+    fn copy_at(&self, savefile: &Path, now: SystemTime) -> Result<Option<PathBuf>, String> {
+        if !savefile.is_file() {
+            return Ok(None);
+        }
+
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Couldn't create {}: {e}", self.dir.display()))?;
+
+        let mut millis = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let path = loop {
+            let path = self.dir.join(backup_name(millis));
+            if !path.exists() {
+                break path;
+            }
+            millis += 1;
+        };
+
+        std::fs::copy(savefile, &path)
+            .map_err(|e| format!("Couldn't back up {}: {e}", savefile.display()))?;
+        Ok(Some(path))
+    }
+
+    /// Backups on disk, newest first.
+    pub(crate) fn list(&self) -> Vec<Backup> {
+        let mut backups: Vec<Backup> = std::fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let path = e.path();
+                let name = path.file_name()?.to_str()?;
+                let stamp = name
+                    .strip_prefix(BACKUP_PREFIX)?
+                    .strip_suffix(BACKUP_EXTENSION)?
+                    .strip_suffix('.')?;
+                let label = parse_stamp(stamp)?;
+                let size = e.metadata().ok()?.len();
+                Some(Backup { path, label, size })
+            })
+            .collect();
+        backups.sort_by(|a, b| b.path.cmp(&a.path));
+        backups
+    }
+
+    /// Deletes the oldest backups beyond the retention count and size cap.
+    pub(crate) fn prune(&self) -> Result<(), String> {
+        let max_bytes = self.settings.max_size_mb * 1024 * 1024;
+        let mut total = 0;
+
+        for (i, backup) in self.list().into_iter().enumerate() {
+            total += backup.size;
+            if i > 0 && (i >= self.settings.keep || total > max_bytes) {
+                std::fs::remove_file(&backup.path)
+                    .map_err(|e| format!("Couldn't delete {}: {e}", backup.path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces `savefile` with a backup, after backing up the current one.
+    pub(crate) fn restore(&self, backup: &Path, savefile: &Path) -> Result<(), String> {
+        let data = std::fs::read(backup)
+            .map_err(|e| format!("Couldn't read {}: {e}", backup.display()))?;
+        write_savefile(savefile, &data, self).map_err(|e| format!("{}: {e}", backup.display()))
+    }
+}
+
+/// Checks that `data` is a save whose slots all match their checksums.
+pub(crate) fn verify(data: &[u8]) -> Result<(), String> {
+    let save = Savefile::parse(data.to_vec())?;
+    let bnd4 = save.bnd4();
+    match bnd4.entries().iter().find(|e| !crypto::verify_entry(bnd4.data(e))) {
+        Some(entry) => Err(format!("{} fails checksum verification", entry.name)),
+        None => Ok(()),
+    }
+}
+
+/// Reads and verifies the save at `path`.
+pub(crate) fn verify_file(path: &Path) -> Result<(), String> {
+    let data = std::fs::read(path).map_err(|e| format!("Couldn't read {}: {e}", path.display()))?;
+    verify(&data).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Overwrites `savefile` with `data`, backing it up first.
+///
+/// The data is written to a temporary file which is verified before it
+/// replaces the save, so a failed or partial write never clobbers it.
+pub(crate) fn write_savefile(
+    savefile: &Path,
+    data: &[u8],
+    backups: &Backups,
+) -> Result<(), String> {
+    verify(data)?;
+    backups.backup(savefile)?;
+
+    let tmp = savefile.with_extension("sl2.tmp");
+    std::fs::write(&tmp, data).map_err(|e| format!("Couldn't write {}: {e}", tmp.display()))?;
+    if let Err(e) = verify_file(&tmp) {
+        std::fs::remove_file(&tmp).ok();
+        return Err(e);
+    }
+    std::fs::rename(&tmp, savefile)
+        .map_err(|e| format!("Couldn't replace {}: {e}", savefile.display()))
+}
+
+fn backup_name(millis: u64) -> String {
+    let secs = millis / 1000;
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs = secs % 86400;
+    format!(
+        "{BACKUP_PREFIX}{year:04}{month:02}{day:02}-{:02}{:02}{:02}-{:03}.{BACKUP_EXTENSION}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        millis % 1000
+    )
+}
+
+// `20261019-120000-123` to `2026-10-19 12:00:00`.
+fn parse_stamp(stamp: &str) -> Option<String> {
+    let digits = |s: &str| s.chars().all(|c| c.is_ascii_digit()).then_some(());
+    let (date, rest) = stamp.split_once('-')?;
+    let (time, millis) = rest.split_once('-')?;
+    if date.len() != 8 || time.len() != 6 || millis.len() != 3 {
+        return None;
+    }
+    digits(date)?;
+    digits(time)?;
+    digits(millis)?;
+
+    Some(format!(
+        "{}-{}-{} {}:{}:{}",
+        &date[..4],
+        &date[4..6],
+        &date[6..],
+        &time[..2],
+        &time[2..4],
+        &time[4..]
+    ))
+}
+
+// Days since the Unix epoch to a proleptic Gregorian date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::savefile::tests::{temp_dir, write_save};
+    use crate::savefile::SAVEFILE_NAME;
+
+    #[test]
+    fn test_backup_names() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(20745), (2026, 10, 19));
+        assert_eq!(backup_name(1_790_000_000_123), "DRAKS0005_20260921-141320-123.bak");
+        assert_eq!(parse_stamp("20260922-142000-123").unwrap(), "2026-09-22 14:20:00");
+        assert!(parse_stamp("2026092-142000-123").is_none());
+        assert!(parse_stamp("notes").is_none());
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = temp_dir("backups");
+        let savefile = dir.join(SAVEFILE_NAME);
+        let backups = Backups::new(&dir, BackupSettings { keep: 3, max_size_mb: 100 });
+        assert_eq!(backups.backup(&savefile).unwrap(), None);
+
+        write_save(&savefile, &["Solaire"]);
+        let start = UNIX_EPOCH + Duration::from_secs(1_790_000_000);
+        for i in 0..5 {
+            backups.backup_at(&savefile, start + Duration::from_secs(i)).unwrap();
+        }
+        // Backups taken in the same millisecond don't overwrite each other.
+        backups.backup_at(&savefile, start + Duration::from_secs(4)).unwrap();
+
+        let list = backups.list();
+        let labels: Vec<_> = list.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(labels, ["2026-09-21 14:13:24", "2026-09-21 14:13:24", "2026-09-21 14:13:23"]);
+
+        // The size cap prunes everything but the newest backup.
+        let backups = Backups::new(&dir, BackupSettings { keep: 3, max_size_mb: 0 });
+        backups.prune().unwrap();
+        assert_eq!(backups.list().len(), 1);
+        assert_eq!(backups.list()[0].path, list[0].path);
+
+        // Copies aren't pruned until kept, and can be dropped.
+        let copy = backups.copy(&savefile).unwrap().unwrap();
+        assert_eq!(backups.list().len(), 2);
+        backups.discard(&copy).unwrap();
+        assert_eq!(backups.list()[0].path, list[0].path);
+        assert_eq!(backups.list().len(), 1);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_write_and_restore() {
+        let dir = temp_dir("restore");
+        let savefile = dir.join(SAVEFILE_NAME);
+        let backups = Backups::new(&dir, BackupSettings::default());
+        write_save(&savefile, &["Solaire"]);
+        let original = std::fs::read(&savefile).unwrap();
+        verify_file(&savefile).unwrap();
+
+        // Partial and corrupted saves are refused and leave the save alone.
+        assert!(write_savefile(&savefile, &original[..original.len() - 16], &backups).is_err());
+        let mut corrupted = original.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xff;
+        assert!(verify(&corrupted).unwrap_err().contains("USER_DATA000"));
+        assert!(write_savefile(&savefile, &corrupted, &backups).is_err());
+        assert_eq!(std::fs::read(&savefile).unwrap(), original);
+        assert!(backups.list().is_empty());
+
+        write_save(&dir.join("other.sl2"), &["Oscar"]);
+        let other = std::fs::read(dir.join("other.sl2")).unwrap();
+        write_savefile(&savefile, &other, &backups).unwrap();
+        assert_eq!(std::fs::read(&savefile).unwrap(), other);
+        assert!(!savefile.with_extension("sl2.tmp").exists());
+
+        let backup = &backups.list()[0];
+        assert_eq!(std::fs::read(&backup.path).unwrap(), original);
+        backups.restore(&backup.path, &savefile).unwrap();
+        assert_eq!(std::fs::read(&savefile).unwrap(), original);
+        assert_eq!(backups.list().len(), 2);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
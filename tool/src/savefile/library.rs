@@ -0,0 +1,211 @@
+//! Metadata for the practice saves of an account.
+//!
+//! The practice saves are the `.sl2` files stored under the account folder,
+//! next to the game's own save. Their metadata is kept in a sidecar JSON
+//! index in the same folder, keyed by path relative to the folder, so the
+//! saves themselves are never touched.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use libdsr::savefile::Savefile;
+use serde::{Deserialize, Serialize};
+
+use super::SAVEFILE_NAME;
+
+pub(crate) const INDEX_NAME: &str = "practice_library.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct SaveMetadata {
+    pub(crate) route: String,
+    pub(crate) segment: String,
+    pub(crate) level: Option<i32>,
+    pub(crate) area: String,
+    pub(crate) tags: Vec<String>,
+    pub(crate) notes: String,
+}
+
+impl SaveMetadata {
+    // Whether a lowercase search term matches this save.
+    fn matches(&self, path: &str, term: &str) -> bool {
+        if let Some(tag) = term.strip_prefix('#') {
+            return self.tags.iter().any(|t| t.to_lowercase().starts_with(tag));
+        }
+        if let Some(level) = term.strip_prefix("sl") {
+            if let Ok(level) = level.parse::<i32>() {
+                return self.level == Some(level);
+            }
+        }
+
+        [path, &self.route, &self.segment, &self.area, &self.notes]
+            .into_iter()
+            .chain(self.tags.iter().map(String::as_str))
+            .any(|field| field.to_lowercase().contains(term))
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Library {
+    #[serde(skip)]
+    dir: PathBuf,
+    saves: BTreeMap<String, SaveMetadata>,
+    /// Levels read from the files of saves that were never edited, until the
+    /// next scan.
+    #[serde(skip)]
+    file_levels: Mutex<HashMap<String, Option<i32>>>,
+}
+
+impl Library {
+    /// Loads the index of the account folder `dir`. A missing index is an
+    /// empty library.
+    pub(crate) fn load(dir: &Path) -> Result<Library, String> {
+        let path = dir.join(INDEX_NAME);
+        let mut library = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("Couldn't parse {}: {e}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Library::default(),
+            Err(e) => return Err(format!("Couldn't read {}: {e}", path.display())),
+        };
+        library.dir = dir.to_path_buf();
+        Ok(library)
+    }
+
+    pub(crate) fn save(&self) -> Result<(), String> {
+        let path = self.dir.join(INDEX_NAME);
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, content)
+            .map_err(|e| format!("Couldn't write {}: {e}", path.display()))
+    }
+
+    pub(crate) fn path(&self, save: &str) -> PathBuf {
+        self.dir.join(save)
+    }
+
+    /// Metadata of a save. Saves that were never edited get their level
+    /// filled in from the file.
+    pub(crate) fn get(&self, save: &str) -> SaveMetadata {
+        self.saves
+            .get(save)
+            .cloned()
+            .unwrap_or_else(|| SaveMetadata { level: self.file_level(save), ..Default::default() })
+    }
+
+    fn file_level(&self, save: &str) -> Option<i32> {
+        let mut levels = self.file_levels.lock().unwrap();
+        *levels.entry(save.to_string()).or_insert_with(|| {
+            Savefile::open(&self.path(save))
+                .and_then(|s| s.slots())
+                .ok()
+                .and_then(|slots| slots.first().map(|s| s.level))
+        })
+    }
+
+    pub(crate) fn set(&mut self, save: &str, metadata: SaveMetadata) {
+        self.saves.insert(save.to_string(), metadata);
+    }
+
+    /// The practice saves on disk, as paths relative to the account folder.
+    pub(crate) fn scan(&self) -> Vec<String> {
+        let mut saves = Vec::new();
+        scan_dir(&self.dir, &self.dir, &mut saves);
+        saves.retain(|s| s != SAVEFILE_NAME);
+        saves.sort();
+        self.file_levels.lock().unwrap().clear();
+        saves
+    }
+
+    /// Filters `saves` by a search query. Every whitespace-separated term has
+    /// to match: `#tag` matches tags by prefix, `sl30` matches the level and
+    /// anything else is looked up in the path and the text fields.
+    pub(crate) fn search<'a>(&self, saves: &'a [String], query: &str) -> Vec<&'a str> {
+        let query = query.to_lowercase();
+        let terms: Vec<&str> = query.split_whitespace().collect();
+        if terms.is_empty() {
+            return saves.iter().map(String::as_str).collect();
+        }
+
+        saves
+            .iter()
+            .filter(|save| {
+                let metadata = self.get(save);
+                terms.iter().all(|term| metadata.matches(&save.to_lowercase(), term))
+            })
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+fn scan_dir(root: &Path, dir: &Path, saves: &mut Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.is_dir() {
+            scan_dir(root, &path, saves);
+        } else if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("sl2")) {
+            if let Ok(relative) = path.strip_prefix(root) {
+                let parts: Vec<_> = relative.iter().map(|p| p.to_string_lossy()).collect();
+                saves.push(parts.join("/"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::savefile::tests::write_save;
+
+    #[test]
+    fn test_library() {
+        let dir = std::env::temp_dir().join(format!("dsr-tool-library-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(dir.join("any%")).unwrap();
+        for save in [SAVEFILE_NAME, "any%/01 asylum.sl2", "any%/02 taurus.sl2", "misc.sl2"] {
+            std::fs::write(dir.join(save), b"").unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), b"").unwrap();
+
+        let mut library = Library::load(&dir).unwrap();
+        let saves = library.scan();
+        assert_eq!(saves, ["any%/01 asylum.sl2", "any%/02 taurus.sl2", "misc.sl2"]);
+
+        library.set(
+            "any%/02 taurus.sl2",
+            SaveMetadata {
+                route: "Any% No Master Key".into(),
+                segment: "Taurus Demon".into(),
+                level: Some(12),
+                area: "Undead Burg".into(),
+                tags: vec!["boss".into(), "rta".into()],
+                notes: "Plunging attack setup".into(),
+            },
+        );
+        library.save().unwrap();
+
+        let library = Library::load(&dir).unwrap();
+        assert_eq!(library.get("any%/02 taurus.sl2").level, Some(12));
+        assert_eq!(library.get("misc.sl2"), SaveMetadata::default());
+
+        assert_eq!(library.search(&saves, "").len(), 3);
+        assert_eq!(library.search(&saves, "ASYLUM"), ["any%/01 asylum.sl2"]);
+        assert_eq!(library.search(&saves, "burg plunging"), ["any%/02 taurus.sl2"]);
+        assert_eq!(library.search(&saves, "#bo sl12"), ["any%/02 taurus.sl2"]);
+        assert!(library.search(&saves, "#boss sl13").is_empty());
+        assert_eq!(library.search(&saves, "any%").len(), 2);
+
+        // Saves that were never edited are found by the level in their file,
+        // as read since the last scan.
+        assert!(library.search(&saves, "sl0").is_empty());
+        write_save(&dir.join("misc.sl2"), &["Solaire"]);
+        assert!(library.search(&saves, "sl0").is_empty());
+        library.scan();
+        assert_eq!(library.search(&saves, "sl0"), ["misc.sl2"]);
+        assert_eq!(library.get("misc.sl2").level, Some(0));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
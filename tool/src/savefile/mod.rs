@@ -0,0 +1,195 @@
+//! Discovery of the game's save files, and export/import of single
+//! character slots.
+//!
+//! Saves live in `Documents\NBGI\DARK SOULS REMASTERED\<steam id>\DRAKS0005.sl2`,
+//! with one folder per Steam account that played the game.
+
+pub(crate) mod backup;
+pub(crate) mod library;
+
+use std::path::{Path, PathBuf};
+
+use libdsr::savefile::{Savefile, SlotSummary};
+
+use backup::{write_savefile, Backups};
+
+pub(crate) const SAVEFILE_NAME: &str = "DRAKS0005.sl2";
+pub(crate) const SLOT_EXTENSION: &str = "dsrslot";
+
+/// A Steam account folder.
+#[derive(Debug, Clone)]
+pub(crate) struct Account {
+    pub(crate) id: String,
+    pub(crate) dir: PathBuf,
+}
+
+impl Account {
+    pub(crate) fn savefile_path(&self) -> PathBuf {
+        self.dir.join(SAVEFILE_NAME)
+    }
+
+    /// The occupied character slots of the account's save.
+    pub(crate) fn slots(&self) -> Result<Vec<SlotSummary>, String> {
+        let path = self.savefile_path();
+        if !path.is_file() {
+            return Err(format!("No save file in {}", self.dir.display()));
+        }
+        Savefile::open(&path)?.slots()
+    }
+}
+
+/// The folder containing the account folders.
+pub(crate) fn saves_root() -> Result<PathBuf, String> {
+    let profile = std::env::var("USERPROFILE").map_err(|e| format!("USERPROFILE: {e}"))?;
+    Ok([profile.as_str(), "Documents", "NBGI", "DARK SOULS REMASTERED"].iter().collect())
+}
+
+/// Lists the account folders under `root`, sorted by ID.
+pub(crate) fn find_accounts(root: &Path) -> Result<Vec<Account>, String> {
+    let re = regex::Regex::new(r"^[a-f0-9]+$").unwrap();
+
+    let mut accounts: Vec<Account> = std::fs::read_dir(root)
+        .map_err(|e| format!("Couldn't read {}: {e}", root.display()))?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| {
+            let id = e.file_name().to_string_lossy().into_owned();
+            re.is_match(&id).then(|| Account { id, dir: e.path() })
+        })
+        .collect();
+
+    if accounts.is_empty() {
+        return Err(format!("Couldn't find any save folder in {}", root.display()));
+    }
+
+    accounts.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(accounts)
+}
+
+/// Writes character slot `index` of `savefile` to `dir`, in a file named
+/// after the character.
+pub(crate) fn export_slot(savefile: &Path, index: usize, dir: &Path) -> Result<PathBuf, String> {
+    let save = Savefile::open(savefile)?;
+    let summary = save.slot(index)?.ok_or_else(|| format!("Slot {} is empty", index + 1))?;
+    let data = save.export_slot(index)?;
+
+    let name: String = summary
+        .name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == ' ' || c == '-' { c } else { '_' })
+        .collect();
+    let path = dir.join(format!("{} SL{}.{SLOT_EXTENSION}", name.trim(), summary.level));
+
+    std::fs::create_dir_all(dir).map_err(|e| format!("Couldn't create {}: {e}", dir.display()))?;
+    std::fs::write(&path, data).map_err(|e| format!("Couldn't write {}: {e}", path.display()))?;
+    Ok(path)
+}
+
+/// Replaces character slot `index` of `savefile` with an exported slot.
+pub(crate) fn import_slot(
+    savefile: &Path,
+    index: usize,
+    slot: &Path,
+    backups: &Backups,
+) -> Result<(), String> {
+    let exported =
+        std::fs::read(slot).map_err(|e| format!("Couldn't read {}: {e}", slot.display()))?;
+    let mut save = Savefile::open(savefile)?;
+    save.import_slot(index, &exported).map_err(|e| format!("{}: {e}", slot.display()))?;
+    write_savefile(savefile, save.as_bytes(), backups)
+}
+
+/// Exported slot files in `dir`, sorted by name.
+pub(crate) fn list_exports(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == SLOT_EXTENSION))
+        .collect();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use libdsr::savefile::bnd4::Bnd4;
+    use libdsr::savefile::{crypto, layout, slot_entry_name};
+
+    use super::backup::BackupSettings;
+    use super::*;
+
+    fn slot(name: &str) -> Vec<u8> {
+        let mut data = vec![0u8; 0x400];
+        let offset = layout::PLAYER_GAME_DATA + layout::NAME;
+        for (i, c) in name.encode_utf16().enumerate() {
+            data[offset + i * 2..][..2].copy_from_slice(&c.to_le_bytes());
+        }
+        data
+    }
+
+    pub(crate) fn write_save(path: &Path, names: &[&str]) {
+        let entries: Vec<_> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                (slot_entry_name(i), crypto::encrypt_entry(&slot(name), &[i as u8; 16]).unwrap())
+            })
+            .collect();
+        std::fs::write(path, Bnd4::build(&entries)).unwrap();
+    }
+
+    pub(crate) fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dsr-tool-{name}-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_find_accounts() {
+        let root = temp_dir("accounts");
+        std::fs::create_dir(root.join("deadbeef")).unwrap();
+        std::fs::create_dir(root.join("0110000100000001")).unwrap();
+        std::fs::create_dir(root.join("Backups")).unwrap();
+        std::fs::write(root.join("abcdef"), b"not a folder").unwrap();
+        std::fs::write(root.join("deadbeef").join(SAVEFILE_NAME), b"garbage").unwrap();
+
+        let accounts = find_accounts(&root).unwrap();
+        let ids: Vec<_> = accounts.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, ["0110000100000001", "deadbeef"]);
+
+        // Missing and corrupt saves are errors, not panics.
+        assert!(accounts[0].slots().unwrap_err().contains("No save file"));
+        assert!(accounts[1].slots().is_err());
+
+        assert!(find_accounts(&root.join("Backups")).is_err());
+        assert!(find_accounts(&root.join("missing")).is_err());
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_export_import() {
+        let dir = temp_dir("slots");
+        let savefile = dir.join(SAVEFILE_NAME);
+        write_save(&savefile, &["Solaire", "", "Oscar"]);
+
+        let exported = export_slot(&savefile, 0, &dir.join("exports")).unwrap();
+        assert_eq!(exported.file_name().unwrap(), "Solaire SL0.dsrslot");
+        assert!(export_slot(&savefile, 1, &dir.join("exports")).is_err());
+        assert_eq!(list_exports(&dir.join("exports")), std::slice::from_ref(&exported));
+
+        let backups = Backups::new(&dir, BackupSettings::default());
+        import_slot(&savefile, 1, &exported, &backups).unwrap();
+        assert_eq!(backups.list().len(), 1);
+        let account = Account { id: "test".into(), dir: dir.clone() };
+        let names: Vec<_> = account.slots().unwrap().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, ["Solaire", "Solaire", "Oscar"]);
+
+        assert!(import_slot(&savefile, 1, &dir.join("missing.dsrslot"), &backups).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,415 @@
+//! Rhai scripts run by the tool.
+//!
+//! Scripts read and write the game through a [`GameMemory`], so the host can
+//! be driven by a fake in tests. A script can define `fn on_frame()`, called
+//! every frame, and bind hotkeys to its functions with
+//! `bind("ctrl+e", "refill")` at its top level. In those functions, `this` is
+//! a map kept between calls.
+//!
+//! Errors are logged and only stop the script that caused them: a script
+//! whose `on_frame` fails isn't called every frame anymore, and every call is
+//! limited in operations so that a loop can't hang the game.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST, FLOAT, INT};
+
+/// Operations a script may run per call.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Game state exposed to scripts. Flags and values are looked up by name;
+/// unknown names are errors, and `None` or `false` mean that the game's
+/// memory can't be read or written right now, e.g. during a loading screen.
+pub(crate) trait GameMemory: Send + Sync {
+    fn flag(&self, name: &str) -> Result<Option<bool>, String>;
+    fn set_flag(&self, name: &str, state: bool) -> Result<bool, String>;
+    fn value(&self, name: &str) -> Result<Option<f64>, String>;
+    fn set_value(&self, name: &str, value: f64) -> Result<bool, String>;
+    fn position(&self) -> Option<([f32; 3], f32)>;
+    fn set_position(&self, pos: [f32; 3], angle: Option<f32>) -> bool;
+}
+
+/// State shared with the functions registered in the engine.
+#[derive(Default)]
+struct Shared {
+    /// The script being run, which log lines are prefixed with.
+    current: String,
+    logs: Vec<String>,
+    bindings: Vec<(String, String)>,
+}
+
+impl Shared {
+    fn log(&mut self, message: &str) {
+        self.logs.push(format!("[{}] {message}", self.current));
+    }
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+    scope: Scope<'static>,
+    /// `this` in the script's functions.
+    state: Dynamic,
+    on_frame: bool,
+    /// Hotkeys and the functions they call.
+    bindings: Vec<(String, String)>,
+}
+
+pub(crate) struct ScriptHost {
+    engine: Engine,
+    scripts: Vec<Script>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl ScriptHost {
+    pub(crate) fn new(memory: Arc<dyn GameMemory>) -> Self {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        let s = shared.clone();
+        engine.on_print(move |message| s.lock().unwrap().log(message));
+        let s = shared.clone();
+        engine.on_debug(move |message, _, _| s.lock().unwrap().log(message));
+        let s = shared.clone();
+        engine.register_fn("log", move |message: &str| s.lock().unwrap().log(message));
+        let s = shared.clone();
+        engine.register_fn("bind", move |key: &str, callback: &str| {
+            s.lock().unwrap().bindings.push((key.to_string(), callback.to_string()));
+        });
+
+        let m = memory.clone();
+        engine.register_fn("flag", move |name: &str| -> ScriptResult<Dynamic> {
+            Ok(m.flag(name)?.map_or(Dynamic::UNIT, Dynamic::from))
+        });
+        let m = memory.clone();
+        engine.register_fn("set_flag", move |name: &str, state: bool| -> ScriptResult<bool> {
+            Ok(m.set_flag(name, state)?)
+        });
+        let m = memory.clone();
+        engine.register_fn("get", move |name: &str| -> ScriptResult<Dynamic> {
+            Ok(m.value(name)?.map_or(Dynamic::UNIT, Dynamic::from))
+        });
+        let m = memory.clone();
+        engine.register_fn("set", move |name: &str, value: FLOAT| -> ScriptResult<bool> {
+            Ok(m.set_value(name, value)?)
+        });
+        let m = memory.clone();
+        engine.register_fn("set", move |name: &str, value: INT| -> ScriptResult<bool> {
+            Ok(m.set_value(name, value as f64)?)
+        });
+        let m = memory.clone();
+        engine.register_fn("igt", move || -> ScriptResult<Dynamic> {
+            Ok(m.value("igt")?.map_or(Dynamic::UNIT, |v| Dynamic::from(v as INT)))
+        });
+        let m = memory.clone();
+        engine.register_fn("position", move || match m.position() {
+            Some(([x, y, z], angle)) => {
+                let position: Array =
+                    [x, y, z, angle].into_iter().map(|v| Dynamic::from(v as FLOAT)).collect();
+                Dynamic::from_array(position)
+            }
+            None => Dynamic::UNIT,
+        });
+        let m = memory.clone();
+        engine.register_fn("set_position", move |x: FLOAT, y: FLOAT, z: FLOAT| {
+            m.set_position([x as f32, y as f32, z as f32], None)
+        });
+        let m = memory;
+        engine.register_fn("set_position", move |x: FLOAT, y: FLOAT, z: FLOAT, angle: FLOAT| {
+            m.set_position([x as f32, y as f32, z as f32], Some(angle as f32))
+        });
+
+        ScriptHost { engine, scripts: Vec::new(), shared }
+    }
+
+    /// Loads the `.rhai` files of a directory, in name order.
+    pub(crate) fn load_dir(&mut self, dir: &Path) {
+        let mut paths: Vec<_> = match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+                .collect(),
+            Err(e) => {
+                self.shared.lock().unwrap().logs.push(format!("Couldn't read scripts: {e}"));
+                return;
+            }
+        };
+        paths.sort();
+
+        for path in paths {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            match std::fs::read_to_string(&path) {
+                Ok(source) => {
+                    self.load(&name, &source);
+                }
+                Err(e) => {
+                    self.shared.lock().unwrap().logs.push(format!("Couldn't read {name}: {e}"))
+                }
+            }
+        }
+    }
+
+    /// Compiles a script and runs its top level. Returns whether it loaded;
+    /// errors are logged.
+    pub(crate) fn load(&mut self, name: &str, source: &str) -> bool {
+        self.shared.lock().unwrap().current = name.to_string();
+
+        let engine = &self.engine;
+        let result = isolate(|| {
+            let ast = engine.compile(source).map_err(|e| e.to_string())?;
+            let mut scope = Scope::new();
+            engine.run_ast_with_scope(&mut scope, &ast).map_err(|e| e.to_string())?;
+            Ok((ast, scope))
+        });
+
+        let mut shared = self.shared.lock().unwrap();
+        let bindings = std::mem::take(&mut shared.bindings);
+        let (ast, scope) = match result {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                shared.log(&format!("Couldn't load: {e}"));
+                return false;
+            }
+        };
+
+        let has_fn =
+            |name: &str| ast.iter_functions().any(|f| f.name == name && f.params.is_empty());
+        let bindings = bindings
+            .into_iter()
+            .filter(|(key, callback)| {
+                let found = has_fn(callback);
+                if !found {
+                    shared.log(&format!("No function {callback}() to bind to {key}"));
+                }
+                found
+            })
+            .collect();
+
+        self.scripts.push(Script {
+            name: name.to_string(),
+            on_frame: has_fn("on_frame"),
+            state: Dynamic::from_map(Map::new()),
+            ast,
+            scope,
+            bindings,
+        });
+        true
+    }
+
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.scripts.iter().map(|s| s.name.as_str())
+    }
+
+    /// The hotkeys bound by every script, as the index of the script, the
+    /// key and the function to call.
+    pub(crate) fn bindings(&self) -> impl Iterator<Item = (usize, &str, &str)> {
+        self.scripts.iter().enumerate().flat_map(|(i, script)| {
+            script.bindings.iter().map(move |(key, callback)| (i, key.as_str(), callback.as_str()))
+        })
+    }
+
+    pub(crate) fn on_frame(&mut self) {
+        for index in 0..self.scripts.len() {
+            if !self.scripts[index].on_frame {
+                continue;
+            }
+            if let Err(e) = self.call(index, "on_frame") {
+                self.scripts[index].on_frame = false;
+                self.shared.lock().unwrap().log(&format!("on_frame stopped: {e}"));
+            }
+        }
+    }
+
+    pub(crate) fn on_hotkey(&mut self, script: usize, callback: &str) {
+        if let Err(e) = self.call(script, callback) {
+            self.shared.lock().unwrap().log(&format!("{callback} failed: {e}"));
+        }
+    }
+
+    pub(crate) fn take_logs(&self) -> Vec<String> {
+        std::mem::take(&mut self.shared.lock().unwrap().logs)
+    }
+
+    fn call(&mut self, index: usize, callback: &str) -> Result<(), String> {
+        let Some(Script { name, ast, scope, state, .. }) = self.scripts.get_mut(index) else {
+            return Ok(());
+        };
+        self.shared.lock().unwrap().current = name.clone();
+
+        let engine = &self.engine;
+        let result = isolate(|| {
+            let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(state);
+            engine
+                .call_fn_with_options::<Dynamic>(options, scope, ast, callback, ())
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        });
+
+        let mut shared = self.shared.lock().unwrap();
+        if !std::mem::take(&mut shared.bindings).is_empty() {
+            shared.log("bind() only works at the top level of a script");
+        }
+        result
+    }
+}
+
+/// Turns a panic into an error, so that it doesn't unwind into the game.
+fn isolate<T>(f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| Err("Script panicked".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Game memory backed by maps. Missing flags and values are unreadable,
+    /// like during a loading screen.
+    #[derive(Default)]
+    struct FakeMemory {
+        flags: Mutex<HashMap<&'static str, bool>>,
+        values: Mutex<HashMap<&'static str, f64>>,
+        position: Mutex<Option<([f32; 3], f32)>>,
+    }
+
+    fn known(name: &str, names: &[&'static str]) -> Result<&'static str, String> {
+        names.iter().copied().find(|&n| n == name).ok_or_else(|| format!("Unknown name {name}"))
+    }
+
+    impl GameMemory for FakeMemory {
+        fn flag(&self, name: &str) -> Result<Option<bool>, String> {
+            let name = known(name, &["no_death", "gravity"])?;
+            Ok(self.flags.lock().unwrap().get(name).copied())
+        }
+
+        fn set_flag(&self, name: &str, state: bool) -> Result<bool, String> {
+            let name = known(name, &["no_death", "gravity"])?;
+            Ok(self.flags.lock().unwrap().get_mut(name).map(|flag| *flag = state).is_some())
+        }
+
+        fn value(&self, name: &str) -> Result<Option<f64>, String> {
+            let name = known(name, &["souls", "speed", "igt"])?;
+            Ok(self.values.lock().unwrap().get(name).copied())
+        }
+
+        fn set_value(&self, name: &str, value: f64) -> Result<bool, String> {
+            let name = known(name, &["souls", "speed", "igt"])?;
+            Ok(self.values.lock().unwrap().get_mut(name).map(|v| *v = value).is_some())
+        }
+
+        fn position(&self) -> Option<([f32; 3], f32)> {
+            *self.position.lock().unwrap()
+        }
+
+        fn set_position(&self, pos: [f32; 3], angle: Option<f32>) -> bool {
+            let mut position = self.position.lock().unwrap();
+            let Some((_, current)) = *position else {
+                return false;
+            };
+            *position = Some((pos, angle.unwrap_or(current)));
+            true
+        }
+    }
+
+    fn host() -> (ScriptHost, Arc<FakeMemory>) {
+        let memory = Arc::new(FakeMemory::default());
+        memory.flags.lock().unwrap().insert("no_death", false);
+        memory.values.lock().unwrap().extend([("souls", 500.), ("speed", 1.)]);
+        memory.position.lock().unwrap().replace(([1., 2., 3.], 0.));
+        (ScriptHost::new(memory.clone()), memory)
+    }
+
+    #[test]
+    fn test_callbacks() {
+        let (mut host, memory) = host();
+        assert!(host.load(
+            "souls.rhai",
+            r#"
+            bind("ctrl+e", "refill");
+
+            fn on_frame() {
+                let souls = get("souls");
+                if this.last != () && souls < this.last {
+                    log(`Souls dropped to ${souls}`);
+                }
+                this.last = souls;
+                if igt() == () {
+                    print("No IGT");
+                }
+            }
+
+            fn refill() {
+                set("souls", 1000);
+                set_flag("no_death", !flag("no_death"));
+                let p = position();
+                set_position(p[0] + 1.0, p[1], p[2], 1.5);
+            }
+            "#
+        ));
+        assert_eq!(host.names().collect::<Vec<_>>(), ["souls.rhai"]);
+        assert_eq!(host.bindings().collect::<Vec<_>>(), [(0, "ctrl+e", "refill")]);
+
+        host.on_frame();
+        memory.values.lock().unwrap().insert("souls", 400.);
+        memory.values.lock().unwrap().insert("igt", 1000.);
+        host.on_frame();
+        assert_eq!(
+            host.take_logs(),
+            ["[souls.rhai] No IGT", "[souls.rhai] Souls dropped to 400.0"]
+        );
+
+        host.on_hotkey(0, "refill");
+        assert!(host.take_logs().is_empty());
+        assert_eq!(memory.values.lock().unwrap()["souls"], 1000.);
+        assert!(memory.flags.lock().unwrap()["no_death"]);
+        assert_eq!(*memory.position.lock().unwrap(), Some(([2., 2., 3.], 1.5)));
+    }
+
+    #[test]
+    fn test_errors() {
+        let (mut host, memory) = host();
+        assert!(!host.load("broken.rhai", "fn on_frame( {"));
+        assert!(!host.load("fails.rhai", r#"throw "at load";"#));
+        assert!(host.load("unknown.rhai", r#"fn on_frame() { flag("nope") }"#));
+        assert!(host.load("loop.rhai", "fn on_frame() { loop {} }"));
+        assert!(
+            host.load("unbound.rhai", r#"bind("f1", "missing"); fn late() { bind("f2", "late") }"#)
+        );
+        assert!(host.load("speed.rhai", r#"fn on_frame() { set("speed", get("speed") + 1.0) }"#));
+        assert_eq!(
+            host.names().collect::<Vec<_>>(),
+            ["unknown.rhai", "loop.rhai", "unbound.rhai", "speed.rhai"]
+        );
+        assert_eq!(host.bindings().count(), 0);
+
+        let logs = host.take_logs();
+        assert_eq!(logs.len(), 3);
+        assert!(logs[0].starts_with("[broken.rhai] Couldn't load: "));
+        assert!(logs[1].starts_with("[fails.rhai] Couldn't load: "));
+        assert_eq!(logs[2], "[unbound.rhai] No function missing() to bind to f1");
+
+        // Failing scripts stop running every frame, the others go on.
+        host.on_frame();
+        let logs = host.take_logs();
+        assert_eq!(logs.len(), 2);
+        assert!(logs[0].starts_with("[unknown.rhai] on_frame stopped: "));
+        assert!(logs[1].starts_with("[loop.rhai] on_frame stopped: "));
+        host.on_frame();
+        assert!(host.take_logs().is_empty());
+        assert_eq!(memory.values.lock().unwrap()["speed"], 3.);
+
+        host.on_hotkey(2, "late");
+        assert_eq!(
+            host.take_logs(),
+            ["[unbound.rhai] bind() only works at the top level of a script"]
+        );
+        host.on_hotkey(2, "missing");
+        assert_eq!(host.take_logs().len(), 1);
+    }
+}
@@ -0,0 +1,321 @@
+//! Import and export of LiveSplit `.lss` split files.
+//!
+//! Only what the timer tracks is read and written: segment names, the
+//! personal best split times and the best segment times. Game time is
+//! preferred over real time when both are present.
+
+use std::fmt::Write;
+
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use super::{Records, SegmentRecord};
+
+const GAME_NAME: &str = "Dark Souls: Remastered";
+
+/// Parse an `.lss` file into per-segment records.
+pub(crate) fn import(xml: &str) -> Result<Records, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut segments: Vec<LssSegment> = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+    let mut in_personal_best = false;
+
+    loop {
+        let event = reader.read_event().map_err(|e| {
+            format!(
+                "Invalid LiveSplit file at {}: {e}",
+                reader.buffer_position()
+            )
+        })?;
+
+        match event {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                match name.as_str() {
+                    "Segment" if at(&path, &["Run", "Segments"]) => {
+                        segments.push(LssSegment::default())
+                    }
+                    "SplitTime" => {
+                        in_personal_best = e
+                            .try_get_attribute("name")
+                            .ok()
+                            .flatten()
+                            .is_some_and(|a| a.value.as_ref() == b"Personal Best")
+                    }
+                    _ => {}
+                }
+                path.push(name);
+            }
+            Event::End(_) => {
+                path.pop();
+            }
+            Event::Text(t) => {
+                let text = t
+                    .unescape()
+                    .map_err(|e| format!("Invalid LiveSplit file: {e}"))?;
+                let Some(segment) = segments.last_mut() else {
+                    continue;
+                };
+
+                if at(&path, &["Segment", "Name"]) {
+                    segment.name = text.into_owned();
+                } else if at(&path, &["SplitTime", "GameTime"]) && in_personal_best {
+                    segment.personal_best.game = parse_time(&text);
+                } else if at(&path, &["SplitTime", "RealTime"]) && in_personal_best {
+                    segment.personal_best.real = parse_time(&text);
+                } else if at(&path, &["BestSegmentTime", "GameTime"]) {
+                    segment.best_segment.game = parse_time(&text);
+                } else if at(&path, &["BestSegmentTime", "RealTime"]) {
+                    segment.best_segment.real = parse_time(&text);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if segments.is_empty() {
+        return Err("LiveSplit file has no segments".to_string());
+    }
+
+    Ok(Records {
+        segments: segments
+            .into_iter()
+            .map(|s| SegmentRecord {
+                name: s.name,
+                personal_best: s.personal_best.get(),
+                best_segment: s.best_segment.get(),
+            })
+            .collect(),
+    })
+}
+
+/// Write records as an `.lss` file, with times as game time.
+pub(crate) fn export(records: &Records) -> String {
+    let mut out = String::new();
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#).ok();
+    writeln!(out, r#"<Run version="1.7.0">"#).ok();
+    writeln!(out, "  <GameIcon />").ok();
+    writeln!(out, "  <GameName>{}</GameName>", escape(GAME_NAME)).ok();
+    writeln!(out, "  <CategoryName />").ok();
+    writeln!(out, "  <Offset>00:00:00</Offset>").ok();
+    writeln!(out, "  <AttemptCount>0</AttemptCount>").ok();
+    writeln!(out, "  <AttemptHistory />").ok();
+    writeln!(out, "  <Segments>").ok();
+
+    for segment in &records.segments {
+        writeln!(out, "    <Segment>").ok();
+        writeln!(out, "      <Name>{}</Name>", escape(&segment.name)).ok();
+        writeln!(out, "      <Icon />").ok();
+        writeln!(out, "      <SplitTimes>").ok();
+        match segment.personal_best {
+            Some(pb) => {
+                writeln!(out, r#"        <SplitTime name="Personal Best">"#).ok();
+                writeln!(out, "          <GameTime>{}</GameTime>", format_time(pb)).ok();
+                writeln!(out, "        </SplitTime>").ok();
+            }
+            None => {
+                writeln!(out, r#"        <SplitTime name="Personal Best" />"#).ok();
+            }
+        }
+        writeln!(out, "      </SplitTimes>").ok();
+        match segment.best_segment {
+            Some(best) => {
+                writeln!(out, "      <BestSegmentTime>").ok();
+                writeln!(out, "        <GameTime>{}</GameTime>", format_time(best)).ok();
+                writeln!(out, "      </BestSegmentTime>").ok();
+            }
+            None => {
+                writeln!(out, "      <BestSegmentTime />").ok();
+            }
+        }
+        writeln!(out, "      <SegmentHistory />").ok();
+        writeln!(out, "    </Segment>").ok();
+    }
+
+    writeln!(out, "  </Segments>").ok();
+    writeln!(out, "  <AutoSplitterSettings />").ok();
+    writeln!(out, "</Run>").ok();
+
+    out
+}
+
+#[derive(Default)]
+struct LssSegment {
+    name: String,
+    personal_best: LssTime,
+    best_segment: LssTime,
+}
+
+#[derive(Default)]
+struct LssTime {
+    real: Option<u32>,
+    game: Option<u32>,
+}
+
+impl LssTime {
+    fn get(&self) -> Option<u32> {
+        self.game.or(self.real)
+    }
+}
+
+fn at(path: &[String], suffix: &[&str]) -> bool {
+    path.len() >= suffix.len()
+        && path[path.len() - suffix.len()..]
+            .iter()
+            .zip(suffix)
+            .all(|(a, b)| a == b)
+}
+
+// LiveSplit times look like `[d.]hh:mm:ss[.fffffff]`.
+fn parse_time(s: &str) -> Option<u32> {
+    let mut parts = s.trim().split(':');
+    let (days_hours, minutes, seconds) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        return None;
+    }
+
+    let (days, hours): (u64, u64) = match days_hours.split_once('.') {
+        Some((days, hours)) => (days.parse().ok()?, hours.parse().ok()?),
+        None => (0, days_hours.parse().ok()?),
+    };
+    let minutes: u64 = minutes.parse().ok()?;
+    let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    let seconds: u64 = seconds.parse().ok()?;
+    let millis: u64 = match fraction.len() {
+        0 => 0,
+        n => format!("{:0<3}", &fraction[..n.min(3)]).parse().ok()?,
+    };
+
+    let total = (((days * 24 + hours) * 60 + minutes) * 60 + seconds) * 1000 + millis;
+    u32::try_from(total).ok()
+}
+
+// Same format as LiveSplit writes, with the days only when there are any.
+fn format_time(ms: u32) -> String {
+    let days = ms / 86400000;
+    let hours = ms / 3600000 % 24;
+    let minutes = ms / 60000 % 60;
+    let seconds = ms / 1000 % 60;
+    let millis = ms % 1000;
+    match days {
+        0 => format!("{hours:02}:{minutes:02}:{seconds:02}.{millis:03}0000"),
+        d => format!("{d}.{hours:02}:{minutes:02}:{seconds:02}.{millis:03}0000"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LSS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Run version="1.7.0">
+  <GameName>Dark Souls: Remastered</GameName>
+  <CategoryName>All Bosses</CategoryName>
+  <Segments>
+    <Segment>
+      <Name>Asylum &amp; Demon</Name>
+      <SplitTimes>
+        <SplitTime name="Personal Best">
+          <RealTime>00:02:10.1234567</RealTime>
+          <GameTime>00:02:01.5000000</GameTime>
+        </SplitTime>
+        <SplitTime name="Sum of Best">
+          <GameTime>00:01:00</GameTime>
+        </SplitTime>
+      </SplitTimes>
+      <BestSegmentTime>
+        <RealTime>00:01:59.9</RealTime>
+      </BestSegmentTime>
+      <SegmentHistory>
+        <Time id="1">
+          <GameTime>00:09:00</GameTime>
+        </Time>
+      </SegmentHistory>
+    </Segment>
+    <Segment>
+      <Name>Taurus</Name>
+      <SplitTimes>
+        <SplitTime name="Personal Best" />
+      </SplitTimes>
+      <BestSegmentTime />
+    </Segment>
+  </Segments>
+</Run>"#;
+
+    #[test]
+    fn test_import() {
+        let records = import(LSS).unwrap();
+        assert_eq!(
+            records.segments,
+            vec![
+                SegmentRecord {
+                    name: "Asylum & Demon".into(),
+                    personal_best: Some(121500),
+                    best_segment: Some(119900),
+                },
+                SegmentRecord {
+                    name: "Taurus".into(),
+                    personal_best: None,
+                    best_segment: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let records = Records {
+            segments: vec![
+                SegmentRecord {
+                    name: "Bell <1>".into(),
+                    personal_best: Some(1000 * 3600 * 25 + 1),
+                    best_segment: Some(59999),
+                },
+                SegmentRecord {
+                    name: "Gargoyles".into(),
+                    personal_best: None,
+                    best_segment: Some(1),
+                },
+            ],
+        };
+
+        assert_eq!(import(&export(&records)).unwrap(), records);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("00:00:01"), Some(1000));
+        assert_eq!(parse_time("01:02:03.45"), Some(3723450));
+        assert_eq!(parse_time("1.00:00:00.0010000"), Some(86400001));
+        assert_eq!(parse_time("garbage"), None);
+        assert!(import("<Run><Segments /></Run>").is_err());
+    }
+
+    #[test]
+    fn test_format_time() {
+        // As written by LiveSplit.
+        for time in [
+            "00:00:00.0010000",
+            "00:02:01.5000000",
+            "23:59:59.9990000",
+            "1.00:00:00.0010000",
+            "2.03:04:05.6780000",
+        ] {
+            assert_eq!(format_time(parse_time(time).unwrap()), time);
+        }
+
+        let records = Records {
+            segments: vec![SegmentRecord {
+                name: "Long".into(),
+                personal_best: Some(1000 * 3600 * 25 + 1),
+                best_segment: None,
+            }],
+        };
+        assert!(export(&records).contains("<GameTime>1.01:00:00.0010000</GameTime>"));
+    }
+}
@@ -0,0 +1,748 @@
+//! Speedrun split timer driven by the game's IGT.
+//!
+//! The timer itself is independent from the game: it reads IGT through an
+//! [`IgtSource`] and split triggers through an [`EventSource`], so it can be
+//! driven by fakes in tests.
+
+pub(crate) mod lss;
+pub(crate) mod realtime;
+
+use std::fmt::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Provides the current in-game time, in milliseconds.
+pub(crate) trait IgtSource {
+    fn igt(&self) -> Option<u32>;
+}
+
+/// Provides the game state that split triggers watch.
+pub(crate) trait EventSource {
+    fn event_flag(&self, id: u32) -> Option<bool>;
+    fn map_id(&self) -> Option<u32>;
+    fn last_bonfire(&self) -> Option<u32>;
+}
+
+/// What makes the timer split automatically at the end of a segment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SplitTrigger {
+    /// Only the split hotkey ends the segment.
+    Manual,
+    /// An event flag gets set, e.g. a boss kill.
+    EventFlag(u32),
+    /// The player enters the given world and area, e.g. `m10_01`.
+    Area { world: u8, area: u8 },
+    /// The player rests at (or lights) the given bonfire.
+    Bonfire(u32),
+}
+
+impl SplitTrigger {
+    // Reduce the watched game state to a single value, so that triggers can
+    // be edge-detected uniformly.
+    fn observe(&self, events: &impl EventSource) -> Option<u32> {
+        match self {
+            SplitTrigger::Manual => None,
+            SplitTrigger::EventFlag(id) => events.event_flag(*id).map(u32::from),
+            SplitTrigger::Area { .. } => events.map_id().map(|id| id >> 16),
+            SplitTrigger::Bonfire(_) => events.last_bonfire(),
+        }
+    }
+
+    fn target(&self) -> Option<u32> {
+        match self {
+            SplitTrigger::Manual => None,
+            SplitTrigger::EventFlag(_) => Some(1),
+            SplitTrigger::Area { world, area } => Some((*world as u32) << 8 | *area as u32),
+            SplitTrigger::Bonfire(id) => Some(*id),
+        }
+    }
+}
+
+/// A segment as written in the configuration file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "SegmentConfig")]
+pub(crate) struct SegmentSpec {
+    pub(crate) name: String,
+    pub(crate) trigger: SplitTrigger,
+}
+
+#[derive(Debug, Deserialize)]
+struct SegmentConfig {
+    name: String,
+    event_flag: Option<u32>,
+    area: Option<String>,
+    bonfire: Option<u32>,
+}
+
+impl TryFrom<SegmentConfig> for SegmentSpec {
+    type Error = String;
+
+    fn try_from(value: SegmentConfig) -> Result<Self, Self::Error> {
+        let trigger = match (value.event_flag, value.area, value.bonfire) {
+            (None, None, None) => SplitTrigger::Manual,
+            (Some(flag), None, None) => SplitTrigger::EventFlag(flag),
+            (None, Some(area), None) => parse_area(&area)?,
+            (None, None, Some(bonfire)) => SplitTrigger::Bonfire(bonfire),
+            _ => {
+                return Err(format!(
+                    "Segment \"{}\": only one of event_flag, area and bonfire can be set",
+                    value.name
+                ))
+            }
+        };
+
+        Ok(SegmentSpec {
+            name: value.name,
+            trigger,
+        })
+    }
+}
+
+// Parses an area in the game's map naming scheme, e.g. `m10_01`.
+fn parse_area(area: &str) -> Result<SplitTrigger, String> {
+    area.strip_prefix('m')
+        .and_then(|a| a.split_once('_'))
+        .and_then(|(world, area)| {
+            Some(SplitTrigger::Area {
+                world: world.parse().ok()?,
+                area: area.parse().ok()?,
+            })
+        })
+        .ok_or_else(|| format!("\"{area}\" is not a valid area, expected e.g. \"m10_01\""))
+}
+
+/// Best times for one segment, persisted between sessions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SegmentRecord {
+    pub(crate) name: String,
+    /// Split time of this segment in the personal best run.
+    pub(crate) personal_best: Option<u32>,
+    /// Fastest time ever recorded for this segment alone (the "gold").
+    pub(crate) best_segment: Option<u32>,
+}
+
+/// The personal best file, stored next to the DLL.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Records {
+    pub(crate) segments: Vec<SegmentRecord>,
+}
+
+impl Records {
+    pub(crate) fn load(path: &Path) -> Result<Records, String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("Couldn't parse {}: {e}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Records::default()),
+            Err(e) => Err(format!("Couldn't read {}: {e}", path.display())),
+        }
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, content).map_err(|e| format!("Couldn't write {}: {e}", path.display()))
+    }
+
+    fn get(&self, name: &str) -> Option<&SegmentRecord> {
+        self.segments.iter().find(|s| s.name == name)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Segment {
+    pub(crate) name: String,
+    pub(crate) trigger: SplitTrigger,
+    pub(crate) personal_best: Option<u32>,
+    pub(crate) best_segment: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TimerState {
+    NotStarted,
+    /// Running since the given IGT.
+    Running(u32),
+    /// Finished a run started at the given IGT.
+    Finished(u32),
+}
+
+/// The result of an action on the timer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SplitOutcome {
+    Started,
+    Split {
+        index: usize,
+        delta: Option<i64>,
+        gold: bool,
+    },
+    Finished {
+        delta: Option<i64>,
+        personal_best: bool,
+    },
+}
+
+#[derive(Debug)]
+pub(crate) struct SplitTimer {
+    segments: Vec<Segment>,
+    state: TimerState,
+    current: usize,
+    /// Run-relative split times of the current attempt.
+    splits: Vec<Option<u32>>,
+    /// Last observed value for the current segment's trigger.
+    watched: Option<u32>,
+    /// Personal best splits replaced by the finished attempt, put back if the
+    /// last split is undone.
+    replaced_best: Option<Vec<Option<u32>>>,
+    dirty: bool,
+}
+
+impl SplitTimer {
+    pub(crate) fn new(specs: Vec<SegmentSpec>, records: &Records) -> Self {
+        let segments: Vec<_> = specs
+            .into_iter()
+            .map(|spec| {
+                let record = records.get(&spec.name);
+                Segment {
+                    personal_best: record.and_then(|r| r.personal_best),
+                    best_segment: record.and_then(|r| r.best_segment),
+                    name: spec.name,
+                    trigger: spec.trigger,
+                }
+            })
+            .collect();
+        let splits = vec![None; segments.len()];
+
+        SplitTimer {
+            segments,
+            state: TimerState::NotStarted,
+            current: 0,
+            splits,
+            watched: None,
+            replaced_best: None,
+            dirty: false,
+        }
+    }
+
+    pub(crate) fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub(crate) fn state(&self) -> TimerState {
+        self.state
+    }
+
+    /// Index of the segment being run.
+    pub(crate) fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Run-relative split time of segment `index` in the current attempt.
+    pub(crate) fn split_time(&self, index: usize) -> Option<u32> {
+        self.splits.get(index).copied().flatten()
+    }
+
+    /// Current run time.
+    pub(crate) fn time(&self, igt: u32) -> Option<u32> {
+        match self.state {
+            TimerState::NotStarted => None,
+            TimerState::Running(start) => Some(igt.saturating_sub(start)),
+            TimerState::Finished(_) => self.splits.last().copied().flatten(),
+        }
+    }
+
+    /// Difference between the current attempt and the personal best at the
+    /// end of segment `index`, in milliseconds.
+    pub(crate) fn delta(&self, index: usize) -> Option<i64> {
+        let time = self.split_time(index)?;
+        let pb = self.segments.get(index)?.personal_best?;
+        Some(time as i64 - pb as i64)
+    }
+
+    /// Duration of segment `index` in the current attempt. Undefined if the
+    /// previous segment was skipped.
+    pub(crate) fn segment_time(&self, index: usize) -> Option<u32> {
+        let end = self.split_time(index)?;
+        match index {
+            0 => Some(end),
+            i => self
+                .split_time(i - 1)
+                .map(|start| end.saturating_sub(start)),
+        }
+    }
+
+    /// Whether segment `index` of the current attempt set a new best segment.
+    pub(crate) fn is_gold(&self, index: usize) -> bool {
+        match (self.segment_time(index), self.segments.get(index)) {
+            (Some(time), Some(segment)) => segment.best_segment == Some(time),
+            _ => false,
+        }
+    }
+
+    /// Start the timer, or end the current segment.
+    pub(crate) fn split(&mut self, igt: u32) -> Option<SplitOutcome> {
+        match self.state {
+            TimerState::NotStarted if !self.segments.is_empty() => {
+                self.state = TimerState::Running(igt);
+                self.current = 0;
+                self.watched = None;
+                Some(SplitOutcome::Started)
+            }
+            TimerState::Running(start) => {
+                let index = self.current;
+                self.splits[index] = Some(igt.saturating_sub(start));
+
+                let gold = match (
+                    self.segment_time(index),
+                    &mut self.segments[index].best_segment,
+                ) {
+                    (Some(time), best @ None) => {
+                        *best = Some(time);
+                        true
+                    }
+                    (Some(time), Some(best)) if time < *best => {
+                        *best = time;
+                        true
+                    }
+                    _ => false,
+                };
+                self.dirty |= gold;
+
+                let delta = self.delta(index);
+                self.current += 1;
+                self.watched = None;
+
+                if self.current < self.segments.len() {
+                    return Some(SplitOutcome::Split { index, delta, gold });
+                }
+
+                self.state = TimerState::Finished(start);
+                let personal_best = delta.map(|d| d < 0).unwrap_or(true);
+                if personal_best {
+                    self.replaced_best =
+                        Some(self.segments.iter().map(|s| s.personal_best).collect());
+                    // Skipped segments keep their previous split.
+                    for (segment, split) in self.segments.iter_mut().zip(&self.splits) {
+                        if split.is_some() {
+                            segment.personal_best = *split;
+                        }
+                    }
+                    self.dirty = true;
+                }
+
+                Some(SplitOutcome::Finished {
+                    delta,
+                    personal_best,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Move to the next segment without recording a time. Returns whether
+    /// there was a segment to skip.
+    pub(crate) fn skip(&mut self) -> bool {
+        match self.state {
+            TimerState::Running(_) if self.current + 1 < self.segments.len() => {
+                self.splits[self.current] = None;
+                self.current += 1;
+                self.watched = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Go back to the previous segment. Best segments set by the undone split
+    /// are kept, but a personal best set by it is reverted. Returns whether
+    /// there was a split to undo.
+    pub(crate) fn undo(&mut self) -> bool {
+        let start = match self.state {
+            TimerState::Running(start) if self.current > 0 => start,
+            TimerState::Finished(start) => {
+                if let Some(replaced) = self.replaced_best.take() {
+                    for (segment, pb) in self.segments.iter_mut().zip(replaced) {
+                        segment.personal_best = pb;
+                    }
+                    self.dirty = true;
+                }
+                start
+            }
+            _ => return false,
+        };
+
+        self.current -= 1;
+        self.splits[self.current] = None;
+        self.state = TimerState::Running(start);
+        self.watched = None;
+        true
+    }
+
+    /// Returns whether the timer was started.
+    pub(crate) fn reset(&mut self) -> bool {
+        if self.state == TimerState::NotStarted {
+            return false;
+        }
+
+        self.state = TimerState::NotStarted;
+        self.current = 0;
+        self.splits.iter_mut().for_each(|s| *s = None);
+        self.watched = None;
+        self.replaced_best = None;
+        true
+    }
+
+    /// Poll the current segment's trigger, splitting if it fired.
+    pub(crate) fn update(
+        &mut self,
+        igt: &impl IgtSource,
+        events: &impl EventSource,
+    ) -> Option<SplitOutcome> {
+        let TimerState::Running(_) = self.state else {
+            return None;
+        };
+
+        let trigger = self.segments[self.current].trigger;
+        let observed = trigger.observe(events)?;
+        let previous = self.watched.replace(observed);
+
+        // Only fire on a change towards the target, so that state which is
+        // already set when the segment starts doesn't split immediately.
+        if previous.is_some_and(|p| p != observed) && Some(observed) == trigger.target() {
+            self.split(igt.igt()?)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn records(&self) -> Records {
+        Records {
+            segments: self
+                .segments
+                .iter()
+                .map(|s| SegmentRecord {
+                    name: s.name.clone(),
+                    personal_best: s.personal_best,
+                    best_segment: s.best_segment,
+                })
+                .collect(),
+        }
+    }
+
+    /// Replace the best times with `records`, matching segments by name.
+    pub(crate) fn import(&mut self, records: &Records) {
+        for segment in &mut self.segments {
+            if let Some(record) = records.get(&segment.name) {
+                segment.personal_best = record.personal_best;
+                segment.best_segment = record.best_segment;
+            }
+        }
+        self.replaced_best = None;
+        self.dirty = true;
+    }
+
+    /// Whether best times changed since the last call.
+    pub(crate) fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+}
+
+/// Writes `ms` as `H:MM:SS.cc`, omitting the hours when zero.
+pub(crate) fn write_time(out: &mut String, ms: u32) {
+    let centis = ms % 1000 / 10;
+    let seconds = ms / 1000 % 60;
+    let minutes = ms / 60000 % 60;
+    let hours = ms / 3600000;
+    match hours {
+        0 => write!(out, "{minutes}:{seconds:02}.{centis:02}"),
+        h => write!(out, "{h}:{minutes:02}:{seconds:02}.{centis:02}"),
+    }
+    .ok();
+}
+
+/// Writes a signed difference as `+M:SS.cc` / `-M:SS.cc`.
+pub(crate) fn write_delta(out: &mut String, delta: i64) {
+    out.push(if delta < 0 { '-' } else { '+' });
+    write_time(out, delta.unsigned_abs().min(u32::MAX as u64) as u32);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeGame {
+        igt: Cell<u32>,
+        flag: Cell<bool>,
+        map_id: Cell<u32>,
+        bonfire: Cell<u32>,
+    }
+
+    impl IgtSource for FakeGame {
+        fn igt(&self) -> Option<u32> {
+            Some(self.igt.get())
+        }
+    }
+
+    impl EventSource for FakeGame {
+        fn event_flag(&self, id: u32) -> Option<bool> {
+            (id == 16).then(|| self.flag.get())
+        }
+
+        fn map_id(&self) -> Option<u32> {
+            Some(self.map_id.get())
+        }
+
+        fn last_bonfire(&self) -> Option<u32> {
+            Some(self.bonfire.get())
+        }
+    }
+
+    fn spec(name: &str, trigger: SplitTrigger) -> SegmentSpec {
+        SegmentSpec {
+            name: name.to_string(),
+            trigger,
+        }
+    }
+
+    fn specs() -> Vec<SegmentSpec> {
+        vec![
+            spec("Asylum Demon", SplitTrigger::EventFlag(16)),
+            spec("Firelink", SplitTrigger::Area { world: 10, area: 2 }),
+            spec("Bonfire", SplitTrigger::Bonfire(1022960)),
+            spec("End", SplitTrigger::Manual),
+        ]
+    }
+
+    #[test]
+    fn test_triggers() {
+        let game = FakeGame::default();
+        let mut timer = SplitTimer::new(specs(), &Records::default());
+
+        game.igt.set(1000);
+        assert_eq!(timer.update(&game, &game), None);
+        assert_eq!(timer.split(game.igt.get()), Some(SplitOutcome::Started));
+
+        // The first poll only primes the trigger.
+        assert_eq!(timer.update(&game, &game), None);
+        game.igt.set(61000);
+        game.flag.set(true);
+        assert_eq!(
+            timer.update(&game, &game),
+            Some(SplitOutcome::Split {
+                index: 0,
+                delta: None,
+                gold: true
+            })
+        );
+        assert_eq!(timer.split_time(0), Some(60000));
+
+        // The flag staying set doesn't split the next segment.
+        assert_eq!(timer.update(&game, &game), None);
+        game.map_id.set(0x0a010000);
+        assert_eq!(timer.update(&game, &game), None);
+        game.igt.set(91000);
+        game.map_id.set(0x0a020100);
+        assert!(matches!(
+            timer.update(&game, &game),
+            Some(SplitOutcome::Split { index: 1, .. })
+        ));
+        assert_eq!(timer.segment_time(1), Some(30000));
+
+        assert_eq!(timer.update(&game, &game), None);
+        game.bonfire.set(1022960);
+        assert!(matches!(
+            timer.update(&game, &game),
+            Some(SplitOutcome::Split { index: 2, .. })
+        ));
+
+        game.igt.set(100000);
+        assert_eq!(timer.update(&game, &game), None);
+        assert_eq!(
+            timer.split(game.igt.get()),
+            Some(SplitOutcome::Finished {
+                delta: None,
+                personal_best: true
+            })
+        );
+        assert_eq!(timer.state(), TimerState::Finished(1000));
+        assert_eq!(timer.time(200000), Some(99000));
+        assert!(timer.take_dirty());
+        assert!(!timer.take_dirty());
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let records = Records {
+            segments: vec![
+                SegmentRecord {
+                    name: "A".into(),
+                    personal_best: Some(10000),
+                    best_segment: Some(9000),
+                },
+                SegmentRecord {
+                    name: "B".into(),
+                    personal_best: Some(25000),
+                    best_segment: Some(14000),
+                },
+            ],
+        };
+        let mut timer = SplitTimer::new(
+            vec![
+                spec("A", SplitTrigger::Manual),
+                spec("B", SplitTrigger::Manual),
+            ],
+            &records,
+        );
+
+        timer.split(0);
+        assert_eq!(
+            timer.split(11000),
+            Some(SplitOutcome::Split {
+                index: 0,
+                delta: Some(1000),
+                gold: false
+            })
+        );
+        assert_eq!(
+            timer.split(24000),
+            Some(SplitOutcome::Finished {
+                delta: Some(-1000),
+                personal_best: true
+            })
+        );
+        assert!(timer.is_gold(1));
+        assert!(!timer.is_gold(0));
+
+        let records = timer.records();
+        assert_eq!(records.segments[0].personal_best, Some(11000));
+        assert_eq!(records.segments[0].best_segment, Some(9000));
+        assert_eq!(records.segments[1].personal_best, Some(24000));
+        assert_eq!(records.segments[1].best_segment, Some(13000));
+
+        // A slower run keeps the personal best.
+        timer.reset();
+        timer.split(0);
+        timer.split(10000);
+        assert_eq!(
+            timer.split(30000),
+            Some(SplitOutcome::Finished {
+                delta: Some(6000),
+                personal_best: false
+            })
+        );
+        assert_eq!(timer.records().segments[1].personal_best, Some(24000));
+        assert_eq!(timer.records().segments[0].best_segment, Some(9000));
+
+        // A faster run with a skipped segment keeps the skipped split.
+        timer.reset();
+        timer.split(0);
+        timer.skip();
+        assert_eq!(
+            timer.split(20000),
+            Some(SplitOutcome::Finished {
+                delta: Some(-4000),
+                personal_best: true
+            })
+        );
+        assert_eq!(timer.records().segments[0].personal_best, Some(11000));
+        assert_eq!(timer.records().segments[1].personal_best, Some(20000));
+    }
+
+    #[test]
+    fn test_skip_undo() {
+        let mut timer = SplitTimer::new(specs(), &Records::default());
+        assert!(!timer.skip());
+        assert!(!timer.undo());
+        assert!(!timer.reset());
+
+        timer.split(0);
+        assert!(!timer.undo());
+        assert!(timer.skip());
+        assert_eq!(timer.current(), 1);
+        timer.split(5000);
+        assert_eq!(timer.split_time(0), None);
+        assert_eq!(timer.segment_time(1), None);
+        assert!(!timer.is_gold(1));
+
+        timer.undo();
+        assert_eq!(timer.current(), 1);
+        assert_eq!(timer.split_time(1), None);
+
+        timer.split(7000);
+        timer.split(8000);
+        timer.split(9000);
+        assert_eq!(timer.state(), TimerState::Finished(0));
+        assert_eq!(timer.segments()[3].personal_best, Some(9000));
+        timer.take_dirty();
+
+        // Undoing the last split reverts the personal best it set.
+        timer.undo();
+        assert_eq!(timer.state(), TimerState::Running(0));
+        assert_eq!(timer.current(), 3);
+        assert_eq!(timer.segments()[3].personal_best, None);
+        assert!(timer.take_dirty());
+    }
+
+    #[test]
+    fn test_records_file() {
+        let path = std::env::temp_dir().join(format!("dsr-splits-{}.json", std::process::id()));
+        assert_eq!(Records::load(&path), Ok(Records::default()));
+
+        let mut timer = SplitTimer::new(specs(), &Records::default());
+        timer.split(0);
+        timer.split(1234);
+        timer.records().save(&path).unwrap();
+
+        let records = Records::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(records.segments[0].best_segment, Some(1234));
+        assert_eq!(
+            SplitTimer::new(specs(), &records).segments()[0].best_segment,
+            Some(1234)
+        );
+    }
+
+    #[test]
+    fn test_segment_config() {
+        #[derive(Deserialize)]
+        struct Segments {
+            segments: Vec<SegmentSpec>,
+        }
+
+        let segments = toml::from_str::<Segments>(
+            r#"segments = [
+                { name = "Asylum Demon", event_flag = 16 },
+                { name = "Firelink", area = "m10_02" },
+                { name = "Bonfire", bonfire = 1022960 },
+                { name = "Manual" },
+            ]"#,
+        )
+        .unwrap()
+        .segments;
+
+        let triggers: Vec<_> = segments.iter().map(|s| s.trigger).collect();
+        assert_eq!(
+            triggers,
+            specs().iter().map(|s| s.trigger).collect::<Vec<_>>()
+        );
+
+        assert!(
+            toml::from_str::<Segments>(r#"segments = [{ name = "X", area = "10_02" }]"#).is_err()
+        );
+        assert!(toml::from_str::<Segments>(
+            r#"segments = [{ name = "X", event_flag = 1, bonfire = 2 }]"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        let mut out = String::new();
+        write_time(&mut out, 3723450);
+        assert_eq!(out, "1:02:03.45");
+        out.clear();
+        write_delta(&mut out, -1500);
+        assert_eq!(out, "-0:01.50");
+    }
+}
@@ -0,0 +1,205 @@
+//! Real-time timer with load removal.
+//!
+//! Loading screens are detected in two ways: the game reporting that it is
+//! loading (see [`LoadSource`]), and IGT not advancing for a while, which
+//! also covers loads where the player instance is still alive.
+
+use std::time::{Duration, Instant};
+
+use super::IgtSource;
+
+/// How long IGT has to stay frozen before the time is counted as loading.
+/// IGT advances every frame while playing, so this only needs to absorb
+/// frame hitches.
+pub(crate) const FREEZE_THRESHOLD: Duration = Duration::from_millis(250);
+
+/// Tells whether the game is currently on a loading screen.
+pub(crate) trait LoadSource {
+    fn is_loading(&self) -> bool;
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct RealTimer {
+    paused: bool,
+    /// Time of the last update, if the timer was running at that point.
+    last: Option<Instant>,
+    last_igt: Option<u32>,
+    real: Duration,
+    loads: Duration,
+    /// Time spent with IGT frozen, not counted as loading yet.
+    frozen: Duration,
+    loading: bool,
+}
+
+impl RealTimer {
+    /// Advance the timer to `now`, classifying the elapsed time since the
+    /// last update as loading or not.
+    pub(crate) fn update(&mut self, now: Instant, game: &(impl IgtSource + LoadSource)) {
+        let igt = game.igt();
+        let last = self.last.replace(now);
+        let last_igt = std::mem::replace(&mut self.last_igt, igt);
+
+        let Some(last) = last.filter(|_| !self.paused) else {
+            self.last = (!self.paused).then_some(now);
+            return;
+        };
+        let elapsed = now.saturating_duration_since(last);
+        self.real += elapsed;
+
+        if game.is_loading() {
+            self.loads += elapsed + std::mem::take(&mut self.frozen);
+            self.loading = true;
+        } else if igt.is_none() || igt == last_igt {
+            self.frozen += elapsed;
+            if self.loading || self.frozen >= FREEZE_THRESHOLD {
+                self.loads += std::mem::take(&mut self.frozen);
+                self.loading = true;
+            }
+        } else {
+            self.frozen = Duration::ZERO;
+            self.loading = false;
+        }
+    }
+
+    /// Total real time since the last reset, excluding pauses.
+    pub(crate) fn real_time(&self) -> Duration {
+        self.real
+    }
+
+    /// Real time minus loading screens.
+    pub(crate) fn load_removed_time(&self) -> Duration {
+        self.real - self.loads
+    }
+
+    /// Whether the last update was classified as loading.
+    pub(crate) fn is_loading(&self) -> bool {
+        self.loading
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub(crate) fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.last = None;
+        self.frozen = Duration::ZERO;
+    }
+
+    /// Zero both times. The pause state is kept.
+    pub(crate) fn reset(&mut self) {
+        *self = RealTimer {
+            paused: self.paused,
+            ..Default::default()
+        };
+    }
+}
+
+/// Duration in milliseconds, saturating, for use with [`super::write_time`].
+pub(crate) fn millis(d: Duration) -> u32 {
+    d.as_millis().min(u32::MAX as u128) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeGame {
+        igt: Cell<u32>,
+        loading: Cell<bool>,
+    }
+
+    impl IgtSource for FakeGame {
+        fn igt(&self) -> Option<u32> {
+            Some(self.igt.get())
+        }
+    }
+
+    impl LoadSource for FakeGame {
+        fn is_loading(&self) -> bool {
+            self.loading.get()
+        }
+    }
+
+    struct Clock {
+        now: Instant,
+    }
+
+    impl Clock {
+        fn step(&mut self, game: &FakeGame, timer: &mut RealTimer, ms: u64, igt_advances: bool) {
+            self.now += Duration::from_millis(ms);
+            if igt_advances {
+                game.igt.set(game.igt.get() + ms as u32);
+            }
+            timer.update(self.now, game);
+        }
+    }
+
+    #[test]
+    fn test_load_removal() {
+        let game = FakeGame::default();
+        let mut timer = RealTimer::default();
+        let mut clock = Clock {
+            now: Instant::now(),
+        };
+
+        timer.update(clock.now, &game);
+        clock.step(&game, &mut timer, 1000, true);
+        assert_eq!(millis(timer.real_time()), 1000);
+        assert_eq!(millis(timer.load_removed_time()), 1000);
+
+        // Loading reported by the game.
+        game.loading.set(true);
+        clock.step(&game, &mut timer, 2000, false);
+        assert!(timer.is_loading());
+        game.loading.set(false);
+        clock.step(&game, &mut timer, 500, true);
+        assert!(!timer.is_loading());
+        assert_eq!(millis(timer.real_time()), 3500);
+        assert_eq!(millis(timer.load_removed_time()), 1500);
+
+        // A short hitch isn't a load.
+        clock.step(&game, &mut timer, 100, false);
+        clock.step(&game, &mut timer, 100, true);
+        assert_eq!(millis(timer.load_removed_time()), 1700);
+
+        // A long IGT freeze is, retroactively.
+        for _ in 0..5 {
+            clock.step(&game, &mut timer, 100, false);
+        }
+        assert!(timer.is_loading());
+        clock.step(&game, &mut timer, 100, true);
+        assert_eq!(millis(timer.real_time()), 4300);
+        assert_eq!(millis(timer.load_removed_time()), 1800);
+    }
+
+    #[test]
+    fn test_pause_and_reset() {
+        let game = FakeGame::default();
+        let mut timer = RealTimer::default();
+        let mut clock = Clock {
+            now: Instant::now(),
+        };
+
+        timer.update(clock.now, &game);
+        clock.step(&game, &mut timer, 1000, true);
+        timer.toggle_pause();
+        clock.step(&game, &mut timer, 5000, true);
+        assert!(timer.is_paused());
+        assert_eq!(millis(timer.real_time()), 1000);
+
+        timer.toggle_pause();
+        clock.step(&game, &mut timer, 5000, true);
+        clock.step(&game, &mut timer, 200, true);
+        assert_eq!(millis(timer.real_time()), 1200);
+
+        timer.reset();
+        assert_eq!(timer.real_time(), Duration::ZERO);
+        clock.step(&game, &mut timer, 300, true);
+        clock.step(&game, &mut timer, 300, true);
+        assert_eq!(millis(timer.load_removed_time()), 300);
+    }
+}
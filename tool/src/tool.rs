@@ -1,17 +1,33 @@
-use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use hudhook::tracing::{debug, error, info};
 use hudhook::ImguiRenderLoop;
-use imgui::{Condition, StyleVar, Ui, WindowFlags};
+use imgui::{Condition, StyleVar, WindowFlags};
 use libdsr::prelude::*;
 use practice_tool_core::crossbeam_channel::{self, Receiver, Sender};
 use practice_tool_core::widgets::Widget;
 use tracing_subscriber::prelude::*;
 
-use crate::config::{Config, IndicatorType, Settings};
+use crate::config::{BuildContext, Config, Settings};
+use crate::indicators::{self, IndicatorRegistry, IndicatorSlot};
+use crate::ipc::GameHandler;
+use crate::livesplit::LiveSplit;
+use crate::palette::PaletteRegistry;
+use crate::proxy;
+use crate::restore::RestoreRegistry;
 use crate::util;
+use crate::widgets::palette::Palette;
+use crate::widgets::scripts::scripts;
+
+/// Set from outside the render loop to eject on the next frame.
+static EJECT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Asks the tool to eject itself, as if the menu's Eject button was clicked.
+pub(crate) fn request_eject() {
+    EJECT_REQUESTED.store(true, Ordering::Relaxed);
+}
 
 enum UiState {
     MenuOpen,
@@ -22,22 +38,17 @@ enum UiState {
 pub(crate) struct Tool {
     settings: Settings,
     pointers: PointerChains,
-    version_label: String,
     widgets: Vec<Box<dyn Widget>>,
+    palette: Option<Palette>,
+    indicators: Vec<IndicatorSlot>,
+    livesplit: Option<LiveSplit>,
+    ipc: Option<dsr_ipc::Server>,
+    restore: RestoreRegistry,
 
     log: Vec<(Instant, String)>,
     log_rx: Receiver<String>,
     log_tx: Sender<String>,
     ui_state: UiState,
-
-    position_bufs: [String; 4],
-    position_prev: [f32; 3],
-    position_change_buf: String,
-
-    igt_buf: String,
-
-    framecount: u32,
-    framecount_buf: String,
 }
 
 impl Tool {
@@ -122,31 +133,63 @@ impl Tool {
 
         let pointers = PointerChains::new();
         info!("pointers {:?}", pointers);
-        let version_label = {
-            let (maj, min, patch) = (*VERSION).into();
-            format!("Game Ver {}.{:02}.{}", maj, min, patch)
-        };
         let settings = config.settings.clone();
-        let widgets = config.make_commands(&pointers);
-
         let (log_tx, log_rx) = crossbeam_channel::unbounded();
+        let livesplit =
+            settings.livesplit.as_ref().map(|ls| LiveSplit::connect(&ls.address, log_tx.clone()));
+        let ipc = settings.ipc.as_ref().and_then(|ipc| {
+            match dsr_ipc::Server::spawn(&ipc.address, &ipc.token) {
+                Ok(server) => {
+                    log_tx.send(format!("Serving IPC on {}", server.address())).ok();
+                    Some(server)
+                }
+                Err(e) => {
+                    log_tx.send(e).ok();
+                    None
+                }
+            }
+        });
+        let mut registry = IndicatorRegistry::default();
+        let restore = RestoreRegistry::default();
+        let mut palette_registry = PaletteRegistry::default();
+        let mut widgets = config.make_commands(&mut BuildContext {
+            chains: &pointers,
+            indicators: &mut registry,
+            livesplit: livesplit.as_ref(),
+            restore: &restore,
+            palette: &mut palette_registry,
+        });
+        widgets.extend(scripts(&restore, &mut palette_registry));
+        let palette = settings
+            .palette
+            .map(|key| Palette::new(key, pointers.cursor_show.clone(), palette_registry.actions()));
+        let (indicators, indicator_errors) =
+            indicators::build_slots(&settings.indicators, &pointers, registry);
+
+        for e in indicator_errors.into_iter().chain(palette_registry.missing()) {
+            log_tx.send(e).ok();
+        }
         info!("Initialized");
 
+        proxy::load_dlls(
+            &settings.load_dlls,
+            Duration::from_millis(settings.load_dlls_delay_ms),
+            log_tx.clone(),
+        );
+
         Tool {
             settings,
             pointers,
-            version_label,
             widgets,
+            palette,
+            indicators,
+            livesplit,
+            ipc,
+            restore,
             log: Vec::new(),
             log_tx,
             log_rx,
             ui_state: UiState::Closed,
-            position_prev: Default::default(),
-            position_bufs: Default::default(),
-            position_change_buf: Default::default(),
-            igt_buf: Default::default(),
-            framecount: 0,
-            framecount_buf: Default::default(),
         }
     }
 
@@ -178,13 +221,34 @@ impl Tool {
                 }
 
                 if ui.button_with_size("Eject", [320.0, 0.0]) {
-                    self.ui_state = UiState::Closed;
-                    self.pointers.cursor_show.set(false);
-                    hudhook::eject();
+                    self.eject();
                 }
             });
     }
 
+    fn eject(&mut self) {
+        self.ui_state = UiState::Closed;
+        self.pointers.cursor_show.set(false);
+        self.restore_state();
+        hudhook::eject();
+    }
+
+    /// Puts back the game state the widgets changed, unless the config opts
+    /// out of it.
+    fn restore_state(&self) {
+        if self.settings.keep_state_on_eject {
+            info!("Ejecting, keeping the game state as it is");
+            return;
+        }
+
+        let restored = self.restore.restore_all();
+        if restored.is_empty() {
+            info!("Ejecting, no game state to restore");
+        } else {
+            info!("Ejecting and resetting all: {}", restored.join(", "));
+        }
+    }
+
     fn render_closed(&mut self, ui: &imgui::Ui) {
         let stack_tokens = [
             ui.push_style_var(StyleVar::WindowRounding(0.)),
@@ -228,24 +292,13 @@ impl Tool {
                         );
                         ui.separator();
 
-                        for indicator in &mut self.settings.indicators {
-                            let label = match indicator.indicator {
-                                IndicatorType::GameVersion => "Game Version",
-                                IndicatorType::Position => "Player Position",
-                                IndicatorType::PositionChange => "Player Velocity",
-                                IndicatorType::Igt => "IGT Timer",
-                                IndicatorType::Fps => "FPS",
-                                IndicatorType::FrameCount => "Frame Counter",
-                                IndicatorType::ImguiDebug => "ImGui Debug Info",
-                                IndicatorType::Animation => "Animation",
-                            };
-
-                            let mut state = indicator.enabled;
-
-                            if ui.checkbox(label, &mut state) {
-                                indicator.enabled = state;
+                        for slot in &mut self.indicators {
+                            let mut state = slot.settings.enabled;
+
+                            if ui.checkbox(slot.label(), &mut state) {
+                                slot.settings.enabled = state;
                             }
-                            if let IndicatorType::FrameCount = indicator.indicator {
+                            if slot.indicator.resettable() {
                                 ui.same_line();
 
                                 let btn_reset_label = "Reset";
@@ -258,7 +311,7 @@ impl Tool {
                                 ]);
 
                                 if ui.button("Reset") {
-                                    self.framecount = 0;
+                                    slot.indicator.reset();
                                 }
                             }
                         }
@@ -295,96 +348,7 @@ impl Tool {
 
                 ui.new_line();
 
-                for indicator in &self.settings.indicators {
-                    if !indicator.enabled {
-                        continue;
-                    }
-
-                    match indicator.indicator {
-                        IndicatorType::Position => {
-                            if let (Some([x, y, z]), Some(a)) = (
-                                self.pointers.position.1.read(),
-                                self.pointers.position.0.read(),
-                            ) {
-                                self.position_bufs.iter_mut().for_each(String::clear);
-                                write!(self.position_bufs[0], "{x:.3}").ok();
-                                write!(self.position_bufs[1], "{y:.3}").ok();
-                                write!(self.position_bufs[2], "{z:.3}").ok();
-                                write!(self.position_bufs[3], "{a:.3}").ok();
-
-                                ui.text_colored(
-                                    [0.7048, 0.1228, 0.1734, 1.],
-                                    &self.position_bufs[0],
-                                );
-                                ui.same_line();
-                                ui.text_colored(
-                                    [0.1161, 0.5327, 0.3512, 1.],
-                                    &self.position_bufs[1],
-                                );
-                                ui.same_line();
-                                ui.text_colored(
-                                    [0.1445, 0.2852, 0.5703, 1.],
-                                    &self.position_bufs[2],
-                                );
-                                ui.same_line();
-                                ui.text(&self.position_bufs[3]);
-                            }
-                        }
-                        IndicatorType::PositionChange => {
-                            if let Some([x, y, z]) = self.pointers.position.1.read() {
-                                let position_change_xyz = ((x - self.position_prev[0]).powf(2.0)
-                                    + (y - self.position_prev[1]).powf(2.0)
-                                    + (z - self.position_prev[2]).powf(2.0))
-                                .sqrt();
-
-                                let position_change_xz = ((x - self.position_prev[0]).powf(2.0)
-                                    + (z - self.position_prev[2]).powf(2.0))
-                                .sqrt();
-
-                                let position_change_y = y - self.position_prev[1];
-
-                                self.position_change_buf.clear();
-                                write!(
-                                    self.position_change_buf,
-                                    "[XYZ] {position_change_xyz:.6} | [XZ] \
-                                     {position_change_xz:.6} | [Y] {position_change_y:.6}"
-                                )
-                                .ok();
-                                ui.text(&self.position_change_buf);
-
-                                self.position_prev = [x, y, z];
-                            }
-                        }
-                        IndicatorType::Igt => {
-                            if let Some(igt) = self.pointers.igt.read() {
-                                let millis = (igt % 1000) / 10;
-                                let total_seconds = igt / 1000;
-                                let seconds = total_seconds % 60;
-                                let minutes = total_seconds / 60 % 60;
-                                let hours = total_seconds / 3600;
-                                self.igt_buf.clear();
-                                write!(
-                                    self.igt_buf,
-                                    "IGT {hours:02}:{minutes:02}:{seconds:02}.{millis:02}",
-                                )
-                                .ok();
-                                ui.text(&self.igt_buf);
-                            }
-                        }
-                        IndicatorType::GameVersion => {
-                            ui.text(&self.version_label);
-                        }
-                        IndicatorType::FrameCount => {
-                            self.framecount_buf.clear();
-                            write!(self.framecount_buf, "Frame count {0}", self.framecount,).ok();
-                            ui.text(&self.framecount_buf);
-                        }
-                        IndicatorType::ImguiDebug => {
-                            imgui_debug(ui);
-                        }
-                        _ => {}
-                    }
-                }
+                indicators::render_inline(ui, &mut self.indicators);
 
                 for w in self.widgets.iter_mut() {
                     w.render_closed(ui);
@@ -395,6 +359,8 @@ impl Tool {
                 }
             });
 
+        indicators::render_anchored(ui, &mut self.indicators);
+
         for st in stack_tokens.into_iter().rev() {
             st.pop();
         }
@@ -449,6 +415,11 @@ impl Tool {
 
 impl ImguiRenderLoop for Tool {
     fn render(&mut self, ui: &mut imgui::Ui) {
+        if EJECT_REQUESTED.swap(false, Ordering::Relaxed) {
+            self.eject();
+            return;
+        }
+
         let display = self.settings.display.is_pressed(ui);
         let hide = self
             .settings
@@ -456,7 +427,26 @@ impl ImguiRenderLoop for Tool {
             .map(|k| k.is_pressed(ui))
             .unwrap_or(false);
 
-        self.framecount += 1;
+        for slot in &mut self.indicators {
+            slot.indicator.tick(ui);
+        }
+
+        if let (Some(livesplit), Some(igt)) = (&mut self.livesplit, self.pointers.igt.read()) {
+            livesplit.set_game_time(igt);
+        }
+
+        if let Some(ipc) = &self.ipc {
+            ipc.poll(&mut GameHandler {
+                chains: &self.pointers,
+                restore: &self.restore,
+                backups: self.settings.backups,
+                log_tx: &self.log_tx,
+            });
+        }
+
+        if let Some(palette) = &mut self.palette {
+            palette.interact(ui);
+        }
 
         if !ui.io().want_capture_keyboard && (display || hide) {
             self.ui_state = match (&self.ui_state, hide) {
@@ -486,9 +476,17 @@ impl ImguiRenderLoop for Tool {
             }
         }
 
+        if let Some(palette) = &mut self.palette {
+            palette.render(ui);
+            palette.log(self.log_tx.clone());
+        }
+
         for w in &mut self.widgets {
             w.log(self.log_tx.clone());
         }
+        for log in proxy::take_logs() {
+            self.log_tx.send(log).ok();
+        }
 
         let now = Instant::now();
         self.log.extend(
@@ -505,18 +503,3 @@ impl ImguiRenderLoop for Tool {
         // self.render_visible(ui)
     }
 }
-
-// Display some imgui debug information. Very expensive.
-fn imgui_debug(ui: &Ui) {
-    let io = ui.io();
-    ui.text(format!("Mouse position     {:?}", io.mouse_pos));
-    ui.text(format!("Mouse down         {:?}", io.mouse_down));
-    ui.text(format!("Want capture mouse {:?}", io.want_capture_mouse));
-    ui.text(format!("Want capture kbd   {:?}", io.want_capture_keyboard));
-    ui.text(format!("Want text input    {:?}", io.want_text_input));
-    ui.text(format!("Want set mouse pos {:?}", io.want_set_mouse_pos));
-    ui.text(format!("Any item active    {:?}", ui.is_any_item_active()));
-    ui.text(format!("Any item hovered   {:?}", ui.is_any_item_hovered()));
-    ui.text(format!("Any item focused   {:?}", ui.is_any_item_focused()));
-    ui.text(format!("Any mouse down     {:?}", ui.is_any_mouse_down()));
-}
@@ -2,25 +2,31 @@ use std::cmp::Ordering;
 use std::fmt::Write;
 
 
+use imgui::Ui;
 use practice_tool_core::key::Key;
 use practice_tool_core::widgets::store_value::{ReadWrite, StoreValue};
 use practice_tool_core::widgets::Widget;
 
 use libdsr::prelude::*;
 
-#[derive(Debug)]
+use crate::indicators::{HudProvider, Indicator, IndicatorRegistry};
+use crate::palette::{PaletteAction, PaletteProvider, PaletteRegistry};
+use crate::restore::RestoreRegistry;
+
+#[derive(Debug, Clone)]
 struct CycleSpeed {
     ptr: PointerChain<f32>,
     values: Vec<f32>,
     current: Option<f32>,
     label: String,
+    restore: RestoreRegistry,
 }
 
 impl CycleSpeed {
-    fn new(values: &[f32], ptr: PointerChain<f32>) -> Self {
+    fn new(values: &[f32], ptr: PointerChain<f32>, restore: RestoreRegistry) -> Self {
         let mut values = values.to_vec();
         values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
-        CycleSpeed { ptr, values, current: None, label: String::new() }
+        CycleSpeed { ptr, values, current: None, label: String::new(), restore }
     }
 }
 
@@ -44,6 +50,7 @@ impl ReadWrite for CycleSpeed {
             .and_then(|current| self.values.iter().find(|&&x| x > current))
             .unwrap_or_else(|| self.values.first().unwrap_or(&1.0));
 
+        self.restore.record("Speed", &self.ptr);
         self.ptr.write(next);
     }
 
@@ -52,10 +59,74 @@ impl ReadWrite for CycleSpeed {
     }
 }
 
+impl HudProvider for CycleSpeed {
+    fn hud_indicators(&self) -> Vec<(&'static str, Box<dyn Indicator>)> {
+        vec![("speed", Box::new(SpeedIndicator { ptr: self.ptr.clone(), buf: String::new() }))]
+    }
+}
+
+impl PaletteAction for CycleSpeed {
+    fn run(&mut self) -> Result<(), String> {
+        if !self.read() {
+            return Err("Couldn't read speed".to_string());
+        }
+        self.write();
+        Ok(())
+    }
+}
+
+/// Sets one of the configured speeds.
+struct SetSpeed(PointerChain<f32>, f32, RestoreRegistry);
+
+impl PaletteAction for SetSpeed {
+    fn run(&mut self) -> Result<(), String> {
+        let SetSpeed(ptr, speed, restore) = self;
+        restore.record("Speed", ptr);
+        ptr.write(*speed).ok_or_else(|| "Couldn't set speed".to_string())
+    }
+}
+
+impl PaletteProvider for CycleSpeed {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+        let mut actions: Vec<(String, Box<dyn PaletteAction>)> =
+            vec![("Cycle speed".to_string(), Box::new(self.clone()))];
+        actions.extend(self.values.iter().map(|&speed| {
+            let action = SetSpeed(self.ptr.clone(), speed, self.restore.clone());
+            (format!("Speed {speed:.1}x"), Box::new(action) as _)
+        }));
+        actions
+    }
+}
+
+struct SpeedIndicator {
+    ptr: PointerChain<f32>,
+    buf: String,
+}
+
+impl Indicator for SpeedIndicator {
+    fn label(&self) -> &str {
+        "Speed"
+    }
+
+    fn render(&mut self, ui: &Ui) {
+        if let Some(speed) = self.ptr.read() {
+            self.buf.clear();
+            write!(self.buf, "Speed {:.1}x", speed).ok();
+            ui.text(&self.buf);
+        }
+    }
+}
+
 pub(crate) fn cycle_speed(
     values: &[f32],
     ptr: PointerChain<f32>,
     key: Option<Key>,
+    indicators: &mut IndicatorRegistry,
+    restore: &RestoreRegistry,
+    palette: &mut PaletteRegistry,
 ) -> Box<dyn Widget> {
-    Box::new(StoreValue::new(CycleSpeed::new(values, ptr), key))
+    let cycle_speed = CycleSpeed::new(values, ptr, restore.clone());
+    indicators.register(&cycle_speed);
+    palette.register(&cycle_speed);
+    Box::new(StoreValue::new(cycle_speed, key))
 }
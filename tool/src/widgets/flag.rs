@@ -3,15 +3,57 @@ use practice_tool_core::key::Key;
 use practice_tool_core::widgets::flag::{Flag, FlagWidget};
 use practice_tool_core::widgets::Widget;
 
-struct Bitflag(BitflagInner<u8>);
+use crate::palette::{PaletteAction, PaletteProvider, PaletteRegistry};
+use crate::restore::RestoreRegistry;
+
+#[derive(Clone)]
+struct Bitflag {
+    flag: BitflagInner<u8>,
+    label: String,
+    restore: RestoreRegistry,
+}
 
 impl Flag for Bitflag {
     fn set(&mut self, value: bool) {
-        self.0.set(value);
+        self.restore.record(&self.label, &self.flag);
+        self.flag.set(value);
     }
 
     fn get(&self) -> Option<bool> {
-        self.0.get()
+        self.flag.get()
+    }
+}
+
+impl PaletteAction for Bitflag {
+    fn run(&mut self) -> Result<(), String> {
+        let state = self.get().ok_or_else(|| format!("Couldn't read {}", self.label))?;
+        self.set(!state);
+        Ok(())
+    }
+}
+
+/// Sets the flag to a given state, for macros that shouldn't depend on the
+/// state they start from.
+struct SetFlag(Bitflag, bool);
+
+impl PaletteAction for SetFlag {
+    fn run(&mut self) -> Result<(), String> {
+        let SetFlag(flag, state) = self;
+        flag.set(*state);
+        if flag.get() != Some(*state) {
+            return Err(format!("Couldn't set {}", flag.label));
+        }
+        Ok(())
+    }
+}
+
+impl PaletteProvider for Bitflag {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+        vec![
+            (self.label.clone(), Box::new(self.clone())),
+            (format!("{} on", self.label), Box::new(SetFlag(self.clone(), true))),
+            (format!("{} off", self.label), Box::new(SetFlag(self.clone(), false))),
+        ]
     }
 }
 
@@ -19,6 +61,10 @@ pub(crate) fn flag_widget(
     label: &str,
     bitflag: BitflagInner<u8>,
     key: Option<Key>,
+    restore: &RestoreRegistry,
+    palette: &mut PaletteRegistry,
 ) -> Box<dyn Widget> {
-    Box::new(FlagWidget::new(label, Bitflag(bitflag), key))
+    let flag = Bitflag { flag: bitflag, label: label.to_string(), restore: restore.clone() };
+    palette.register(&flag);
+    Box::new(FlagWidget::new(label, flag, key))
 }
@@ -0,0 +1,155 @@
+use std::time::Instant;
+
+use imgui::Ui;
+use libdsr::memedit::{Bitflag, PointerChain};
+use libdsr::prelude::PointerChains;
+use practice_tool_core::crossbeam_channel::Sender;
+use practice_tool_core::key::Key;
+use practice_tool_core::widgets::Widget;
+
+use crate::macros::{Sequence, Step};
+use crate::palette::{
+    hotkeys_enabled, Actions, PaletteAction, PaletteProvider, PaletteRegistry, Trigger,
+};
+use crate::restore::RestoreRegistry;
+
+/// A change to the game state made by a macro step.
+pub(crate) enum Action {
+    Flag {
+        label: String,
+        flag: Bitflag<u8>,
+        state: bool,
+    },
+    Speed(PointerChain<f32>, f32),
+    AddSouls(PointerChain<u32>, u32),
+    Position {
+        ptr: (PointerChain<f32>, PointerChain<[f32; 3]>),
+        pos: [f32; 3],
+        angle: Option<f32>,
+    },
+    /// An action of another command, by its name in the palette.
+    Run {
+        name: String,
+        actions: Actions,
+    },
+}
+
+impl Action {
+    fn run(&self, restore: &RestoreRegistry) -> Result<(), String> {
+        match self {
+            Action::Flag { label, flag, state } => {
+                restore.record(label, flag);
+                flag.set(*state);
+                if flag.get() != Some(*state) {
+                    return Err(format!("Couldn't set {label}"));
+                }
+            }
+            Action::Speed(ptr, speed) => {
+                restore.record("Speed", ptr);
+                ptr.write(*speed).ok_or("Couldn't set speed")?;
+            }
+            Action::AddSouls(ptr, amount) => {
+                let souls = ptr.read().ok_or("Couldn't read souls")?;
+                ptr.write(souls.saturating_add(*amount)).ok_or("Couldn't add souls")?;
+            }
+            Action::Position { ptr: (ptr_angle, ptr_pos), pos, angle } => {
+                ptr_pos.write(*pos).ok_or("Couldn't set position")?;
+                if let Some(angle) = angle {
+                    ptr_angle.write(*angle).ok_or("Couldn't set angle")?;
+                }
+            }
+            Action::Run { name, actions } => actions.run(name)?,
+        }
+        Ok(())
+    }
+}
+
+/// Runs its steps from the start when clicked or on its hotkey, advancing
+/// every frame whether or not the menu is open.
+struct Macro {
+    label: String,
+    key: Option<Key>,
+    /// Pulled by the palette to start.
+    trigger: Trigger,
+    sequence: Sequence<Action>,
+    igt: PointerChain<u32>,
+    /// Null or unreadable while a loading screen is up.
+    player_ins: PointerChain<usize>,
+    restore: RestoreRegistry,
+    logs: Vec<String>,
+}
+
+impl Macro {
+    fn start(&mut self) {
+        self.sequence.start();
+        self.logs.push(format!("Running {}", self.label));
+    }
+}
+
+impl Widget for Macro {
+    fn render(&mut self, ui: &Ui) {
+        let label = match self.sequence.progress() {
+            Some((step, steps)) => format!("{} [{step}/{steps}]###{}", self.label, self.label),
+            None => format!("{}###{}", self.label, self.label),
+        };
+        if ui.button_with_size(label, [320., 0.]) {
+            self.start();
+        }
+    }
+
+    fn render_closed(&mut self, ui: &Ui) {
+        if let Some((step, steps)) = self.sequence.progress() {
+            ui.text(format!("{} [{step}/{steps}]", self.label));
+        }
+    }
+
+    fn interact(&mut self, ui: &Ui) {
+        let pressed = hotkeys_enabled() && self.key.is_some_and(|k| k.is_pressed(ui));
+        if pressed || self.trigger.take() {
+            self.start();
+        }
+
+        let igt = self.igt.read();
+        let loading = self.player_ins.read().unwrap_or(0) == 0;
+        let restore = &self.restore;
+        match self.sequence.tick(Instant::now(), igt, loading, |action| action.run(restore)) {
+            Ok(true) => self.logs.push(format!("{} done", self.label)),
+            Ok(false) => {}
+            Err(e) => self.logs.push(format!("{} stopped: {e}", self.label)),
+        }
+    }
+
+    fn log(&mut self, tx: Sender<String>) {
+        for log in self.logs.drain(..) {
+            tx.send(log).ok();
+        }
+    }
+}
+
+impl PaletteProvider for Macro {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+        vec![(self.label.clone(), Box::new(self.trigger.clone()))]
+    }
+}
+
+pub(crate) fn macro_widget(
+    label: &str,
+    steps: Vec<Step<Action>>,
+    chains: &PointerChains,
+    key: Option<Key>,
+    restore: &RestoreRegistry,
+    palette: &mut PaletteRegistry,
+) -> Box<dyn Widget> {
+    let widget = Macro {
+        label: label.to_string(),
+        key,
+        trigger: Trigger::default(),
+        sequence: Sequence::new(steps),
+        igt: chains.igt.clone(),
+        player_ins: chains.player_ins.clone(),
+        restore: restore.clone(),
+        logs: Vec::new(),
+    };
+    palette.register(&widget);
+    Box::new(widget)
+}
@@ -9,3 +9,11 @@ pub(crate) mod warp_menu;
 pub(crate) mod position;
 pub(crate) mod nudge_pos;
 pub(crate) mod savefile_manager;
+pub(crate) mod splits;
+pub(crate) mod real_timer;
+pub(crate) mod quitout;
+pub(crate) mod position_library;
+pub(crate) mod noclip;
+pub(crate) mod macros;
+pub(crate) mod scripts;
+pub(crate) mod palette;
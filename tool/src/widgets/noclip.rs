@@ -0,0 +1,172 @@
+use std::time::Instant;
+
+use imgui::Ui;
+use libdsr::memedit::{Bitflag, PointerChain};
+use libdsr::prelude::PointerChains;
+use practice_tool_core::crossbeam_channel::Sender;
+use practice_tool_core::key::Key;
+use practice_tool_core::widgets::Widget;
+
+use crate::palette::{hotkeys_enabled, PaletteAction, PaletteProvider, PaletteRegistry, Trigger};
+use crate::positions::offset_relative;
+use crate::restore::RestoreRegistry;
+
+const MIN_SPEED: f32 = 0.5;
+const MAX_SPEED: f32 = 100.;
+
+/// Movement hotkeys, held down to fly.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct NoclipKeys {
+    pub(crate) forward: Option<Key>,
+    pub(crate) back: Option<Key>,
+    pub(crate) left: Option<Key>,
+    pub(crate) right: Option<Key>,
+    pub(crate) up: Option<Key>,
+    pub(crate) down: Option<Key>,
+    pub(crate) faster: Option<Key>,
+    pub(crate) slower: Option<Key>,
+}
+
+/// Disables gravity and collision together and flies the player around with
+/// the movement keys, relative to the direction the camera looks in.
+struct Noclip {
+    camera_yaw: PointerChain<f32>,
+    ptr_pos: PointerChain<[f32; 3]>,
+    gravity: Bitflag<u8>,
+    collision: Bitflag<u8>,
+    key_toggle: Option<Key>,
+    /// Pulled by the palette to toggle.
+    trigger: Trigger,
+    keys: NoclipKeys,
+    /// Units per second.
+    speed: f32,
+    /// Gravity and collision flags from before enabling, restored when
+    /// disabling.
+    previous: Option<(Option<bool>, Option<bool>)>,
+    restore: RestoreRegistry,
+    last_frame: Instant,
+    logs: Vec<String>,
+}
+
+impl Noclip {
+    fn enable(&mut self) {
+        self.restore.record("No Gravity", &self.gravity);
+        self.restore.record("No Collision", &self.collision);
+        self.previous = Some((self.gravity.get(), self.collision.get()));
+        self.gravity.set(true);
+        self.collision.set(true);
+        self.last_frame = Instant::now();
+        self.logs.push("Noclip enabled".to_string());
+    }
+
+    fn disable(&mut self) {
+        let Some((gravity, collision)) = self.previous.take() else {
+            return;
+        };
+        self.gravity.set(gravity.unwrap_or(false));
+        self.collision.set(collision.unwrap_or(false));
+        self.logs.push("Noclip disabled".to_string());
+    }
+
+    fn toggle(&mut self) {
+        if self.previous.is_some() {
+            self.disable();
+        } else {
+            self.enable();
+        }
+    }
+
+    fn fly(&mut self, ui: &Ui) {
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_frame).as_secs_f32().min(0.1);
+        self.last_frame = now;
+
+        let hotkeys = hotkeys_enabled();
+        let held = |key: Option<Key>| {
+            if hotkeys && key.is_some_and(|k| k.is_down(ui)) {
+                1.
+            } else {
+                0.
+            }
+        };
+        let keys = self.keys;
+        let direction = [
+            held(keys.forward) - held(keys.back),
+            held(keys.right) - held(keys.left),
+            held(keys.up) - held(keys.down),
+        ];
+        if direction == [0.; 3] {
+            return;
+        }
+
+        if let (Some(pos), Some(angle)) = (self.ptr_pos.read(), self.camera_yaw.read()) {
+            let distance = self.speed * dt;
+            self.ptr_pos.write(offset_relative(pos, angle, direction.map(|d| d * distance)));
+        }
+    }
+}
+
+impl Widget for Noclip {
+    fn render(&mut self, ui: &Ui) {
+        let mut enabled = self.previous.is_some();
+        if ui.checkbox("Noclip", &mut enabled) {
+            self.toggle();
+        }
+        ui.set_next_item_width(150.);
+        ui.slider("Noclip speed", MIN_SPEED, MAX_SPEED, &mut self.speed);
+    }
+
+    fn interact(&mut self, ui: &Ui) {
+        let pressed = |key: Option<Key>| hotkeys_enabled() && key.is_some_and(|k| k.is_pressed(ui));
+        if pressed(self.key_toggle) || self.trigger.take() {
+            self.toggle();
+        }
+        if pressed(self.keys.faster) {
+            self.speed = (self.speed * 2.).min(MAX_SPEED);
+        }
+        if pressed(self.keys.slower) {
+            self.speed = (self.speed / 2.).max(MIN_SPEED);
+        }
+        if self.previous.is_some() {
+            self.fly(ui);
+        }
+    }
+
+    fn log(&mut self, tx: Sender<String>) {
+        for log in self.logs.drain(..) {
+            tx.send(log).ok();
+        }
+    }
+}
+
+impl PaletteProvider for Noclip {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+        vec![("Toggle noclip".to_string(), Box::new(self.trigger.clone()))]
+    }
+}
+
+pub(crate) fn noclip(
+    chains: &PointerChains,
+    key_toggle: Option<Key>,
+    speed: f32,
+    keys: NoclipKeys,
+    restore: &RestoreRegistry,
+    palette: &mut PaletteRegistry,
+) -> Box<dyn Widget> {
+    let noclip = Noclip {
+        camera_yaw: chains.camera_yaw.clone(),
+        ptr_pos: chains.position.1.clone(),
+        gravity: chains.gravity.clone(),
+        collision: chains.collision.clone(),
+        key_toggle,
+        trigger: Trigger::default(),
+        keys,
+        speed: speed.clamp(MIN_SPEED, MAX_SPEED),
+        previous: None,
+        restore: restore.clone(),
+        last_frame: Instant::now(),
+        logs: Vec::new(),
+    };
+    palette.register(&noclip);
+    Box::new(noclip)
+}
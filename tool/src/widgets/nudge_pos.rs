@@ -1,15 +1,175 @@
+use imgui::Ui;
 use libdsr::prelude::*;
+use practice_tool_core::crossbeam_channel::Sender;
 use practice_tool_core::key::Key;
 use practice_tool_core::widgets::nudge_position::NudgePosition;
 use practice_tool_core::widgets::Widget;
 
+use crate::palette::{PaletteAction, PaletteProvider, PaletteRegistry};
 use crate::widgets::position::SavePosition;
 
+/// Hotkeys for the nudges beyond up and down.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct NudgeKeys {
+    pub(crate) forward: Option<Key>,
+    pub(crate) back: Option<Key>,
+    pub(crate) left: Option<Key>,
+    pub(crate) right: Option<Key>,
+    pub(crate) x_pos: Option<Key>,
+    pub(crate) x_neg: Option<Key>,
+    pub(crate) z_pos: Option<Key>,
+    pub(crate) z_neg: Option<Key>,
+    pub(crate) rotate_left: Option<Key>,
+    pub(crate) rotate_right: Option<Key>,
+}
+
+#[derive(Clone, Copy)]
+enum Nudge {
+    Relative([f32; 3]),
+    Absolute([f32; 3]),
+    Rotate(f32),
+}
+
+/// Forwards to the core up/down nudges, and adds nudges relative to the
+/// facing direction, along the world axes, and rotations.
+struct Nudges {
+    inner: Box<dyn Widget>,
+    position: SavePosition,
+    rotate: f32,
+    keys: NudgeKeys,
+}
+
+impl Nudges {
+    fn actions(&self) -> [(&'static str, Option<Key>, Nudge); 10] {
+        let k = &self.keys;
+        [
+            ("Forward", k.forward, Nudge::Relative([1., 0., 0.])),
+            ("Back", k.back, Nudge::Relative([-1., 0., 0.])),
+            ("Left", k.left, Nudge::Relative([0., -1., 0.])),
+            ("Right", k.right, Nudge::Relative([0., 1., 0.])),
+            ("+X", k.x_pos, Nudge::Absolute([1., 0., 0.])),
+            ("-X", k.x_neg, Nudge::Absolute([-1., 0., 0.])),
+            ("+Z", k.z_pos, Nudge::Absolute([0., 0., 1.])),
+            ("-Z", k.z_neg, Nudge::Absolute([0., 0., -1.])),
+            ("Turn left", k.rotate_left, Nudge::Rotate(-self.rotate)),
+            ("Turn right", k.rotate_right, Nudge::Rotate(self.rotate)),
+        ]
+    }
+
+    fn apply(&mut self, nudge: Nudge) {
+        nudge.apply(&mut self.position);
+    }
+}
+
+impl Nudge {
+    fn apply(self, position: &mut SavePosition) {
+        match self {
+            Nudge::Relative(direction) => position.nudge_relative(direction),
+            Nudge::Absolute(direction) => position.nudge_absolute(direction),
+            Nudge::Rotate(degrees) => position.rotate(degrees),
+        }
+    }
+}
+
+impl Widget for Nudges {
+    fn render(&mut self, ui: &Ui) {
+        // Keeps the buttons apart from those of other nudge commands.
+        let _id = ui.push_id_ptr(self);
+        self.inner.render(ui);
+
+        let spacing = ui.clone_style().item_spacing[0];
+        let button_width = (320. - 3. * spacing) / 4.;
+        let mut pressed = None;
+        for (i, (label, _, nudge)) in self.actions().into_iter().enumerate() {
+            // Four buttons per row, with the two rotations on the last one.
+            if i % 4 != 0 {
+                ui.same_line();
+            }
+            let width = if i >= 8 { button_width * 2. + spacing } else { button_width };
+            if ui.button_with_size(label, [width, 0.]) {
+                pressed = Some(nudge);
+            }
+        }
+        if let Some(nudge) = pressed {
+            self.apply(nudge);
+        }
+    }
+
+    fn render_closed(&mut self, ui: &Ui) {
+        self.inner.render_closed(ui);
+    }
+
+    fn interact(&mut self, ui: &Ui) {
+        self.inner.interact(ui);
+        for (_, key, nudge) in self.actions() {
+            if key.is_some_and(|k| k.is_pressed(ui)) {
+                self.apply(nudge);
+            }
+        }
+    }
+
+    fn log(&mut self, tx: Sender<String>) {
+        self.inner.log(tx);
+    }
+}
+
+impl PaletteProvider for Nudges {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+        let up_down =
+            [("Up", Nudge::Absolute([0., 1., 0.])), ("Down", Nudge::Absolute([0., -1., 0.]))];
+        let others = self.actions().map(|(label, _, nudge)| (label, nudge));
+        up_down
+            .into_iter()
+            .chain(others)
+            .map(|(label, nudge)| {
+                let name = match nudge {
+                    Nudge::Rotate(_) => label.to_string(),
+                    // "Nudge forward", but "Nudge +X".
+                    _ if label.starts_with(char::is_alphabetic) => {
+                        format!("Nudge {}", label.to_lowercase())
+                    }
+                    _ => format!("Nudge {label}"),
+                };
+                let action: Box<dyn PaletteAction> =
+                    Box::new(NudgeAction { position: self.position.clone(), nudge });
+                (name, action)
+            })
+            .collect()
+    }
+}
+
+/// Applies one nudge of a nudge command.
+struct NudgeAction {
+    position: SavePosition,
+    nudge: Nudge,
+}
+
+impl PaletteAction for NudgeAction {
+    fn run(&mut self) -> Result<(), String> {
+        self.nudge.apply(&mut self.position);
+        Ok(())
+    }
+}
+
 pub(crate) fn nudge_position(
     ptr: (PointerChain<f32>, PointerChain<[f32; 3]>),
     nudge: f32,
+    rotate: f32,
     key_nudge_up: Option<Key>,
     key_nudge_down: Option<Key>,
+    keys: NudgeKeys,
+    palette: &mut PaletteRegistry,
 ) -> Box<dyn Widget> {
-    Box::new(NudgePosition::new(SavePosition::new(ptr, nudge), key_nudge_up, key_nudge_down))
+    let nudges = Nudges {
+        inner: Box::new(NudgePosition::new(
+            SavePosition::new(ptr.clone(), nudge),
+            key_nudge_up,
+            key_nudge_down,
+        )),
+        position: SavePosition::new(ptr, nudge),
+        rotate,
+        keys,
+    };
+    palette.register(&nudges);
+    Box::new(nudges)
 }
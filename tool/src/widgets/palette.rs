@@ -0,0 +1,187 @@
+use imgui::{Condition, Ui, WindowFlags};
+use libdsr::memedit::Bitflag;
+use practice_tool_core::crossbeam_channel::Sender;
+use practice_tool_core::key::Key;
+use practice_tool_core::widgets::Widget;
+
+use crate::palette::{hotkeys_enabled, search, set_typing, Actions};
+
+/// Number of matches listed at once.
+const MAX_RESULTS: usize = 12;
+
+/// Lists the actions of the commands, fuzzy-searched by name, and runs the
+/// selected one. It opens on its hotkey whether the menu is open, closed or
+/// hidden.
+pub(crate) struct Palette {
+    key: Key,
+    cursor_show: Bitflag<u8>,
+    actions: Actions,
+    names: Vec<String>,
+    is_open: bool,
+    focus: bool,
+    query: String,
+    results: Vec<usize>,
+    selected: usize,
+    logs: Vec<String>,
+}
+
+impl Palette {
+    pub(crate) fn new(key: Key, cursor_show: Bitflag<u8>, actions: Actions) -> Self {
+        Palette {
+            key,
+            cursor_show,
+            actions,
+            names: Vec::new(),
+            is_open: false,
+            focus: false,
+            query: String::new(),
+            results: Vec::new(),
+            selected: 0,
+            logs: Vec::new(),
+        }
+    }
+
+    fn open(&mut self) {
+        self.is_open = true;
+        self.focus = true;
+        set_typing(true);
+        self.names = self.actions.names();
+        self.query.clear();
+        self.refresh();
+    }
+
+    fn close(&mut self) {
+        self.is_open = false;
+        self.cursor_show.set(false);
+        set_typing(false);
+    }
+
+    fn refresh(&mut self) {
+        self.results = search(self.names.iter().map(String::as_str), &self.query);
+        self.results.truncate(MAX_RESULTS);
+        self.selected = 0;
+    }
+
+    fn run(&mut self, index: usize) {
+        let name = &self.names[index];
+        match self.actions.run(name) {
+            Ok(()) => self.logs.push(format!("Ran {name}")),
+            Err(e) => self.logs.push(format!("{name}: {e}")),
+        }
+        self.close();
+    }
+
+    /// Opens or closes on the hotkey. A text field having the keyboard keeps
+    /// it from opening, but its own search field doesn't keep it from
+    /// closing.
+    pub(crate) fn interact(&mut self, ui: &Ui) {
+        let typing = ui.io().want_capture_keyboard && !self.is_open;
+        if typing || !self.key.is_pressed(ui) {
+            return;
+        }
+        if self.is_open {
+            self.close();
+        } else {
+            self.open();
+        }
+    }
+
+    pub(crate) fn render(&mut self, ui: &Ui) {
+        if !self.is_open {
+            return;
+        }
+        self.cursor_show.set(true);
+
+        let [dw, dh] = ui.io().display_size;
+        let mut run = None;
+        let mut close = false;
+
+        ui.window("##palette")
+            .position([dw * 0.5, dh * 0.2], Condition::Always)
+            .position_pivot([0.5, 0.])
+            .bg_alpha(0.9)
+            .flags({
+                WindowFlags::NO_TITLE_BAR
+                    | WindowFlags::NO_RESIZE
+                    | WindowFlags::NO_MOVE
+                    | WindowFlags::NO_SCROLLBAR
+                    | WindowFlags::ALWAYS_AUTO_RESIZE
+            })
+            .build(|| {
+                if self.focus {
+                    ui.set_keyboard_focus_here();
+                    self.focus = false;
+                }
+                ui.set_next_item_width(480.);
+                if ui.input_text("##palette_query", &mut self.query).hint("Search actions").build()
+                {
+                    self.refresh();
+                }
+
+                if ui.is_key_pressed(imgui::Key::DownArrow) {
+                    self.selected = (self.selected + 1).min(self.results.len().saturating_sub(1));
+                }
+                if ui.is_key_pressed(imgui::Key::UpArrow) {
+                    self.selected = self.selected.saturating_sub(1);
+                }
+                if ui.is_key_pressed(imgui::Key::Enter) {
+                    run = self.results.get(self.selected).copied();
+                }
+                if ui.is_key_pressed(imgui::Key::Escape) {
+                    close = true;
+                }
+
+                for (i, &index) in self.results.iter().enumerate() {
+                    // Several commands may have actions of the same name.
+                    let label = format!("{}###palette_{index}", self.names[index]);
+                    if ui.selectable_config(label).selected(i == self.selected).build() {
+                        run = Some(index);
+                    }
+                }
+                if self.results.is_empty() {
+                    ui.text("No matching action");
+                }
+            });
+
+        if let Some(index) = run {
+            self.run(index);
+        } else if close {
+            self.close();
+        }
+    }
+
+    pub(crate) fn log(&mut self, tx: Sender<String>) {
+        for log in self.logs.drain(..) {
+            tx.send(log).ok();
+        }
+    }
+}
+
+/// A command whose `interact` only handles hotkeys and palette triggers, and
+/// is skipped while typing in the palette. Triggers pulled from the palette
+/// run once it has closed.
+struct Hotkeys(Box<dyn Widget>);
+
+impl Widget for Hotkeys {
+    fn render(&mut self, ui: &Ui) {
+        self.0.render(ui);
+    }
+
+    fn render_closed(&mut self, ui: &Ui) {
+        self.0.render_closed(ui);
+    }
+
+    fn interact(&mut self, ui: &Ui) {
+        if hotkeys_enabled() {
+            self.0.interact(ui);
+        }
+    }
+
+    fn log(&mut self, tx: Sender<String>) {
+        self.0.log(tx);
+    }
+}
+
+pub(crate) fn hotkeys(widget: Box<dyn Widget>) -> Box<dyn Widget> {
+    Box::new(Hotkeys(widget))
+}
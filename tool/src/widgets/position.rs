@@ -1,15 +1,147 @@
 use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use libdsr::memedit::PointerChain;
+use imgui::Ui;
+use libdsr::memedit::{Bitflag, PointerChain};
+use libdsr::prelude::PointerChains;
+use practice_tool_core::crossbeam_channel::Sender;
 use practice_tool_core::key::Key;
 use practice_tool_core::widgets::nudge_position::NudgePositionStorage;
 use practice_tool_core::widgets::position::{Position, PositionStorage};
 use practice_tool_core::widgets::Widget;
 
+use crate::indicators::{HudProvider, Indicator, IndicatorRegistry};
+use crate::palette::{hotkeys_enabled, PaletteAction, PaletteProvider, PaletteRegistry};
+use crate::positions::{self, bonfire_in_map, map_name, same_block, LoadPlan, StoredPosition};
+use crate::restore::RestoreRegistry;
+
+/// How long a warp may take before the pending position is dropped.
+const WARP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Keeps loads of a saved position in the map it was saved in. Positions in
+/// another map are reached by warping to the bonfire the player had last
+/// rested at when saving, and applied once the map has loaded.
+#[derive(Clone)]
+pub(super) struct Teleport {
+    ptr_angle: PointerChain<f32>,
+    ptr_pos: PointerChain<[f32; 3]>,
+    map_id: PointerChain<u32>,
+    last_bonfire: PointerChain<u32>,
+    player_ins: PointerChain<usize>,
+    warp: Bitflag<u8>,
+    pending: Arc<Mutex<Option<PendingWarp>>>,
+    restore: RestoreRegistry,
+    logs: Arc<Mutex<Vec<String>>>,
+}
+
+/// A position waiting on a warp to its map.
+#[derive(Clone, Copy)]
+struct PendingWarp {
+    stored: StoredPosition,
+    started: Instant,
+    /// Last bonfire before the warp, put back if it times out.
+    previous_bonfire: Option<u32>,
+}
+
+impl Teleport {
+    fn new(chains: &PointerChains, restore: &RestoreRegistry) -> Self {
+        Teleport {
+            ptr_angle: chains.position.0.clone(),
+            ptr_pos: chains.position.1.clone(),
+            map_id: chains.map_id.clone(),
+            last_bonfire: chains.last_bonfire.clone(),
+            player_ins: chains.player_ins.clone(),
+            warp: chains.bonfire_warp_menu.clone(),
+            pending: Default::default(),
+            restore: restore.clone(),
+            logs: Default::default(),
+        }
+    }
+
+    fn stamp(&self, pos: [f32; 3], angle: f32) -> StoredPosition {
+        let map_id = self.map_id.read().filter(|&id| id != 0);
+        let mut bonfire = self.last_bonfire.read();
+        if let (Some(map_id), Some(id)) = (map_id, bonfire) {
+            if !bonfire_in_map(id, map_id) {
+                bonfire = None;
+                self.log(format!(
+                    "Last bonfire isn't in {}: the position only loads from that map",
+                    map_name(map_id)
+                ));
+            }
+        }
+        StoredPosition { pos, angle, map_id, bonfire }
+    }
+
+    fn load(&self, stored: StoredPosition) {
+        let current = self.map_id.read().filter(|&id| id != 0);
+        match stored.plan_load(current) {
+            LoadPlan::Apply => self.apply(&stored),
+            LoadPlan::Warp(bonfire) => {
+                let previous_bonfire = self.last_bonfire.read();
+                self.restore.record("Last bonfire", &self.last_bonfire);
+                self.restore.record("Bonfire warp menu", &self.warp);
+                self.last_bonfire.write(bonfire);
+                self.warp.set(true);
+                *self.pending.lock().unwrap() =
+                    Some(PendingWarp { stored, started: Instant::now(), previous_bonfire });
+                self.log(format!("Warping to bonfire {bonfire} to reach the saved position"));
+            }
+            LoadPlan::Refuse => self.log(format!(
+                "Not loading a position saved in {}: no bonfire to warp there from {}",
+                stored.map_id.map(map_name).unwrap_or_default(),
+                current.map(map_name).unwrap_or_else(|| "a loading screen".to_string())
+            )),
+        }
+    }
+
+    fn apply(&self, stored: &StoredPosition) {
+        self.ptr_pos.write(stored.pos);
+        self.ptr_angle.write(stored.angle);
+    }
+
+    /// Applies a position waiting on a warp, once the player has loaded in
+    /// its map.
+    fn poll(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        let Some(PendingWarp { stored, started, previous_bonfire }) = *pending else {
+            return;
+        };
+
+        if started.elapsed() > WARP_TIMEOUT {
+            *pending = None;
+            drop(pending);
+            if let Some(bonfire) = previous_bonfire {
+                self.last_bonfire.write(bonfire);
+            }
+            self.log("Gave up on the saved position: the warp didn't reach its map".to_string());
+            return;
+        }
+
+        let loaded = self.player_ins.read().is_some_and(|p| p != 0);
+        let arrived = match (self.map_id.read(), stored.map_id) {
+            (Some(current), Some(saved)) => same_block(current, saved),
+            _ => false,
+        };
+        if loaded && arrived {
+            *pending = None;
+            self.apply(&stored);
+        }
+    }
+
+    fn log(&self, message: String) {
+        self.logs.lock().unwrap().push(message);
+    }
+}
+
+#[derive(Clone)]
 pub(super) struct SavePosition {
     ptr_angle: PointerChain<f32>,
     ptr_pos: PointerChain<[f32; 3]>,
-    saved_position: [f32; 4],
+    // Shared with the HUD indicator and the palette actions.
+    saved_position: Arc<Mutex<Option<StoredPosition>>>,
+    teleport: Option<Teleport>,
     label_current: String,
     label_stored: String,
     valid: bool,
@@ -21,7 +153,8 @@ impl SavePosition {
         Self {
             ptr_angle: ptr.0,
             ptr_pos: ptr.1,
-            saved_position: [0.0; 4],
+            saved_position: Default::default(),
+            teleport: None,
             label_current: String::new(),
             label_stored: String::new(),
             valid: false,
@@ -30,10 +163,39 @@ impl SavePosition {
     }
 }
 
+impl SavePosition {
+    /// Moves by `nudge` units along `[forward, right, up]`, relative to the
+    /// direction the character faces.
+    pub(super) fn nudge_relative(&mut self, direction: [f32; 3]) {
+        if let (Some(pos), Some(angle)) = (self.ptr_pos.read(), self.ptr_angle.read()) {
+            let offset = direction.map(|d| d * self.nudge);
+            self.ptr_pos.write(positions::offset_relative(pos, angle, offset));
+        }
+    }
+
+    /// Moves by `nudge` units along the world `[x, y, z]` axes.
+    pub(super) fn nudge_absolute(&mut self, direction: [f32; 3]) {
+        if let Some(pos) = self.ptr_pos.read() {
+            self.ptr_pos.write([0, 1, 2].map(|i| pos[i] + direction[i] * self.nudge));
+        }
+    }
+
+    /// Turns the character by `degrees` to the right.
+    pub(super) fn rotate(&mut self, degrees: f32) {
+        if let Some(angle) = self.ptr_angle.read() {
+            self.ptr_angle.write(positions::rotate(angle, degrees));
+        }
+    }
+}
+
 impl PositionStorage for SavePosition {
     fn save(&mut self) {
         if let (Some(pos), Some(angle)) = (self.ptr_pos.read(), self.ptr_angle.read()) {
-            self.saved_position = [pos[0], pos[1], pos[2], angle];
+            let stored = match &self.teleport {
+                Some(teleport) => teleport.stamp(pos, angle),
+                None => StoredPosition { pos, angle, map_id: None, bonfire: None },
+            };
+            *self.saved_position.lock().unwrap() = Some(stored);
             self.valid = true;
         } else {
             self.valid = false;
@@ -41,12 +203,16 @@ impl PositionStorage for SavePosition {
     }
 
     fn load(&mut self) {
-        self.ptr_pos.write([
-            self.saved_position[0],
-            self.saved_position[1],
-            self.saved_position[2],
-        ]);
-        self.ptr_angle.write(self.saved_position[3]);
+        let Some(stored) = *self.saved_position.lock().unwrap() else {
+            return;
+        };
+        match &self.teleport {
+            Some(teleport) => teleport.load(stored),
+            None => {
+                self.ptr_pos.write(stored.pos);
+                self.ptr_angle.write(stored.angle);
+            }
+        }
     }
 
     fn display_current(&mut self) -> &str {
@@ -76,9 +242,13 @@ impl PositionStorage for SavePosition {
     fn display_stored(&mut self) -> &str {
         self.label_stored.clear();
 
-        let [x, y, z, a] = self.saved_position;
+        let stored = *self.saved_position.lock().unwrap();
+        let ([x, y, z], a) = stored.map(|s| (s.pos, s.angle)).unwrap_or_default();
 
         write!(self.label_stored, "{:7.1} {:7.1} {:7.1} {:7.1}", x, y, z, a).ok();
+        if let Some(map_id) = stored.and_then(|s| s.map_id) {
+            write!(self.label_stored, " {}", map_name(map_id)).ok();
+        }
 
         &self.label_stored
     }
@@ -102,10 +272,129 @@ impl NudgePositionStorage for SavePosition {
     }
 }
 
+impl HudProvider for SavePosition {
+    fn hud_indicators(&self) -> Vec<(&'static str, Box<dyn Indicator>)> {
+        vec![(
+            "saved_position",
+            Box::new(SavedPositionIndicator {
+                saved_position: Arc::clone(&self.saved_position),
+                buf: String::new(),
+            }),
+        )]
+    }
+}
+
+impl PaletteProvider for SavePosition {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+        vec![
+            (
+                "Save position".to_string(),
+                Box::new(PositionAction { storage: self.clone(), save: true }),
+            ),
+            (
+                "Load position".to_string(),
+                Box::new(PositionAction { storage: self.clone(), save: false }),
+            ),
+        ]
+    }
+}
+
+/// Saves or loads the position of a position command.
+struct PositionAction {
+    storage: SavePosition,
+    save: bool,
+}
+
+impl PaletteAction for PositionAction {
+    fn run(&mut self) -> Result<(), String> {
+        if self.save {
+            self.storage.save();
+            if !self.storage.valid {
+                return Err("Couldn't read the position".to_string());
+            }
+        } else {
+            if self.storage.saved_position.lock().unwrap().is_none() {
+                return Err("No saved position".to_string());
+            }
+            self.storage.load();
+        }
+        Ok(())
+    }
+}
+
+struct SavedPositionIndicator {
+    saved_position: Arc<Mutex<Option<StoredPosition>>>,
+    buf: String,
+}
+
+impl Indicator for SavedPositionIndicator {
+    fn label(&self) -> &str {
+        "Saved position"
+    }
+
+    fn render(&mut self, ui: &Ui) {
+        self.buf.clear();
+        match *self.saved_position.lock().unwrap() {
+            Some(StoredPosition { pos: [x, y, z], angle: a, map_id, .. }) => {
+                write!(self.buf, "Saved {:7.1} {:7.1} {:7.1} {:7.1}", x, y, z, a).ok();
+                if let Some(map_id) = map_id {
+                    write!(self.buf, " {}", map_name(map_id)).ok();
+                }
+            }
+            None => {
+                write!(self.buf, "Saved (empty)").ok();
+            }
+        };
+        ui.text(&self.buf);
+    }
+}
+
+/// Forwards to the core position widget, and finishes loads that had to
+/// warp to another map first.
+struct MapAwarePosition {
+    inner: Box<dyn Widget>,
+    teleport: Teleport,
+}
+
+impl Widget for MapAwarePosition {
+    fn render(&mut self, ui: &Ui) {
+        self.inner.render(ui);
+    }
+
+    fn render_closed(&mut self, ui: &Ui) {
+        self.inner.render_closed(ui);
+    }
+
+    fn interact(&mut self, ui: &Ui) {
+        if hotkeys_enabled() {
+            self.inner.interact(ui);
+        }
+        self.teleport.poll();
+    }
+
+    fn log(&mut self, tx: Sender<String>) {
+        for log in self.teleport.logs.lock().unwrap().drain(..) {
+            tx.send(log).ok();
+        }
+        self.inner.log(tx);
+    }
+}
+
 pub(crate) fn save_position(
-    ptr: (PointerChain<f32>, PointerChain<[f32; 3]>),
+    chains: &PointerChains,
     key_load: Option<Key>,
     key_save: Option<Key>,
+    indicators: &mut IndicatorRegistry,
+    restore: &RestoreRegistry,
+    palette: &mut PaletteRegistry,
 ) -> Box<dyn Widget> {
-    Box::new(Position::new(SavePosition::new(ptr, 0.0), key_load, key_save))
+    let teleport = Teleport::new(chains, restore);
+    let mut save_position = SavePosition::new(chains.position.clone(), 0.0);
+    save_position.teleport = Some(teleport.clone());
+    indicators.register(&save_position);
+    palette.register(&save_position);
+    Box::new(MapAwarePosition {
+        inner: Box::new(Position::new(save_position, key_load, key_save)),
+        teleport,
+    })
 }
@@ -0,0 +1,222 @@
+use std::path::PathBuf;
+
+use imgui::{TreeNodeFlags, Ui};
+use libdsr::memedit::PointerChain;
+use practice_tool_core::crossbeam_channel::Sender;
+use practice_tool_core::key::Key;
+use practice_tool_core::widgets::Widget;
+
+use crate::palette::{PaletteAction, PaletteProvider, PaletteRegistry, Trigger};
+use crate::positions::{map_name, NamedPosition, PositionLibrary};
+use crate::util;
+
+const LIBRARY_FILE: &str = "dark_souls_remastered_tool.positions.json";
+const EXPORT_FILE: &str = "positions_export.json";
+
+/// Browser for the named positions of the current map, with teleport and
+/// import/export of the library.
+struct PositionLibraryWidget {
+    ptr_angle: PointerChain<f32>,
+    ptr_pos: PointerChain<[f32; 3]>,
+    map_id: PointerChain<u32>,
+    key_teleport: Option<Key>,
+    /// Pulled by the palette to teleport to the selected position.
+    trigger: Trigger,
+    library: PositionLibrary,
+    library_path: Option<PathBuf>,
+    dir: Option<PathBuf>,
+    map: Option<String>,
+    selected: Option<String>,
+    name: String,
+    transfer_file: String,
+    logs: Vec<String>,
+}
+
+impl PositionLibraryWidget {
+    fn save_library(&mut self) {
+        if let Some(path) = &self.library_path {
+            if let Err(e) = self.library.save(path) {
+                self.logs.push(e);
+            }
+        }
+    }
+
+    fn save_current(&mut self, map: &str) {
+        let (Some(pos), Some(angle)) = (self.ptr_pos.read(), self.ptr_angle.read()) else {
+            self.logs.push("Couldn't read the current position".to_string());
+            return;
+        };
+
+        let name = self.name.trim().to_string();
+        self.library.insert(map, NamedPosition { name: name.clone(), pos, angle });
+        self.logs.push(format!("Saved \"{name}\" in {map}"));
+        self.selected = Some(name);
+        self.name.clear();
+        self.save_library();
+    }
+
+    fn teleport(&mut self) {
+        let (Some(map), Some(name)) = (&self.map, &self.selected) else {
+            return;
+        };
+        let Some(position) = self.library.positions(map).iter().find(|p| &p.name == name) else {
+            return;
+        };
+
+        self.ptr_pos.write(position.pos);
+        self.ptr_angle.write(position.angle);
+    }
+
+    fn transfer_path(&self) -> Option<PathBuf> {
+        Some(self.dir.as_ref()?.join(self.transfer_file.trim()))
+    }
+
+    fn import(&mut self) {
+        let Some(path) = self.transfer_path() else {
+            return;
+        };
+        match self.library.import(&path) {
+            Ok(count) => {
+                self.logs.push(format!("Imported {count} positions from {}", path.display()));
+                self.save_library();
+            }
+            Err(e) => self.logs.push(e),
+        }
+    }
+
+    fn export(&mut self) {
+        let Some(path) = self.transfer_path() else {
+            return;
+        };
+        match self.library.save(&path) {
+            Ok(()) => self.logs.push(format!("Exported positions to {}", path.display())),
+            Err(e) => self.logs.push(e),
+        }
+    }
+}
+
+impl Widget for PositionLibraryWidget {
+    fn render(&mut self, ui: &Ui) {
+        if !ui.collapsing_header("Position library", TreeNodeFlags::empty()) {
+            return;
+        }
+
+        self.map = self.map_id.read().filter(|&id| id != 0).map(map_name);
+        let Some(map) = self.map.clone() else {
+            ui.text_disabled("Not in a map");
+            return;
+        };
+        ui.text(format!("Positions in {map}"));
+
+        let mut selected = None;
+        ui.child_window("##positions").size([320., 120.]).build(|| {
+            for position in self.library.positions(&map) {
+                let is_selected = self.selected.as_ref() == Some(&position.name);
+                if ui.selectable_config(&position.name).selected(is_selected).build() {
+                    selected = Some(position.name.clone());
+                }
+            }
+        });
+        if selected.is_some() {
+            self.selected = selected;
+        }
+
+        let button_width = (320. - ui.clone_style().item_spacing[0]) / 2.;
+        let has_selection = self
+            .selected
+            .as_ref()
+            .is_some_and(|s| self.library.positions(&map).iter().any(|p| &p.name == s));
+        if has_selection {
+            if ui.button_with_size("Teleport", [button_width, 0.]) {
+                self.teleport();
+            }
+            ui.same_line();
+            if ui.button_with_size("Delete", [button_width, 0.]) {
+                if let Some(name) = self.selected.take() {
+                    self.library.remove(&map, &name);
+                    self.save_library();
+                }
+            }
+        }
+
+        ui.set_next_item_width(button_width);
+        ui.input_text("##position_name", &mut self.name).hint("Name").build();
+        ui.same_line();
+        ui.disabled(self.name.trim().is_empty(), || {
+            if ui.button_with_size("Save current", [button_width, 0.]) {
+                self.save_current(&map);
+            }
+        });
+
+        ui.separator();
+        ui.set_next_item_width(320.);
+        ui.input_text("##position_file", &mut self.transfer_file).build();
+        if ui.button_with_size("Import", [button_width, 0.]) {
+            self.import();
+        }
+        ui.same_line();
+        if ui.button_with_size("Export", [button_width, 0.]) {
+            self.export();
+        }
+    }
+
+    fn interact(&mut self, ui: &Ui) {
+        if self.key_teleport.is_some_and(|key| key.is_pressed(ui)) || self.trigger.take() {
+            self.map = self.map_id.read().filter(|&id| id != 0).map(map_name);
+            self.teleport();
+        }
+    }
+
+    fn log(&mut self, tx: Sender<String>) {
+        for log in self.logs.drain(..) {
+            tx.send(log).ok();
+        }
+    }
+}
+
+impl PaletteProvider for PositionLibraryWidget {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+        vec![("Teleport to selected position".to_string(), Box::new(self.trigger.clone()))]
+    }
+}
+
+pub(crate) fn position_library(
+    ptr: (PointerChain<f32>, PointerChain<[f32; 3]>),
+    map_id: PointerChain<u32>,
+    key_teleport: Option<Key>,
+    palette: &mut PaletteRegistry,
+) -> Box<dyn Widget> {
+    let dir = util::get_dll_path().map(|mut path| {
+        path.pop();
+        path
+    });
+    let library_path = dir.as_ref().map(|dir| dir.join(LIBRARY_FILE));
+
+    let mut logs = Vec::new();
+    let library = match library_path.as_deref().map(PositionLibrary::load) {
+        Some(Ok(library)) => library,
+        Some(Err(e)) => {
+            logs.push(e);
+            PositionLibrary::default()
+        }
+        None => PositionLibrary::default(),
+    };
+
+    let widget = PositionLibraryWidget {
+        ptr_angle: ptr.0,
+        ptr_pos: ptr.1,
+        map_id,
+        key_teleport,
+        trigger: Trigger::default(),
+        library,
+        library_path,
+        dir,
+        map: None,
+        selected: None,
+        name: String::new(),
+        transfer_file: EXPORT_FILE.to_string(),
+        logs,
+    };
+    palette.register(&widget);
+    Box::new(widget)
+}
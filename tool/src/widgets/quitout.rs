@@ -0,0 +1,47 @@
+use libdsr::prelude::PointerChain;
+use practice_tool_core::key::Key;
+use practice_tool_core::widgets::store_value::{ReadWrite, StoreValue};
+use practice_tool_core::widgets::Widget;
+
+use crate::palette::{PaletteAction, PaletteProvider, PaletteRegistry};
+
+#[derive(Debug, Clone)]
+struct Quitout {
+    ptr: PointerChain<u8>,
+}
+
+impl ReadWrite for Quitout {
+    fn read(&mut self) -> bool {
+        self.ptr.read().is_some()
+    }
+
+    fn write(&mut self) {
+        self.ptr.write(1);
+    }
+
+    fn label(&self) -> &str {
+        "Quitout"
+    }
+}
+
+impl PaletteAction for Quitout {
+    fn run(&mut self) -> Result<(), String> {
+        self.ptr.write(1).ok_or_else(|| "Couldn't quit out".to_string())
+    }
+}
+
+impl PaletteProvider for Quitout {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+        vec![(self.label().to_string(), Box::new(self.clone()))]
+    }
+}
+
+pub(crate) fn quitout(
+    ptr: PointerChain<u8>,
+    key: Option<Key>,
+    palette: &mut PaletteRegistry,
+) -> Box<dyn Widget> {
+    let quitout = Quitout { ptr };
+    palette.register(&quitout);
+    Box::new(StoreValue::new(quitout, key))
+}
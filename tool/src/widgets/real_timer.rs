@@ -0,0 +1,188 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use imgui::Ui;
+use libdsr::prelude::*;
+use practice_tool_core::key::Key;
+use practice_tool_core::widgets::Widget;
+
+use crate::indicators::{HudProvider, Indicator, IndicatorRegistry};
+use crate::palette::{hotkeys_enabled, PaletteAction, PaletteProvider, PaletteRegistry};
+use crate::splits::realtime::{millis, LoadSource, RealTimer};
+use crate::splits::{write_time, IgtSource};
+
+struct GameSource {
+    igt: PointerChain<u32>,
+    player_ins: PointerChain<usize>,
+}
+
+impl IgtSource for GameSource {
+    fn igt(&self) -> Option<u32> {
+        self.igt.read()
+    }
+}
+
+impl LoadSource for GameSource {
+    fn is_loading(&self) -> bool {
+        self.player_ins.read().is_none_or(|p| p == 0)
+    }
+}
+
+struct RealTimerWidget {
+    timer: Arc<Mutex<RealTimer>>,
+    game: GameSource,
+    key_pause: Option<Key>,
+    key_reset: Option<Key>,
+    label: String,
+}
+
+impl Widget for RealTimerWidget {
+    fn render(&mut self, ui: &Ui) {
+        let button_width = (320. - ui.clone_style().item_spacing[0]) / 2.;
+        let mut timer = self.timer.lock().unwrap();
+
+        self.label.clear();
+        self.label.push_str("RTA ");
+        write_time(&mut self.label, millis(timer.real_time()));
+        self.label.push_str("  LRT ");
+        write_time(&mut self.label, millis(timer.load_removed_time()));
+        ui.text(&self.label);
+
+        let pause = if timer.is_paused() { "Resume" } else { "Pause" };
+        if ui.button_with_size(pause, [button_width, 0.]) {
+            timer.toggle_pause();
+        }
+        ui.same_line();
+        if ui.button_with_size("Reset timer", [button_width, 0.]) {
+            timer.reset();
+        }
+    }
+
+    fn interact(&mut self, ui: &Ui) {
+        let mut timer = self.timer.lock().unwrap();
+
+        if hotkeys_enabled() {
+            if self.key_pause.map(|k| k.is_pressed(ui)).unwrap_or(false) {
+                timer.toggle_pause();
+            }
+            if self.key_reset.map(|k| k.is_pressed(ui)).unwrap_or(false) {
+                timer.reset();
+            }
+        }
+
+        timer.update(Instant::now(), &self.game);
+    }
+}
+
+impl HudProvider for RealTimerWidget {
+    fn hud_indicators(&self) -> Vec<(&'static str, Box<dyn Indicator>)> {
+        vec![
+            (
+                "rta",
+                Box::new(TimeIndicator {
+                    timer: Arc::clone(&self.timer),
+                    load_removed: false,
+                    buf: String::new(),
+                }),
+            ),
+            (
+                "load_removed",
+                Box::new(TimeIndicator {
+                    timer: Arc::clone(&self.timer),
+                    load_removed: true,
+                    buf: String::new(),
+                }),
+            ),
+        ]
+    }
+}
+
+impl PaletteProvider for RealTimerWidget {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+        vec![
+            (
+                "Pause real timer".to_string(),
+                Box::new(TimerAction { timer: Arc::clone(&self.timer), reset: false }),
+            ),
+            (
+                "Reset real timer".to_string(),
+                Box::new(TimerAction { timer: Arc::clone(&self.timer), reset: true }),
+            ),
+        ]
+    }
+}
+
+/// Pauses, resumes or resets the real timer.
+struct TimerAction {
+    timer: Arc<Mutex<RealTimer>>,
+    reset: bool,
+}
+
+impl PaletteAction for TimerAction {
+    fn run(&mut self) -> Result<(), String> {
+        let mut timer = self.timer.lock().unwrap();
+        if self.reset {
+            timer.reset();
+        } else {
+            timer.toggle_pause();
+        }
+        Ok(())
+    }
+}
+
+/// Shows either the real time or the load-removed time.
+struct TimeIndicator {
+    timer: Arc<Mutex<RealTimer>>,
+    load_removed: bool,
+    buf: String,
+}
+
+impl Indicator for TimeIndicator {
+    fn label(&self) -> &str {
+        if self.load_removed {
+            "Load-removed time"
+        } else {
+            "Real time"
+        }
+    }
+
+    fn render(&mut self, ui: &Ui) {
+        let timer = self.timer.lock().unwrap();
+
+        self.buf.clear();
+        if self.load_removed {
+            self.buf.push_str("LRT ");
+            write_time(&mut self.buf, millis(timer.load_removed_time()));
+        } else {
+            self.buf.push_str("RTA ");
+            write_time(&mut self.buf, millis(timer.real_time()));
+        }
+
+        if timer.is_paused() {
+            self.buf.push_str(" (paused)");
+        } else if self.load_removed && timer.is_loading() {
+            self.buf.push_str(" (loading)");
+        }
+        ui.text(&self.buf);
+    }
+}
+
+pub(crate) fn real_timer(
+    chains: &PointerChains,
+    key_pause: Option<Key>,
+    key_reset: Option<Key>,
+    indicators: &mut IndicatorRegistry,
+    palette: &mut PaletteRegistry,
+) -> Box<dyn Widget> {
+    let widget = RealTimerWidget {
+        timer: Arc::new(Mutex::new(RealTimer::default())),
+        game: GameSource { igt: chains.igt.clone(), player_ins: chains.player_ins.clone() },
+        key_pause,
+        key_reset,
+        label: String::new(),
+    };
+
+    indicators.register(&widget);
+    palette.register(&widget);
+    Box::new(widget)
+}
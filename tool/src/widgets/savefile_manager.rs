@@ -1,24 +1,553 @@
+use std::fmt::Write;
 use std::path::PathBuf;
+use std::time::SystemTime;
 
+use imgui::{TreeNodeFlags, Ui};
+use libdsr::prelude::PointerChain;
+use libdsr::savefile::{SlotSummary, SLOT_COUNT};
+use practice_tool_core::crossbeam_channel::Sender;
 use practice_tool_core::key::Key;
 use practice_tool_core::widgets::savefile_manager::SavefileManager;
 use practice_tool_core::widgets::Widget;
 
-pub(crate) fn savefile_manager(key_load: Option<Key>, key_close: Key) -> Box<dyn Widget> {
-    Box::new(SavefileManager::new(key_load, Some(key_close), get_savefile_path().unwrap()))
+use crate::palette::{PaletteAction, PaletteProvider, PaletteRegistry, Trigger};
+use crate::savefile::backup::{verify_file, write_savefile, Backup, BackupSettings, Backups};
+use crate::savefile::library::{Library, SaveMetadata};
+use crate::savefile::{export_slot, find_accounts, import_slot, list_exports, saves_root, Account};
+use crate::util;
+
+/// Wraps the core savefile manager with a choice of Steam account, a listing
+/// of the character slots in its save, export/import of single slots and a
+/// searchable library of practice saves. The save is backed up before
+/// every overwrite, and the backups can be restored from here.
+///
+/// With a quitout pointer, every load is followed by a quitout so the game
+/// picks the new save up right away.
+struct SavefileManagerWidget {
+    key_load: Option<Key>,
+    key_close: Key,
+    quitout: Option<PointerChain<u8>>,
+    accounts: Vec<Account>,
+    labels: Vec<String>,
+    selected: usize,
+    slots: Result<Vec<SlotSummary>, String>,
+    manager: Option<SavefileManager>,
+    error: Option<String>,
+    exports_dir: Option<PathBuf>,
+    exports: Vec<PathBuf>,
+    export_labels: Vec<String>,
+    import_file: usize,
+    import_slot: usize,
+    library: Option<Library>,
+    library_saves: Vec<String>,
+    library_stale: bool,
+    query: String,
+    results: Vec<String>,
+    editing: Option<(String, SaveMetadata, String)>,
+    /// Pulled by the palette to load the selected library save.
+    trigger_load: Trigger,
+    /// Pulled by the palette to import the selected slot file.
+    trigger_import: Trigger,
+    backup_settings: BackupSettings,
+    backups: Option<Backups>,
+    backup_list: Vec<Backup>,
+    backup_labels: Vec<String>,
+    restore_index: usize,
+    verify_error: Option<String>,
+    logs: Vec<String>,
 }
 
-fn get_savefile_path() -> Result<PathBuf, String> {
-    let re = regex::Regex::new(r"^[a-f0-9]+$").unwrap();
-    let savefile_path: PathBuf =
-        [std::env::var("USERPROFILE").map_err(|e| format!("{}", e))?.as_str(), "Documents", "NBGI", "DARK SOULS REMASTERED"]
+impl SavefileManagerWidget {
+    fn new(
+        key_load: Option<Key>,
+        key_close: Key,
+        quitout: Option<PointerChain<u8>>,
+        backup_settings: BackupSettings,
+    ) -> Self {
+        let (accounts, error) = match saves_root().and_then(|root| find_accounts(&root)) {
+            Ok(accounts) => (accounts, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        let labels = accounts.iter().map(|a| a.id.clone()).collect();
+        let exports_dir = util::get_dll_path().map(|mut path| {
+            path.pop();
+            path.push("slots");
+            path
+        });
+
+        let mut widget = SavefileManagerWidget {
+            key_load,
+            key_close,
+            quitout,
+            accounts,
+            labels,
+            selected: 0,
+            slots: Ok(Vec::new()),
+            manager: None,
+            error,
+            exports_dir,
+            exports: Vec::new(),
+            export_labels: Vec::new(),
+            import_file: 0,
+            import_slot: 0,
+            library: None,
+            library_saves: Vec::new(),
+            library_stale: true,
+            query: String::new(),
+            results: Vec::new(),
+            editing: None,
+            trigger_load: Trigger::default(),
+            trigger_import: Trigger::default(),
+            backup_settings,
+            backups: None,
+            backup_list: Vec::new(),
+            backup_labels: Vec::new(),
+            restore_index: 0,
+            verify_error: None,
+            logs: Vec::new(),
+        };
+        widget.select(0);
+        widget.refresh_exports();
+        widget
+    }
+
+    fn select(&mut self, index: usize) {
+        let Some(account) = self.accounts.get(index) else {
+            return;
+        };
+
+        self.selected = index;
+        self.slots = account.slots();
+        self.manager = Some(SavefileManager::new(
+            self.key_load,
+            Some(self.key_close),
+            account.savefile_path(),
+        ));
+
+        self.library = match Library::load(&account.dir) {
+            Ok(library) => Some(library),
+            Err(e) => {
+                self.logs.push(e);
+                None
+            }
+        };
+        self.editing = None;
+        self.library_stale = true;
+
+        self.backups = Some(Backups::new(&account.dir, self.backup_settings));
+        self.refresh_backups();
+    }
+
+    fn refresh_backups(&mut self) {
+        let (Some(account), Some(backups)) = (self.accounts.get(self.selected), &self.backups)
+        else {
+            return;
+        };
+
+        self.backup_list = backups.list();
+        self.backup_labels = self
+            .backup_list
+            .iter()
+            .map(|b| format!("{} ({} kB)", b.label, b.size.div_ceil(1024)))
+            .collect();
+        self.restore_index = 0;
+
+        let path = account.savefile_path();
+        self.verify_error = if path.is_file() { verify_file(&path).err() } else { None };
+    }
+
+    fn render_backups(&mut self, ui: &Ui) {
+        if !ui.collapsing_header("Backups", TreeNodeFlags::empty()) {
+            return;
+        }
+        if self.backup_list.is_empty() {
+            ui.text_disabled("No backups yet");
+            return;
+        }
+
+        ui.set_next_item_width(260.);
+        ui.combo_simple_string("##backup", &mut self.restore_index, &self.backup_labels);
+        ui.same_line();
+        if ui.button("Restore") {
+            self.restore();
+        }
+    }
+
+    fn restore(&mut self) {
+        let (Some(account), Some(backups), Some(backup)) = (
+            self.accounts.get(self.selected),
+            &self.backups,
+            self.backup_list.get(self.restore_index),
+        ) else {
+            return;
+        };
+
+        match backups.restore(&backup.path, &account.savefile_path()) {
+            Ok(()) => {
+                self.logs.push(format!("Restored backup from {}", backup.label));
+                self.slots = account.slots();
+                self.quitout();
+            }
+            Err(e) => self.logs.push(e),
+        }
+        self.refresh_backups();
+    }
+
+    /// Lists the saves on disk again. Scanning walks the account folder, so
+    /// it's only done when the menu opens, not while typing a search.
+    fn scan_library(&mut self) {
+        let Some(library) = &self.library else {
+            return;
+        };
+        self.library_saves = library.scan();
+        self.library_stale = false;
+        self.filter_library();
+    }
+
+    fn filter_library(&mut self) {
+        let Some(library) = &self.library else {
+            return;
+        };
+        self.results = library
+            .search(&self.library_saves, &self.query)
+            .into_iter()
+            .map(String::from)
+            .collect();
+    }
+
+    fn render_library(&mut self, ui: &Ui) {
+        if self.library.is_none() || !ui.collapsing_header("Library", TreeNodeFlags::empty()) {
+            return;
+        }
+
+        ui.set_next_item_width(320.);
+        if ui
+            .input_text("##library_search", &mut self.query)
+            .hint("Search: text, #tag, sl30")
+            .build()
+        {
+            self.filter_library();
+        }
+
+        let mut clicked = None;
+        ui.child_window("##library_results").size([320., 120.]).build(|| {
+            for save in &self.results {
+                let selected = self.editing.as_ref().is_some_and(|(s, _, _)| s == save);
+                if ui.selectable_config(save).selected(selected).build() {
+                    clicked = Some(save.clone());
+                }
+            }
+        });
+
+        if let (Some(save), Some(library)) = (clicked, &self.library) {
+            let metadata = library.get(&save);
+            let tags = metadata.tags.join(", ");
+            self.editing = Some((save, metadata, tags));
+        }
+
+        let Some((save, metadata, tags)) = &mut self.editing else {
+            return;
+        };
+
+        ui.text(match metadata.level {
+            Some(level) => format!("{save} (SL{level})"),
+            None => save.clone(),
+        });
+        ui.set_next_item_width(320.);
+        ui.input_text("##route", &mut metadata.route).hint("Route").build();
+        ui.set_next_item_width(320.);
+        ui.input_text("##segment", &mut metadata.segment).hint("Segment").build();
+        ui.set_next_item_width(320.);
+        ui.input_text("##area", &mut metadata.area).hint("Area").build();
+        ui.set_next_item_width(320.);
+        ui.input_text("##tags", tags).hint("Tags, comma separated").build();
+        ui.input_text_multiline("##notes", &mut metadata.notes, [320., 60.]).build();
+
+        let button_width = (320. - ui.clone_style().item_spacing[0]) / 2.;
+        let save_metadata = ui.button_with_size("Save metadata", [button_width, 0.]);
+        ui.same_line();
+        let load = ui.button_with_size("Load save", [button_width, 0.]);
+
+        if save_metadata {
+            metadata.tags = tags
+                .split(',')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(String::from)
+                .collect();
+            if let Some(library) = &mut self.library {
+                library.set(save, metadata.clone());
+                if let Err(e) = library.save() {
+                    self.logs.push(e);
+                }
+            }
+        }
+        let save = save.clone();
+        if save_metadata {
+            self.filter_library();
+        }
+        if load {
+            self.load_library_save(&save);
+        }
+    }
+
+    fn load_library_save(&mut self, save: &str) {
+        let (Some(account), Some(library), Some(backups)) =
+            (self.accounts.get(self.selected), &self.library, &self.backups)
+        else {
+            return;
+        };
+
+        let result = std::fs::read(library.path(save))
+            .map_err(|e| e.to_string())
+            .and_then(|data| write_savefile(&account.savefile_path(), &data, backups));
+        self.slots = account.slots();
+        match result {
+            Ok(()) => {
+                self.logs.push(format!("Loaded {save}"));
+                self.quitout();
+            }
+            Err(e) => self.logs.push(format!("Couldn't load {save}: {e}")),
+        }
+        self.refresh_backups();
+    }
+
+    /// Backs up the save ahead of a load that may not happen, see
+    /// [`Backups::copy`].
+    fn backup(&mut self) -> Option<PathBuf> {
+        let (Some(account), Some(backups)) = (self.accounts.get(self.selected), &self.backups)
+        else {
+            return None;
+        };
+        match backups.copy(&account.savefile_path()) {
+            Ok(backup) => backup,
+            Err(e) => {
+                self.logs.push(e);
+                None
+            }
+        }
+    }
+
+    /// Keeps the backup taken by [`Self::backup`] if the save was loaded,
+    /// and drops it otherwise.
+    fn settle_backup(&mut self, backup: Option<PathBuf>, loaded: bool) {
+        let (Some(backup), Some(backups)) = (backup, &self.backups) else {
+            return;
+        };
+        let result = if loaded { backups.prune() } else { backups.discard(&backup) };
+        if let Err(e) = result {
+            self.logs.push(e);
+        }
+    }
+
+    fn savefile_modified(&self) -> Option<SystemTime> {
+        let account = self.accounts.get(self.selected)?;
+        std::fs::metadata(account.savefile_path()).and_then(|m| m.modified()).ok()
+    }
+
+    fn quitout(&mut self) {
+        if let Some(quitout) = &self.quitout {
+            if quitout.write(1).is_none() {
+                self.logs.push("Couldn't quit out".to_string());
+            }
+        }
+    }
+
+    fn refresh_exports(&mut self) {
+        self.exports = self.exports_dir.as_deref().map(list_exports).unwrap_or_default();
+        self.export_labels = self
+            .exports
             .iter()
+            .map(|p| p.file_stem().unwrap_or_default().to_string_lossy().into_owned())
             .collect();
-    std::fs::read_dir(savefile_path)
-        .map_err(|e| format!("{}", e))?
-        .filter_map(|e| e.ok())
-        .find(|e| re.is_match(&e.file_name().to_string_lossy()) && e.path().is_dir())
-        .map(|e| e.path())
-        .map(|p| p.join("DRAKS0005.sl2"))
-        .ok_or_else(|| String::from("Couldn't find savefile path"))
+        self.import_file = self.import_file.min(self.exports.len().saturating_sub(1));
+    }
+
+    fn export(&mut self, index: usize) {
+        let (Some(account), Some(dir)) = (self.accounts.get(self.selected), &self.exports_dir)
+        else {
+            return;
+        };
+
+        match export_slot(&account.savefile_path(), index, dir) {
+            Ok(path) => self.logs.push(format!("Exported slot to {}", path.display())),
+            Err(e) => self.logs.push(e),
+        }
+        self.refresh_exports();
+    }
+
+    fn import(&mut self) {
+        let (Some(account), Some(file), Some(backups)) =
+            (self.accounts.get(self.selected), self.exports.get(self.import_file), &self.backups)
+        else {
+            return;
+        };
+
+        match import_slot(&account.savefile_path(), self.import_slot, file, backups) {
+            Ok(()) => self.logs.push(format!(
+                "Imported {} into slot {}",
+                self.export_labels[self.import_file],
+                self.import_slot + 1
+            )),
+            Err(e) => self.logs.push(e),
+        }
+        self.slots = account.slots();
+        self.refresh_backups();
+    }
+}
+
+impl Widget for SavefileManagerWidget {
+    fn render(&mut self, ui: &Ui) {
+        if self.library_stale {
+            self.scan_library();
+        }
+        if let Some(error) = &self.error {
+            ui.text_colored([0.9, 0.25, 0.2, 1.], format!("Savefiles: {error}"));
+            return;
+        }
+
+        if self.accounts.len() > 1 {
+            let mut selected = self.selected;
+            ui.set_next_item_width(320.);
+            if ui.combo_simple_string("##account", &mut selected, &self.labels) {
+                self.select(selected);
+            }
+        }
+
+        if let Some(e) = &self.verify_error {
+            ui.text_colored([0.9, 0.6, 0.2, 1.], format!("Save is damaged: {e}"));
+            ui.text_colored([0.9, 0.6, 0.2, 1.], "Restore a backup below.");
+        }
+
+        let mut export = None;
+        match &self.slots {
+            Ok(slots) => {
+                let mut buf = String::new();
+                for slot in slots {
+                    buf.clear();
+                    let secs = slot.playtime / 1000;
+                    write!(
+                        buf,
+                        "{}. {} (SL{}) {}:{:02}:{:02}",
+                        slot.index + 1,
+                        slot.name,
+                        slot.level,
+                        secs / 3600,
+                        secs / 60 % 60,
+                        secs % 60
+                    )
+                    .ok();
+                    ui.text(&buf);
+                    ui.same_line_with_pos(260.);
+                    if ui.small_button(format!("Export##slot_{}", slot.index)) {
+                        export = Some(slot.index);
+                    }
+                }
+            }
+            Err(e) => ui.text_disabled(e),
+        }
+        if let Some(index) = export {
+            self.export(index);
+        }
+
+        if !self.exports.is_empty() {
+            ui.set_next_item_width(200.);
+            ui.combo_simple_string("##import_file", &mut self.import_file, &self.export_labels);
+            ui.same_line();
+            ui.set_next_item_width(50.);
+            let slot_labels: Vec<String> = (1..=SLOT_COUNT).map(|i| i.to_string()).collect();
+            ui.combo_simple_string("##import_slot", &mut self.import_slot, &slot_labels);
+            ui.same_line();
+            if ui.button("Import") {
+                self.import();
+            }
+        }
+
+        self.render_library(ui);
+        self.render_backups(ui);
+
+        if let Some(manager) = &mut self.manager {
+            manager.render(ui);
+        }
+    }
+
+    fn render_closed(&mut self, ui: &Ui) {
+        self.library_stale = true;
+        if let Some(manager) = &mut self.manager {
+            manager.render_closed(ui);
+        }
+    }
+
+    fn interact(&mut self, ui: &Ui) {
+        if self.trigger_load.take() {
+            match self.editing.as_ref().map(|(save, _, _)| save.clone()) {
+                Some(save) => self.load_library_save(&save),
+                None => self.logs.push("No library save selected".to_string()),
+            }
+        }
+        if self.trigger_import.take() {
+            if self.exports.is_empty() {
+                self.logs.push("No exported slots to import".to_string());
+            } else {
+                self.import();
+            }
+        }
+
+        if self.manager.is_none() {
+            return;
+        }
+
+        // The core manager loads on its hotkey without telling us, so the
+        // backup and quitout follow the same key. Whether the load went
+        // through is told by the save having been written, and the backup
+        // is only kept then.
+        let load = self.key_load.is_some_and(|key| key.is_pressed(ui));
+        let mut modified = None;
+        let mut backup = None;
+        if load {
+            modified = self.savefile_modified();
+            backup = self.backup();
+        }
+        if let Some(manager) = &mut self.manager {
+            manager.interact(ui);
+        }
+        if load {
+            let loaded = self.savefile_modified() != modified;
+            if loaded {
+                self.quitout();
+            }
+            self.settle_backup(backup, loaded);
+            self.refresh_backups();
+        }
+    }
+
+    fn log(&mut self, tx: Sender<String>) {
+        for log in self.logs.drain(..) {
+            tx.send(log).ok();
+        }
+        if let Some(manager) = &mut self.manager {
+            manager.log(tx);
+        }
+    }
+}
+
+impl PaletteProvider for SavefileManagerWidget {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+        vec![
+            ("Load selected library save".to_string(), Box::new(self.trigger_load.clone())),
+            ("Import selected slot file".to_string(), Box::new(self.trigger_import.clone())),
+        ]
+    }
+}
+
+pub(crate) fn savefile_manager(
+    key_load: Option<Key>,
+    key_close: Key,
+    quitout: Option<PointerChain<u8>>,
+    backup_settings: BackupSettings,
+    palette: &mut PaletteRegistry,
+) -> Box<dyn Widget> {
+    let widget = SavefileManagerWidget::new(key_load, key_close, quitout, backup_settings);
+    palette.register(&widget);
+    Box::new(widget)
 }
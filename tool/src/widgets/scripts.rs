@@ -0,0 +1,224 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use imgui::Ui;
+use libdsr::prelude::*;
+use practice_tool_core::crossbeam_channel::Sender;
+use practice_tool_core::key::Key;
+use practice_tool_core::widgets::Widget;
+
+use crate::config::FlagSpec;
+use crate::palette::{hotkeys_enabled, PaletteAction, PaletteProvider, PaletteRegistry, Trigger};
+use crate::restore::RestoreRegistry;
+use crate::scripting::{GameMemory, ScriptHost};
+use crate::util;
+
+/// The folder next to the DLL scripts are loaded from.
+const SCRIPTS_DIR: &str = "scripts";
+
+/// Character stats readable and writable by name.
+const STATS: [&str; 10] = [
+    "level",
+    "vitality",
+    "attunement",
+    "endurance",
+    "strength",
+    "dexterity",
+    "resistance",
+    "intelligence",
+    "faith",
+    "humanity",
+];
+
+/// The game's memory, with flags named as in the config and values named
+/// after the pointer chains and character stats.
+struct ChainsMemory {
+    chains: PointerChains,
+    restore: RestoreRegistry,
+}
+
+fn stat<'a>(stats: &'a mut CharacterStats, name: &str) -> Option<&'a mut i32> {
+    Some(match name {
+        "level" => &mut stats.level,
+        "vitality" => &mut stats.vitality,
+        "attunement" => &mut stats.attunement,
+        "endurance" => &mut stats.endurance,
+        "strength" => &mut stats.strength,
+        "dexterity" => &mut stats.dexterity,
+        "resistance" => &mut stats.resistance,
+        "intelligence" => &mut stats.intelligence,
+        "faith" => &mut stats.faith,
+        "humanity" => &mut stats.humanity,
+        _ => return None,
+    })
+}
+
+impl ChainsMemory {
+    fn flag_spec(&self, name: &str) -> Result<(String, &Bitflag<u8>), String> {
+        let spec = FlagSpec::try_from(name.to_string())?;
+        Ok((spec.label, (spec.getter)(&self.chains)))
+    }
+
+    fn check_stat(name: &str) -> Result<(), String> {
+        if STATS.contains(&name) {
+            Ok(())
+        } else {
+            Err(format!("\"{name}\" is not a valid value"))
+        }
+    }
+}
+
+impl GameMemory for ChainsMemory {
+    fn flag(&self, name: &str) -> Result<Option<bool>, String> {
+        Ok(self.flag_spec(name)?.1.get())
+    }
+
+    fn set_flag(&self, name: &str, state: bool) -> Result<bool, String> {
+        let (label, flag) = self.flag_spec(name)?;
+        self.restore.record(&label, flag);
+        flag.set(state);
+        Ok(flag.get() == Some(state))
+    }
+
+    fn value(&self, name: &str) -> Result<Option<f64>, String> {
+        let c = &self.chains;
+        Ok(match name {
+            "souls" => c.souls.read().map(f64::from),
+            "speed" => c.speed.read().map(f64::from),
+            "igt" => c.igt.read().map(f64::from),
+            "map_id" => c.map_id.read().map(f64::from),
+            "last_bonfire" => c.last_bonfire.read().map(f64::from),
+            name => {
+                Self::check_stat(name)?;
+                c.character_stats
+                    .read()
+                    .and_then(|mut stats| stat(&mut stats, name).map(|v| *v as f64))
+            }
+        })
+    }
+
+    fn set_value(&self, name: &str, value: f64) -> Result<bool, String> {
+        let c = &self.chains;
+        Ok(match name {
+            "souls" => c.souls.write(value as u32).is_some(),
+            "speed" => {
+                self.restore.record("Speed", &c.speed);
+                c.speed.write(value as f32).is_some()
+            }
+            "igt" => c.igt.write(value as u32).is_some(),
+            "map_id" | "last_bonfire" => return Err(format!("{name} can't be written")),
+            name => {
+                Self::check_stat(name)?;
+                c.character_stats.read().is_some_and(|mut stats| {
+                    if let Some(v) = stat(&mut stats, name) {
+                        *v = value as i32;
+                    }
+                    c.character_stats.write(stats).is_some()
+                })
+            }
+        })
+    }
+
+    fn position(&self) -> Option<([f32; 3], f32)> {
+        let (angle, pos) = &self.chains.position;
+        Some((pos.read()?, angle.read()?))
+    }
+
+    fn set_position(&self, pos: [f32; 3], angle: Option<f32>) -> bool {
+        let (ptr_angle, ptr_pos) = &self.chains.position;
+        ptr_pos.write(pos).is_some() && angle.is_none_or(|angle| ptr_angle.write(angle).is_some())
+    }
+}
+
+/// Runs the scripts of the scripts folder, and reloads them on demand.
+struct Scripts {
+    dir: PathBuf,
+    memory: Arc<dyn GameMemory>,
+    host: ScriptHost,
+    keys: Vec<(Key, usize, String)>,
+    /// Pulled by the palette to reload.
+    trigger: Trigger,
+    logs: Vec<String>,
+}
+
+impl Scripts {
+    fn reload(&mut self) {
+        self.host = ScriptHost::new(self.memory.clone());
+        self.host.load_dir(&self.dir);
+        self.keys = self
+            .host
+            .bindings()
+            .filter_map(|(script, key, callback)| match key.parse::<Key>() {
+                Ok(key) => Some((key, script, callback.to_string())),
+                Err(e) => {
+                    self.logs.push(format!("Couldn't bind {callback}() to {key}: {e}"));
+                    None
+                }
+            })
+            .collect();
+
+        let names = self.host.names().collect::<Vec<_>>();
+        if !names.is_empty() {
+            self.logs.push(format!("Loaded scripts: {}", names.join(", ")));
+        }
+    }
+}
+
+impl Widget for Scripts {
+    fn render(&mut self, ui: &Ui) {
+        if ui.button_with_size("Reload scripts", [320., 0.]) {
+            self.reload();
+        }
+    }
+
+    fn interact(&mut self, ui: &Ui) {
+        if self.trigger.take() {
+            self.reload();
+        }
+        if hotkeys_enabled() {
+            for (key, script, callback) in &self.keys {
+                if key.is_pressed(ui) {
+                    self.host.on_hotkey(*script, callback);
+                }
+            }
+        }
+        self.host.on_frame();
+    }
+
+    fn log(&mut self, tx: Sender<String>) {
+        for log in self.logs.drain(..).chain(self.host.take_logs()) {
+            tx.send(log).ok();
+        }
+    }
+}
+
+impl PaletteProvider for Scripts {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+        vec![("Reload scripts".to_string(), Box::new(self.trigger.clone()))]
+    }
+}
+
+/// The scripts of the folder next to the DLL, if there is one.
+pub(crate) fn scripts(
+    restore: &RestoreRegistry,
+    palette: &mut PaletteRegistry,
+) -> Option<Box<dyn Widget>> {
+    let dir = util::get_dll_path()?.parent()?.join(SCRIPTS_DIR);
+    if !dir.is_dir() {
+        return None;
+    }
+
+    let memory: Arc<dyn GameMemory> =
+        Arc::new(ChainsMemory { chains: PointerChains::new(), restore: restore.clone() });
+    let mut scripts = Scripts {
+        dir,
+        host: ScriptHost::new(memory.clone()),
+        memory,
+        keys: Vec::new(),
+        trigger: Trigger::default(),
+        logs: Vec::new(),
+    };
+    scripts.reload();
+    palette.register(&scripts);
+    Some(Box::new(scripts))
+}
@@ -3,6 +3,9 @@ use practice_tool_core::key::Key;
 use practice_tool_core::widgets::store_value::{ReadWrite, StoreValue};
 use practice_tool_core::widgets::Widget;
 
+use crate::palette::{PaletteAction, PaletteProvider, PaletteRegistry};
+
+#[derive(Clone)]
 struct Souls {
     ptr: PointerChain<u32>,
     current: u32,
@@ -35,6 +38,29 @@ impl ReadWrite for Souls {
     }
 }
 
-pub(crate) fn souls(amount: u32, ptr: PointerChain<u32>, key: Option<Key>) -> Box<dyn Widget> {
-    Box::new(StoreValue::new(Souls::new(amount, ptr), key))
+impl PaletteAction for Souls {
+    fn run(&mut self) -> Result<(), String> {
+        if !self.read() {
+            return Err("Couldn't read souls".to_string());
+        }
+        self.write();
+        Ok(())
+    }
+}
+
+impl PaletteProvider for Souls {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+        vec![(self.label.clone(), Box::new(self.clone()))]
+    }
+}
+
+pub(crate) fn souls(
+    amount: u32,
+    ptr: PointerChain<u32>,
+    key: Option<Key>,
+    palette: &mut PaletteRegistry,
+) -> Box<dyn Widget> {
+    let souls = Souls::new(amount, ptr);
+    palette.register(&souls);
+    Box::new(StoreValue::new(souls, key))
 }
@@ -0,0 +1,406 @@
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use imgui::Ui;
+use libdsr::prelude::*;
+use practice_tool_core::crossbeam_channel::Sender;
+use practice_tool_core::key::Key;
+use practice_tool_core::widgets::Widget;
+
+use crate::indicators::{HudProvider, Indicator, IndicatorRegistry};
+use crate::livesplit::{Command, LiveSplit};
+use crate::palette::{hotkeys_enabled, PaletteAction, PaletteProvider, PaletteRegistry, Trigger};
+use crate::splits::{
+    lss, write_delta, write_time, EventSource, IgtSource, Records, SegmentSpec, SplitOutcome,
+    SplitTimer, TimerState,
+};
+use crate::util;
+
+const COLOR_AHEAD: [f32; 4] = [0.2, 0.8, 0.3, 1.];
+const COLOR_BEHIND: [f32; 4] = [0.9, 0.25, 0.2, 1.];
+const COLOR_GOLD: [f32; 4] = [1.0, 0.8, 0.1, 1.];
+
+struct GameSource {
+    igt: PointerChain<u32>,
+    event_flags: EventFlags,
+    map_id: PointerChain<u32>,
+    last_bonfire: PointerChain<u32>,
+}
+
+impl IgtSource for GameSource {
+    fn igt(&self) -> Option<u32> {
+        self.igt.read()
+    }
+}
+
+impl EventSource for GameSource {
+    fn event_flag(&self, id: u32) -> Option<bool> {
+        self.event_flags.get(id)
+    }
+
+    fn map_id(&self) -> Option<u32> {
+        self.map_id.read()
+    }
+
+    fn last_bonfire(&self) -> Option<u32> {
+        self.last_bonfire.read()
+    }
+}
+
+struct Splits {
+    timer: Arc<Mutex<SplitTimer>>,
+    game: GameSource,
+    key_split: Option<Key>,
+    key_reset: Option<Key>,
+    key_skip: Option<Key>,
+    key_undo: Option<Key>,
+    /// Pulled by the palette, in the order of the keys above.
+    trigger_split: Trigger,
+    trigger_reset: Trigger,
+    trigger_skip: Trigger,
+    trigger_undo: Trigger,
+    records_path: Option<PathBuf>,
+    lss_path: Option<PathBuf>,
+    livesplit: Option<LiveSplit>,
+    logs: Vec<String>,
+}
+
+impl Splits {
+    fn split(&mut self) {
+        let Some(igt) = self.game.igt() else {
+            return;
+        };
+        let outcome = self.timer.lock().unwrap().split(igt);
+        self.report(outcome);
+    }
+
+    fn skip(&mut self) {
+        if self.timer.lock().unwrap().skip() {
+            self.forward(Command::SkipSplit);
+        }
+    }
+
+    fn undo(&mut self) {
+        if self.timer.lock().unwrap().undo() {
+            self.forward(Command::Unsplit);
+        }
+    }
+
+    fn reset(&mut self) {
+        if self.timer.lock().unwrap().reset() {
+            self.forward(Command::Reset);
+        }
+    }
+
+    fn forward(&self, command: Command) {
+        if let Some(livesplit) = &self.livesplit {
+            livesplit.send(command);
+        }
+    }
+
+    /// Makes the game time sent to LiveSplit count from the start of the run.
+    fn sync_origin(&self) {
+        let Some(livesplit) = &self.livesplit else {
+            return;
+        };
+        match self.timer.lock().unwrap().state() {
+            TimerState::Running(start) | TimerState::Finished(start) => livesplit.set_origin(start),
+            TimerState::NotStarted => livesplit.set_origin(0),
+        }
+    }
+
+    fn report(&mut self, outcome: Option<SplitOutcome>) {
+        match outcome {
+            Some(SplitOutcome::Started) => self.forward(Command::StartTimer),
+            Some(_) => self.forward(Command::Split),
+            None => {}
+        }
+
+        let timer = self.timer.lock().unwrap();
+        let mut msg = String::new();
+
+        match outcome {
+            Some(SplitOutcome::Started) => msg.push_str("Timer started"),
+            Some(SplitOutcome::Split { index, delta, gold }) => {
+                write!(msg, "{}: ", timer.segments()[index].name).ok();
+                write_time(&mut msg, timer.split_time(index).unwrap_or(0));
+                if let Some(delta) = delta {
+                    msg.push_str(" (");
+                    write_delta(&mut msg, delta);
+                    msg.push(')');
+                }
+                if gold {
+                    msg.push_str(" gold!");
+                }
+            }
+            Some(SplitOutcome::Finished { personal_best, .. }) => {
+                msg.push_str("Run finished: ");
+                let last = timer.segments().len() - 1;
+                write_time(&mut msg, timer.split_time(last).unwrap_or(0));
+                if personal_best {
+                    msg.push_str(" new personal best!");
+                }
+            }
+            None => return,
+        }
+
+        drop(timer);
+        self.logs.push(msg);
+    }
+
+    fn save_records(&mut self) {
+        let Some(path) = &self.records_path else {
+            return;
+        };
+        if let Err(e) = self.timer.lock().unwrap().records().save(path) {
+            self.logs.push(e);
+        }
+    }
+
+    fn import_lss(&mut self) {
+        let Some(path) = &self.lss_path else {
+            return;
+        };
+        let result = std::fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read {}: {e}", path.display()))
+            .and_then(|xml| lss::import(&xml));
+        match result {
+            Ok(records) => {
+                self.timer.lock().unwrap().import(&records);
+                self.logs
+                    .push(format!("Imported splits from {}", path.display()));
+            }
+            Err(e) => self.logs.push(e),
+        }
+    }
+
+    fn export_lss(&mut self) {
+        let Some(path) = &self.lss_path else {
+            return;
+        };
+        let xml = lss::export(&self.timer.lock().unwrap().records());
+        match std::fs::write(path, xml) {
+            Ok(()) => self
+                .logs
+                .push(format!("Exported splits to {}", path.display())),
+            Err(e) => self
+                .logs
+                .push(format!("Couldn't write {}: {e}", path.display())),
+        }
+    }
+}
+
+impl Widget for Splits {
+    fn render(&mut self, ui: &Ui) {
+        let button_width = (320. - ui.clone_style().item_spacing[0] * 3.) / 4.;
+
+        ui.text("Splits");
+        if ui.button_with_size("Split", [button_width, 0.]) {
+            self.split();
+        }
+        ui.same_line();
+        if ui.button_with_size("Skip", [button_width, 0.]) {
+            self.skip();
+        }
+        ui.same_line();
+        if ui.button_with_size("Undo", [button_width, 0.]) {
+            self.undo();
+        }
+        ui.same_line();
+        if ui.button_with_size("Reset", [button_width, 0.]) {
+            self.reset();
+        }
+
+        let button_width = (320. - ui.clone_style().item_spacing[0]) / 2.;
+        if ui.button_with_size("Import .lss", [button_width, 0.]) {
+            self.import_lss();
+        }
+        ui.same_line();
+        if ui.button_with_size("Export .lss", [button_width, 0.]) {
+            self.export_lss();
+        }
+    }
+
+    fn interact(&mut self, ui: &Ui) {
+        let pressed = |key: Option<Key>| hotkeys_enabled() && key.is_some_and(|k| k.is_pressed(ui));
+        if pressed(self.key_split) || self.trigger_split.take() {
+            self.split();
+        }
+        if pressed(self.key_skip) || self.trigger_skip.take() {
+            self.skip();
+        }
+        if pressed(self.key_undo) || self.trigger_undo.take() {
+            self.undo();
+        }
+        if pressed(self.key_reset) || self.trigger_reset.take() {
+            self.reset();
+            self.logs.push("Timer reset".to_string());
+        }
+
+        let outcome = self.timer.lock().unwrap().update(&self.game, &self.game);
+        self.report(outcome);
+
+        if self.timer.lock().unwrap().take_dirty() {
+            self.save_records();
+        }
+        self.sync_origin();
+    }
+
+    fn log(&mut self, tx: Sender<String>) {
+        for log in self.logs.drain(..) {
+            tx.send(log).ok();
+        }
+    }
+}
+
+impl HudProvider for Splits {
+    fn hud_indicators(&self) -> Vec<(&'static str, Box<dyn Indicator>)> {
+        vec![(
+            "splits",
+            Box::new(SplitsIndicator {
+                timer: Arc::clone(&self.timer),
+                igt: self.game.igt.clone(),
+                buf: String::new(),
+            }),
+        )]
+    }
+}
+
+/// Shows the run time and the segments around the current one, with the
+/// difference to the personal best.
+struct SplitsIndicator {
+    timer: Arc<Mutex<SplitTimer>>,
+    igt: PointerChain<u32>,
+    buf: String,
+}
+
+impl Indicator for SplitsIndicator {
+    fn label(&self) -> &str {
+        "Splits"
+    }
+
+    fn render(&mut self, ui: &Ui) {
+        let timer = self.timer.lock().unwrap();
+        let igt = self.igt.read().unwrap_or(0);
+
+        let first = timer.current().saturating_sub(2);
+        for (index, segment) in timer.segments().iter().enumerate().skip(first).take(5) {
+            let done = match timer.state() {
+                TimerState::Running(_) => index < timer.current(),
+                TimerState::Finished(_) => true,
+                TimerState::NotStarted => false,
+            };
+
+            ui.text(&segment.name);
+
+            self.buf.clear();
+            match (done, timer.delta(index)) {
+                (true, Some(delta)) => {
+                    write_delta(&mut self.buf, delta);
+                    let color = if timer.is_gold(index) {
+                        COLOR_GOLD
+                    } else if delta < 0 {
+                        COLOR_AHEAD
+                    } else {
+                        COLOR_BEHIND
+                    };
+                    ui.same_line_with_pos(160.);
+                    ui.text_colored(color, &self.buf);
+                    self.buf.clear();
+                }
+                (true, None) if timer.is_gold(index) => {
+                    ui.same_line_with_pos(160.);
+                    ui.text_colored(COLOR_GOLD, "gold");
+                }
+                _ => {}
+            }
+
+            let time = if done {
+                timer.split_time(index)
+            } else {
+                segment.personal_best
+            };
+            match time {
+                Some(time) => write_time(&mut self.buf, time),
+                None => self.buf.push('-'),
+            }
+            ui.same_line_with_pos(240.);
+            ui.text(&self.buf);
+        }
+
+        self.buf.clear();
+        self.buf.push_str("Timer ");
+        write_time(&mut self.buf, timer.time(igt).unwrap_or(0));
+        ui.text(&self.buf);
+    }
+}
+
+impl PaletteProvider for Splits {
+    fn palette_actions(&self) -> Vec<(String, Box<dyn PaletteAction>)> {
+        vec![
+            ("Split".to_string(), Box::new(self.trigger_split.clone())),
+            ("Skip split".to_string(), Box::new(self.trigger_skip.clone())),
+            ("Undo split".to_string(), Box::new(self.trigger_undo.clone())),
+            ("Reset splits".to_string(), Box::new(self.trigger_reset.clone())),
+        ]
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn splits(
+    segments: Vec<SegmentSpec>,
+    chains: &PointerChains,
+    key_split: Option<Key>,
+    key_reset: Option<Key>,
+    key_skip: Option<Key>,
+    key_undo: Option<Key>,
+    indicators: &mut IndicatorRegistry,
+    livesplit: Option<LiveSplit>,
+    palette: &mut PaletteRegistry,
+) -> Box<dyn Widget> {
+    let dll_file = |name: &str| {
+        util::get_dll_path().map(|mut path| {
+            path.pop();
+            path.push(name);
+            path
+        })
+    };
+
+    let records_path = dll_file("dark_souls_remastered_tool.splits.json");
+    let mut logs = Vec::new();
+    let records = match records_path.as_deref().map(Records::load) {
+        Some(Ok(records)) => records,
+        Some(Err(e)) => {
+            logs.push(e);
+            Records::default()
+        }
+        None => Records::default(),
+    };
+
+    let splits = Splits {
+        timer: Arc::new(Mutex::new(SplitTimer::new(segments, &records))),
+        game: GameSource {
+            igt: chains.igt.clone(),
+            event_flags: chains.event_flags.clone(),
+            map_id: chains.map_id.clone(),
+            last_bonfire: chains.last_bonfire.clone(),
+        },
+        key_split,
+        key_reset,
+        key_skip,
+        key_undo,
+        trigger_split: Trigger::default(),
+        trigger_reset: Trigger::default(),
+        trigger_skip: Trigger::default(),
+        trigger_undo: Trigger::default(),
+        records_path,
+        lss_path: dll_file("dark_souls_remastered_tool.lss"),
+        livesplit,
+        logs,
+    };
+
+    indicators.register(&splits);
+    palette.register(&splits);
+    Box::new(splits)
+}
@@ -7,8 +7,9 @@ use std::{
 use super::codegen::{self, aob_indirect_twice};
 
 fn patches_paths() -> impl Iterator<Item = PathBuf> {
-    // let string_path = env::var("DSR_PATCHES_PATH").unwrap_or_else(|_| panic!());
-    let base_path = PathBuf::from(r"D:\SteamLibrary\steamapps\common");
+    let base_path = env::var("DSR_PATCHES_PATH")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(r"D:\SteamLibrary\steamapps\common"));
     base_path
         .read_dir()
         .expect("Couldn't scan patches directory")
@@ -60,6 +61,27 @@ pub fn get_base_addresses() {
             7,
             true,
         ),
+        aob_indirect_twice(
+            "EventFlags",
+            &["48 8B 0D ? ? ? ? 99 33 C2 45 33 C0 2B C2 8D 50 F6"],
+            3,
+            7,
+            true,
+        ),
+        aob_indirect_twice(
+            "GameMan",
+            &["48 8B 05 ? ? ? ? C6 40 18 00"],
+            3,
+            7,
+            true,
+        ),
+        aob_indirect_twice(
+            "FieldArea",
+            &["48 8B 0D ? ? ? ? 48 85 C9 74 26 44 8B"],
+            3,
+            7,
+            true,
+        ),
         // aob_indirect_twice(
         //     "MenuManBase",
         //     &["48 8B 05 ? ? ? ? 89 88 28 08 00 00 85 C9"],